@@ -0,0 +1,53 @@
+use serde::de::DeserializeOwned;
+use std::{
+    env, fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+// Set to load assets from somewhere else, e.g. a copy being worked on
+const ASSETS_ENV: &str = "SPACEVAGRANT_ASSETS";
+
+// Root directory for all game data files
+// That's the assets directory next to the executable, so a build can be copied anywhere along with it
+// Development builds use the one in the source tree instead, so they run straight out of cargo
+pub fn assets_dir() -> PathBuf {
+    if let Some(dir) = env::var_os(ASSETS_ENV) {
+        return PathBuf::from(dir);
+    }
+    if cfg!(debug_assertions) {
+        return PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/assets"));
+    }
+    env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join("assets")))
+        .unwrap_or_else(|| PathBuf::from("assets"))
+}
+
+pub fn asset_path(path: impl AsRef<Path>) -> PathBuf {
+    assets_dir().join(path)
+}
+
+#[derive(Debug)]
+pub enum AssetError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, ron::Error),
+    // The file parsed but what's in it doesn't make sense
+    Invalid(PathBuf, String),
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AssetError::Io(path, e) => write!(f, "failed to read {}: {}", path.display(), e),
+            AssetError::Parse(path, e) => write!(f, "failed to parse {}: {}", path.display(), e),
+            AssetError::Invalid(path, e) => write!(f, "invalid data in {}: {}", path.display(), e),
+        }
+    }
+}
+
+// Load and deserialize a RON data file, relative to the assets directory
+pub fn load_ron<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<T, AssetError> {
+    let path = asset_path(path);
+    let contents = fs::read_to_string(&path).map_err(|e| AssetError::Io(path.clone(), e))?;
+    ron::de::from_str(&contents).map_err(|e| AssetError::Parse(path, e))
+}
@@ -0,0 +1,118 @@
+use crate::{economy::Commodity, rng::Rng};
+
+// Units of extractable material per unit of asteroid area
+const YIELD_PER_AREA: f32 = 6.0;
+const MIN_RADIUS: f32 = 1.0;
+const MAX_RADIUS: f32 = 4.0;
+// Depleted asteroids shrink down to this fraction of their original size before disappearing
+const DEPLETED_SCALE: f32 = 0.3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AsteroidKind {
+    Rocky,
+    Icy,
+    Metallic,
+}
+
+impl AsteroidKind {
+    // What the rock is made of, as fractions of everything mined from it
+    pub fn composition(self) -> &'static [(Commodity, f32)] {
+        match self {
+            AsteroidKind::Rocky => &[(Commodity::Ore, 1.0)],
+            AsteroidKind::Icy => &[(Commodity::Water, 0.8), (Commodity::Ore, 0.2)],
+            AsteroidKind::Metallic => &[(Commodity::Ore, 0.6), (Commodity::Metals, 0.4)],
+        }
+    }
+
+    // How much the rock resists mining, extraction rates are divided by this
+    pub fn hardness(self) -> f32 {
+        match self {
+            AsteroidKind::Rocky => 1.0,
+            AsteroidKind::Icy => 0.6,
+            AsteroidKind::Metallic => 1.8,
+        }
+    }
+}
+
+pub struct Asteroid {
+    pub kind: AsteroidKind,
+    pub position: na::Vector2<f32>,
+    pub angle: f32,
+    initial_radius: f32,
+    initial_yield: f32,
+    remaining: f32,
+}
+
+impl Asteroid {
+    pub fn new(kind: AsteroidKind, position: na::Vector2<f32>, angle: f32, radius: f32) -> Self {
+        let initial_yield = radius * radius * YIELD_PER_AREA;
+        Self {
+            kind,
+            position,
+            angle,
+            initial_radius: radius,
+            initial_yield,
+            remaining: initial_yield,
+        }
+    }
+
+    // Scatter a cluster of asteroids around a point
+    pub fn generate_field(
+        rng: &mut Rng,
+        center: na::Vector2<f32>,
+        spread: f32,
+        count: usize,
+    ) -> Vec<Self> {
+        (0..count)
+            .map(|_| {
+                let angle = rng.range_f32(0.0, std::f32::consts::PI * 2.0);
+                // sqrt keeps the density even across the field instead of bunching in the middle
+                let distance = rng.next_f32().sqrt() * spread;
+                let kind = match rng.below(10) {
+                    0..=5 => AsteroidKind::Rocky,
+                    6..=8 => AsteroidKind::Icy,
+                    _ => AsteroidKind::Metallic,
+                };
+                Self::new(
+                    kind,
+                    center + na::Vector2::new(angle.cos(), angle.sin()) * distance,
+                    rng.range_f32(0.0, std::f32::consts::PI * 2.0),
+                    rng.range_f32(MIN_RADIUS, MAX_RADIUS),
+                )
+            })
+            .collect()
+    }
+
+    // The asteroid shrinks as it's mined out
+    pub fn radius(&self) -> f32 {
+        let left = self.remaining / self.initial_yield;
+        self.initial_radius * (DEPLETED_SCALE + (1.0 - DEPLETED_SCALE) * left.sqrt())
+    }
+
+    pub fn remaining(&self) -> f32 {
+        self.remaining
+    }
+
+    pub fn depleted(&self) -> bool {
+        self.remaining <= 0.0
+    }
+
+    // Take up to the given amount of material out of the rock, returning how much was actually taken
+    pub fn extract(&mut self, amount: f32) -> f32 {
+        let taken = amount.min(self.remaining);
+        self.remaining -= taken;
+        taken
+    }
+
+    // Distance along a ray to where it first hits this asteroid, if it does at all
+    pub fn ray_hit(&self, origin: na::Vector2<f32>, direction: na::Vector2<f32>) -> Option<f32> {
+        let to_center = self.position - origin;
+        let along = to_center.dot(&direction);
+        let radius = self.radius();
+        let miss_squared = to_center.norm_squared() - along * along;
+        if along < 0.0 || miss_squared > radius * radius {
+            return None;
+        }
+        Some((along - (radius * radius - miss_squared).sqrt()).max(0.0))
+    }
+}
@@ -0,0 +1,710 @@
+use crate::rng::Rng;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// How strongly prices react to the difference between current and target stock
+const PRICE_ELASTICITY: f32 = 0.6;
+// Prices are always kept within these multiples of the base price so shortages and gluts can never run away
+const MIN_PRICE_FACTOR: f32 = 0.25;
+const MAX_PRICE_FACTOR: f32 = 4.0;
+// Difference between what a station charges and what it pays, as a fraction of the mid price
+const PRICE_SPREAD: f32 = 0.05;
+// Production is throttled once a station is sitting on this many times its target stock of an output
+const OVERSTOCK_FACTOR: f32 = 2.0;
+// Fraction of any stock above target that leaves the market every tick (spoilage, exports out of the system, etc.)
+// Traders carry goods away before a producer notices it's overstocked, so without this, popular goods would glut everywhere
+const EXCESS_DECAY: f32 = 0.01;
+
+const STATION_STOCK_CAPACITY: f32 = 1000.0;
+const DEFAULT_TARGET_STOCK: f32 = 50.0;
+const TRADED_TARGET_STOCK: f32 = 200.0;
+
+const TRADER_COUNT: usize = 30;
+const TRADER_CAPACITY: u32 = 200;
+// World units per second
+const TRADER_SPEED: f32 = 12.0;
+// Credits per world unit travelled, used when ranking routes
+const TRADER_COST_PER_DISTANCE: f32 = 0.5;
+// Traders only buy stock above this fraction of a station's target, so they move surpluses around rather than strip markets bare
+const TRADER_RESERVE: f32 = 1.0;
+
+// Every tradeable good in the game
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Commodity {
+    Ore,
+    Metals,
+    Water,
+    Food,
+    Fuel,
+    Electronics,
+    Machinery,
+    Medicine,
+}
+
+impl Commodity {
+    pub const COUNT: usize = 8;
+    pub const ALL: [Commodity; Commodity::COUNT] = [
+        Commodity::Ore,
+        Commodity::Metals,
+        Commodity::Water,
+        Commodity::Food,
+        Commodity::Fuel,
+        Commodity::Electronics,
+        Commodity::Machinery,
+        Commodity::Medicine,
+    ];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Commodity::Ore => "Ore",
+            Commodity::Metals => "Metals",
+            Commodity::Water => "Water",
+            Commodity::Food => "Food",
+            Commodity::Fuel => "Fuel",
+            Commodity::Electronics => "Electronics",
+            Commodity::Machinery => "Machinery",
+            Commodity::Medicine => "Medicine",
+        }
+    }
+
+    // The "fair" price of a commodity when a market's supply exactly matches its demand
+    pub fn base_price(self) -> f32 {
+        match self {
+            Commodity::Ore => 10.0,
+            Commodity::Metals => 30.0,
+            Commodity::Water => 5.0,
+            Commodity::Food => 12.0,
+            Commodity::Fuel => 20.0,
+            Commodity::Electronics => 80.0,
+            Commodity::Machinery => 120.0,
+            Commodity::Medicine => 60.0,
+        }
+    }
+}
+
+// Per-commodity quantities, indexed by Commodity::index
+pub type Stock = [f32; Commodity::COUNT];
+
+// A production process a station runs every tick
+// Consumes the inputs and produces the outputs, scaled down if the inputs aren't fully available
+pub struct Recipe {
+    pub inputs: &'static [(Commodity, f32)],
+    pub outputs: &'static [(Commodity, f32)],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StationKind {
+    MiningOutpost,
+    Refinery,
+    Agricultural,
+    Industrial,
+    TradeHub,
+}
+
+impl StationKind {
+    pub const ALL: [StationKind; 5] = [
+        StationKind::MiningOutpost,
+        StationKind::Refinery,
+        StationKind::Agricultural,
+        StationKind::Industrial,
+        StationKind::TradeHub,
+    ];
+
+    pub fn recipes(self) -> &'static [Recipe] {
+        use Commodity::*;
+        match self {
+            StationKind::MiningOutpost => &[
+                Recipe {
+                    inputs: &[],
+                    outputs: &[(Ore, 8.0)],
+                },
+                Recipe {
+                    inputs: &[],
+                    outputs: &[(Water, 8.0)],
+                },
+            ],
+            StationKind::Refinery => &[
+                Recipe {
+                    inputs: &[(Ore, 3.0)],
+                    outputs: &[(Metals, 1.5)],
+                },
+                Recipe {
+                    inputs: &[(Water, 2.0)],
+                    outputs: &[(Fuel, 2.0)],
+                },
+            ],
+            StationKind::Agricultural => &[
+                Recipe {
+                    inputs: &[(Water, 2.0)],
+                    outputs: &[(Food, 2.0)],
+                },
+                Recipe {
+                    inputs: &[(Food, 0.5), (Water, 0.5)],
+                    outputs: &[(Medicine, 0.25)],
+                },
+            ],
+            StationKind::Industrial => &[
+                Recipe {
+                    inputs: &[(Metals, 1.0), (Fuel, 0.5)],
+                    outputs: &[(Machinery, 0.25)],
+                },
+                Recipe {
+                    inputs: &[(Metals, 0.5)],
+                    outputs: &[(Electronics, 0.25)],
+                },
+            ],
+            StationKind::TradeHub => &[],
+        }
+    }
+
+    // Goods used up every tick just by the station existing (people eating, machines wearing out, etc.)
+    // This is what keeps goods flowing, without sinks everything would pile up and prices would collapse
+    pub fn upkeep(self) -> &'static [(Commodity, f32)] {
+        use Commodity::*;
+        match self {
+            StationKind::MiningOutpost => &[(Food, 0.3), (Machinery, 0.05), (Fuel, 0.2)],
+            StationKind::Refinery => &[(Food, 0.3), (Machinery, 0.05)],
+            StationKind::Agricultural => &[(Machinery, 0.02), (Fuel, 0.1)],
+            StationKind::Industrial => &[(Food, 0.5), (Water, 0.3), (Electronics, 0.02)],
+            StationKind::TradeHub => &[
+                (Food, 1.0),
+                (Water, 0.5),
+                (Medicine, 0.1),
+                (Electronics, 0.1),
+                (Fuel, 0.3),
+            ],
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            StationKind::MiningOutpost => "Mining Outpost",
+            StationKind::Refinery => "Refinery",
+            StationKind::Agricultural => "Agri Dome",
+            StationKind::Industrial => "Foundry",
+            StationKind::TradeHub => "Trade Hub",
+        }
+    }
+}
+
+// The goods a station holds and the prices it offers for them
+pub struct Market {
+    pub stock: Stock,
+    // The stock level at which the market considers itself balanced
+    // Prices rise when stock is below this and fall when above it
+    pub target: Stock,
+    pub capacity: f32,
+}
+
+impl Market {
+    fn new(kind: StationKind) -> Self {
+        let mut target = [DEFAULT_TARGET_STOCK; Commodity::COUNT];
+        // Stations care more about the things they make and use, so keep bigger buffers of those
+        for recipe in kind.recipes() {
+            for &(commodity, _) in recipe.inputs.iter().chain(recipe.outputs) {
+                target[commodity.index()] = TRADED_TARGET_STOCK;
+            }
+        }
+        for &(commodity, _) in kind.upkeep() {
+            target[commodity.index()] = TRADED_TARGET_STOCK;
+        }
+
+        Self {
+            stock: target,
+            target,
+            capacity: STATION_STOCK_CAPACITY,
+        }
+    }
+
+    // Mid price of a commodity, before the buy/sell spread is applied
+    pub fn price(&self, commodity: Commodity) -> f32 {
+        let i = commodity.index();
+        let ratio = (self.target[i] + 1.0) / (self.stock[i] + 1.0);
+        commodity.base_price()
+            * ratio
+                .powf(PRICE_ELASTICITY)
+                .clamp(MIN_PRICE_FACTOR, MAX_PRICE_FACTOR)
+    }
+
+    // What the station charges for one unit
+    pub fn buy_price(&self, commodity: Commodity) -> u32 {
+        ((self.price(commodity) * (1.0 + PRICE_SPREAD)).ceil() as u32).max(1)
+    }
+
+    // What the station pays for one unit
+    pub fn sell_price(&self, commodity: Commodity) -> u32 {
+        (self.price(commodity) * (1.0 - PRICE_SPREAD)).floor() as u32
+    }
+
+    fn add(&mut self, commodity: Commodity, amount: f32) {
+        let stock = &mut self.stock[commodity.index()];
+        *stock = (*stock + amount).min(self.capacity);
+    }
+
+    fn remove(&mut self, commodity: Commodity, amount: f32) {
+        let stock = &mut self.stock[commodity.index()];
+        *stock = (*stock - amount).max(0.0);
+    }
+}
+
+pub type StationId = usize;
+
+pub struct Station {
+    pub name: String,
+    pub kind: StationKind,
+    pub position: na::Vector2<f32>,
+    pub market: Market,
+}
+
+impl Station {
+    fn update(&mut self, rng: &mut Rng) {
+        for recipe in self.kind.recipes() {
+            // Run the recipe as much as the inputs allow, with a bit of jitter so the markets aren't perfectly static
+            let mut throughput = rng.range_f32(0.9, 1.1);
+            for &(commodity, amount) in recipe.inputs {
+                throughput = throughput.min(self.market.stock[commodity.index()] / amount);
+            }
+            // Don't bother producing if we're already swimming in the output
+            for &(commodity, _) in recipe.outputs {
+                let i = commodity.index();
+                if self.market.stock[i] >= self.market.target[i] * OVERSTOCK_FACTOR {
+                    throughput = 0.0;
+                }
+            }
+            if throughput <= 0.0 {
+                continue;
+            }
+
+            for &(commodity, amount) in recipe.inputs {
+                self.market.remove(commodity, amount * throughput);
+            }
+            for &(commodity, amount) in recipe.outputs {
+                self.market.add(commodity, amount * throughput);
+            }
+        }
+
+        for &(commodity, amount) in self.kind.upkeep() {
+            self.market.remove(commodity, amount);
+        }
+
+        for i in 0..Commodity::COUNT {
+            let excess = self.market.stock[i] - self.market.target[i];
+            if excess > 0.0 {
+                self.market.stock[i] -= excess * EXCESS_DECAY;
+            }
+        }
+    }
+}
+
+// A list of goods and amounts being carried by a trader
+pub type Manifest = Vec<(Commodity, u32)>;
+
+// An NPC hauler that moves goods between stations looking for profit
+pub struct Trader {
+    pub location: StationId,
+    pub destination: Option<StationId>,
+    pub cargo: Manifest,
+    pub capacity: u32,
+    travel_remaining: f32,
+}
+
+impl Trader {
+    // Traders fly straight from station to station, so this is worked out from how much of the trip is left
+    // None while docked
+    pub fn position(&self, stations: &[Station]) -> Option<na::Vector2<f32>> {
+        let to = stations[self.destination?].position;
+        let offset = to - stations[self.location].position;
+        let distance = offset.norm();
+        if distance <= 0.0 {
+            return Some(to);
+        }
+        let remaining = (self.travel_remaining * TRADER_SPEED).min(distance);
+        Some(to - offset / distance * remaining)
+    }
+}
+
+// Player side storage for goods
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CargoHold {
+    pub capacity: u32,
+    items: [u32; Commodity::COUNT],
+}
+
+impl CargoHold {
+    pub fn new(capacity: u32) -> Self {
+        Self {
+            capacity,
+            items: [0; Commodity::COUNT],
+        }
+    }
+
+    pub fn amount(&self, commodity: Commodity) -> u32 {
+        self.items[commodity.index()]
+    }
+
+    pub fn used(&self) -> u32 {
+        self.items.iter().sum()
+    }
+
+    pub fn free(&self) -> u32 {
+        self.capacity.saturating_sub(self.used())
+    }
+
+    // Adds as much as fits, returning how much was actually added
+    pub fn add(&mut self, commodity: Commodity, amount: u32) -> u32 {
+        let added = amount.min(self.free());
+        self.items[commodity.index()] += added;
+        added
+    }
+
+    // Removes the whole amount, or nothing if there isn't enough
+    pub fn remove(&mut self, commodity: Commodity, amount: u32) -> bool {
+        let item = &mut self.items[commodity.index()];
+        if *item < amount {
+            return false;
+        }
+        *item -= amount;
+        true
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TradeError {
+    NotDocked,
+    OutOfStock,
+    InsufficientCredits,
+    CargoFull,
+    NotEnoughCargo,
+}
+
+impl fmt::Display for TradeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            TradeError::NotDocked => "not docked at a station",
+            TradeError::OutOfStock => "station doesn't have enough in stock",
+            TradeError::InsufficientCredits => "not enough credits",
+            TradeError::CargoFull => "not enough cargo space",
+            TradeError::NotEnoughCargo => "not enough of that commodity in the hold",
+        })
+    }
+}
+
+// The simulated economy of every station and trader in the game
+// Runs in fixed size ticks independent of the frame rate, so the same seed and tick count always gives the same result
+// This also means it can be run headless (see `simulate`) to check for long term problems like runaway inflation
+pub struct Economy {
+    pub stations: Vec<Station>,
+    pub traders: Vec<Trader>,
+    accumulator: f32,
+    rng: Rng,
+}
+
+impl Economy {
+    // Length of one economy tick in seconds of game time
+    pub const TICK: f32 = 1.0;
+
+    // Stations are named after the system they're in
+    pub fn new(seed: u64, system_name: &str) -> Self {
+        let mut rng = Rng::new(seed);
+
+        // One of every kind of station, plus a couple of extra producers so there's competition between markets
+        let kinds = StationKind::ALL.iter().copied().chain(
+            [StationKind::MiningOutpost, StationKind::Agricultural]
+                .iter()
+                .copied(),
+        );
+        let stations = kinds
+            .enumerate()
+            .map(|(i, kind)| {
+                let angle = rng.range_f32(0.0, std::f32::consts::PI * 2.0);
+                let distance = rng.range_f32(30.0, 150.0);
+                Station {
+                    name: format!("{} {} {}", system_name, kind.name(), i + 1),
+                    kind,
+                    position: na::Vector2::new(angle.cos(), angle.sin()) * distance,
+                    market: Market::new(kind),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let traders = (0..TRADER_COUNT)
+            .map(|_| Trader {
+                location: rng.below(stations.len()),
+                destination: None,
+                cargo: Vec::new(),
+                capacity: TRADER_CAPACITY,
+                travel_remaining: 0.0,
+            })
+            .collect();
+
+        Self {
+            stations,
+            traders,
+            accumulator: 0.0,
+            rng,
+        }
+    }
+
+    // Advance the economy by an amount of real time, running as many whole ticks as fit
+    pub fn update(&mut self, delta: f32) {
+        self.accumulator += delta;
+        while self.accumulator >= Self::TICK {
+            self.accumulator -= Self::TICK;
+            self.tick();
+        }
+    }
+
+    // Run a number of ticks back to back, with no rendering or game involved
+    pub fn simulate(&mut self, ticks: u64) {
+        for _ in 0..ticks {
+            self.tick();
+        }
+    }
+
+    pub fn tick(&mut self) {
+        let Self { stations, rng, .. } = self;
+        for station in stations.iter_mut() {
+            station.update(rng);
+        }
+
+        for i in 0..self.traders.len() {
+            self.update_trader(i);
+        }
+    }
+
+    // Buy goods from a station's market into the cargo hold
+    // Either the whole amount is bought or nothing is, and every unit bought pushes the price up a little
+    // Returns the total cost
+    pub fn buy(
+        &mut self,
+        station: StationId,
+        commodity: Commodity,
+        amount: u32,
+        credits: &mut u32,
+        cargo: &mut CargoHold,
+    ) -> Result<u32, TradeError> {
+        let market = &mut self.stations[station].market;
+        if cargo.free() < amount {
+            return Err(TradeError::CargoFull);
+        }
+        if market.stock[commodity.index()] < amount as f32 {
+            return Err(TradeError::OutOfStock);
+        }
+
+        let stock = market.stock[commodity.index()];
+        let mut total = 0;
+        for _ in 0..amount {
+            total += market.buy_price(commodity);
+            market.stock[commodity.index()] -= 1.0;
+        }
+        if total > *credits {
+            market.stock[commodity.index()] = stock;
+            return Err(TradeError::InsufficientCredits);
+        }
+
+        *credits -= total;
+        cargo.add(commodity, amount);
+        Ok(total)
+    }
+
+    // Sell goods from the cargo hold to a station's market
+    // Every unit sold pushes the price down a little
+    // Returns the total payment
+    pub fn sell(
+        &mut self,
+        station: StationId,
+        commodity: Commodity,
+        amount: u32,
+        credits: &mut u32,
+        cargo: &mut CargoHold,
+    ) -> Result<u32, TradeError> {
+        if !cargo.remove(commodity, amount) {
+            return Err(TradeError::NotEnoughCargo);
+        }
+
+        let market = &mut self.stations[station].market;
+        let mut total = 0;
+        for _ in 0..amount {
+            total += market.sell_price(commodity);
+            market.add(commodity, 1.0);
+        }
+
+        *credits += total;
+        Ok(total)
+    }
+
+    fn update_trader(&mut self, index: usize) {
+        let trader = &mut self.traders[index];
+
+        // Still on the way somewhere
+        if let Some(destination) = trader.destination {
+            trader.travel_remaining -= Self::TICK;
+            if trader.travel_remaining > 0.0 {
+                return;
+            }
+
+            // Arrived, unload everything into the destination market
+            trader.location = destination;
+            trader.destination = None;
+            for (commodity, amount) in trader.cargo.drain(..) {
+                self.stations[destination]
+                    .market
+                    .add(commodity, amount as f32);
+            }
+            return;
+        }
+
+        // Docked, look for the most profitable run
+        // A run can start from another station, in which case we fly there empty first,
+        // so runs are ranked by profit per second of travel rather than raw profit
+        let location = trader.location;
+        let mut best_rate = 0.0;
+        let mut best: Option<(StationId, StationId, Manifest)> = None;
+        for (origin, from) in self.stations.iter().enumerate() {
+            let approach = (from.position - self.stations[location].position).norm();
+            for (destination, to) in self.stations.iter().enumerate() {
+                if destination == origin {
+                    continue;
+                }
+                let distance = approach + (to.position - from.position).norm();
+                let (gross, manifest) = plan_manifest(&from.market, &to.market, trader.capacity);
+                let profit = gross - distance * TRADER_COST_PER_DISTANCE;
+                let rate = profit / (distance / TRADER_SPEED).max(Self::TICK);
+                if profit > 0.0 && (best.is_none() || rate > best_rate) {
+                    best_rate = rate;
+                    best = Some((origin, destination, manifest));
+                }
+            }
+        }
+
+        match best {
+            // The best run starts here, load up and go
+            Some((origin, destination, manifest)) if origin == location => {
+                let distance =
+                    (self.stations[destination].position - self.stations[location].position).norm();
+                for &(commodity, amount) in &manifest {
+                    self.stations[location]
+                        .market
+                        .remove(commodity, amount as f32);
+                }
+                trader.cargo = manifest;
+                trader.destination = Some(destination);
+                trader.travel_remaining = distance / TRADER_SPEED;
+            }
+            // The best run starts somewhere else, head there empty
+            Some((origin, ..)) => {
+                let distance =
+                    (self.stations[origin].position - self.stations[location].position).norm();
+                trader.destination = Some(origin);
+                trader.travel_remaining = distance / TRADER_SPEED;
+            }
+            None => (),
+        }
+    }
+}
+
+// Work out what to carry from one market to another to make the most money
+// Fills the hold with whatever has the best margin first, returns the gross profit and the goods to carry
+fn plan_manifest(from: &Market, to: &Market, capacity: u32) -> (f32, Manifest) {
+    let mut margins = Commodity::ALL
+        .iter()
+        .filter_map(|&commodity| {
+            let i = commodity.index();
+            let available = (from.stock[i] - from.target[i] * TRADER_RESERVE).floor();
+            let margin = to.sell_price(commodity) as f32 - from.buy_price(commodity) as f32;
+            if available >= 1.0 && margin > 0.0 {
+                Some((margin, commodity, available as u32))
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    margins.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+
+    let mut space = capacity;
+    let mut profit = 0.0;
+    let mut manifest = Vec::new();
+    for (margin, commodity, available) in margins {
+        let amount = available.min(space);
+        if amount == 0 {
+            break;
+        }
+        space -= amount;
+        profit += margin * amount as f32;
+        manifest.push((commodity, amount));
+    }
+
+    (profit, manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED: u64 = 0x5EED;
+    // A couple of hours of game time
+    const TICKS: u64 = 8000;
+
+    // Where each trader is, where it's going and what it's carrying
+    type TraderState = (StationId, Option<StationId>, Manifest);
+
+    // Everything about the economy that changes as it runs
+    fn snapshot(economy: &Economy) -> (Vec<Stock>, Vec<TraderState>) {
+        (
+            economy
+                .stations
+                .iter()
+                .map(|station| station.market.stock)
+                .collect(),
+            economy
+                .traders
+                .iter()
+                .map(|trader| (trader.location, trader.destination, trader.cargo.clone()))
+                .collect(),
+        )
+    }
+
+    // Average of every price across every station, relative to base prices
+    // 1.0 means the economy as a whole is balanced, a value that keeps climbing over a long simulation means inflation
+    fn price_index(economy: &Economy) -> f32 {
+        let total = economy
+            .stations
+            .iter()
+            .flat_map(|station| {
+                Commodity::ALL
+                    .iter()
+                    .map(move |&c| station.market.price(c) / c.base_price())
+            })
+            .sum::<f32>();
+        total / (economy.stations.len() * Commodity::COUNT).max(1) as f32
+    }
+
+    #[test]
+    fn same_seed_gives_same_economy() {
+        let mut a = Economy::new(SEED, "Test");
+        let mut b = Economy::new(SEED, "Test");
+        a.simulate(TICKS);
+        b.simulate(TICKS);
+        assert_eq!(snapshot(&a), snapshot(&b));
+    }
+
+    // Prices averaged over every market should hover around base prices rather than drift off
+    #[test]
+    fn prices_dont_inflate() {
+        let mut economy = Economy::new(SEED, "Test");
+        for step in 1..=10 {
+            economy.simulate(TICKS / 10);
+            let index = price_index(&economy);
+            assert!(
+                (0.5..2.0).contains(&index),
+                "price index {} after {} ticks",
+                index,
+                step * TICKS / 10
+            );
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use crate::{
+    economy::{Commodity, StationId},
+    galaxy::SystemId,
+};
+
+// Something notable that happened in the game world
+// Events are collected over a frame and handed to anything that needs to react to them (missions, etc.) at the end of the update
+#[derive(Clone, Debug, PartialEq)]
+pub enum GameEvent {
+    Docked(StationId),
+    Undocked(StationId),
+    Bought {
+        station: StationId,
+        commodity: Commodity,
+        amount: u32,
+    },
+    Sold {
+        station: StationId,
+        commodity: Commodity,
+        amount: u32,
+    },
+    Mined {
+        commodity: Commodity,
+        amount: u32,
+    },
+    // An asteroid was mined out completely and broke up
+    // The index is where it was in the system's asteroids before it was removed
+    AsteroidDestroyed {
+        index: usize,
+        position: na::Vector2<f32>,
+    },
+    // One of the player's shots hit something, at the given position
+    ShotHit(na::Vector2<f32>),
+    // A ship with the given name was destroyed by the player
+    ShipDestroyed {
+        name: String,
+        position: na::Vector2<f32>,
+    },
+    // The player finished a jump into another system
+    Arrived(SystemId),
+}
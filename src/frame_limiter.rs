@@ -0,0 +1,51 @@
+use std::time::{Duration, Instant};
+
+// OS timers can wake up a millisecond or two late, so the event loop is woken this early and the rest is spun through
+const SPIN_TIME: Duration = Duration::from_millis(2);
+
+// Keeps the frame rate under a limit, sleeping between frames rather than burning a core
+pub struct FrameLimiter {
+    next_frame: Instant,
+}
+
+impl Default for FrameLimiter {
+    fn default() -> Self {
+        Self {
+            next_frame: Instant::now(),
+        }
+    }
+}
+
+impl FrameLimiter {
+    // Whether it's time for the next frame
+    // When it's nearly time, this waits out the last moment itself so frames are evenly spaced
+    pub fn frame_due(&mut self, frame_limit: Option<u32>) -> bool {
+        let frame_limit = match frame_limit {
+            Some(frame_limit) => frame_limit,
+            None => return true,
+        };
+        let now = Instant::now();
+        if now + SPIN_TIME < self.next_frame {
+            return false;
+        }
+        while Instant::now() < self.next_frame {
+            std::hint::spin_loop();
+        }
+
+        // Falling a whole frame behind starts the schedule again from now, rather than rushing a few frames out to catch up
+        let frame_time = Duration::from_secs_f64(1.0 / frame_limit as f64);
+        let now = Instant::now();
+        self.next_frame += frame_time;
+        if self.next_frame < now {
+            self.next_frame = now + frame_time;
+        }
+        true
+    }
+
+    // When the event loop should wake up to start the next frame
+    pub fn wake_time(&self) -> Instant {
+        self.next_frame
+            .checked_sub(SPIN_TIME)
+            .unwrap_or(self.next_frame)
+    }
+}
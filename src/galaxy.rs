@@ -0,0 +1,209 @@
+use crate::rng::Rng;
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+const SYSTEM_COUNT: usize = 24;
+// Systems are scattered in a disc this many light years across
+const GALAXY_RADIUS: f32 = 100.0;
+// Systems are kept at least this far apart so the map stays readable
+const MIN_SEPARATION: f32 = 14.0;
+// On top of the lanes needed to keep every system reachable, each system gets lanes to this many of its nearest neighbours
+const NEAREST_LANES: usize = 2;
+// Nearest neighbour lanes longer than this aren't added, so distant systems don't get linked up by accident
+const MAX_LANE_LENGTH: f32 = 35.0;
+
+const NAME_PREFIXES: [&str; 12] = [
+    "Al", "Bel", "Cor", "Dra", "Eri", "Fen", "Gal", "Hy", "Ix", "Kor", "Lum", "Mir",
+];
+const NAME_SUFFIXES: [&str; 12] = [
+    "ara", "eth", "ion", "os", "ux", "ada", "ene", "is", "orn", "ath", "yra", "on",
+];
+
+pub type SystemId = usize;
+
+pub struct StarSystem {
+    pub name: String,
+    // Position on the galaxy map in light years, this has nothing to do with world space inside the system
+    pub position: na::Vector2<f32>,
+    // Seed everything inside the system is generated from
+    pub seed: u64,
+    // Systems that can be jumped to directly from here
+    pub lanes: Vec<SystemId>,
+}
+
+// Every star system in the game and the jump lanes between them
+pub struct Galaxy {
+    pub systems: Vec<StarSystem>,
+}
+
+impl Galaxy {
+    pub fn generate(seed: u64) -> Self {
+        let mut rng = Rng::new(seed);
+
+        // Scatter systems around the disc, throwing away any that land too close to another
+        let mut positions: Vec<na::Vector2<f32>> = Vec::new();
+        while positions.len() < SYSTEM_COUNT {
+            let angle = rng.range_f32(0.0, std::f32::consts::PI * 2.0);
+            // Square root keeps the systems evenly spread rather than bunched in the middle
+            let distance = rng.next_f32().sqrt() * GALAXY_RADIUS;
+            let position = na::Vector2::new(angle.cos(), angle.sin()) * distance;
+            if positions
+                .iter()
+                .all(|other| (other - position).norm() >= MIN_SEPARATION)
+            {
+                positions.push(position);
+            }
+        }
+
+        let mut systems: Vec<StarSystem> = Vec::new();
+        for position in positions {
+            // Names are rerolled until they're unique
+            let name = loop {
+                let name = format!(
+                    "{}{}",
+                    NAME_PREFIXES[rng.below(NAME_PREFIXES.len())],
+                    NAME_SUFFIXES[rng.below(NAME_SUFFIXES.len())]
+                );
+                if systems.iter().all(|system| system.name != name) {
+                    break name;
+                }
+            };
+            systems.push(StarSystem {
+                name,
+                position,
+                seed: rng.next_u64(),
+                lanes: Vec::new(),
+            });
+        }
+
+        // A minimum spanning tree guarantees every system can be reached from every other
+        let mut connected = vec![false; systems.len()];
+        connected[0] = true;
+        for _ in 1..systems.len() {
+            let mut shortest = (0, 0);
+            let mut shortest_length = f32::INFINITY;
+            for a in (0..systems.len()).filter(|&a| connected[a]) {
+                for b in (0..systems.len()).filter(|&b| !connected[b]) {
+                    let length = (systems[a].position - systems[b].position).norm();
+                    if length < shortest_length {
+                        shortest = (a, b);
+                        shortest_length = length;
+                    }
+                }
+            }
+            let (a, b) = shortest;
+            connected[b] = true;
+            add_lane(&mut systems, a, b);
+        }
+
+        // Then a few extra short lanes so there's more than one way to get around
+        for a in 0..systems.len() {
+            let mut neighbours = (0..systems.len())
+                .filter(|&b| b != a)
+                .map(|b| (b, (systems[a].position - systems[b].position).norm()))
+                .filter(|&(_, length)| length <= MAX_LANE_LENGTH)
+                .collect::<Vec<_>>();
+            neighbours.sort_by(|x, y| x.1.partial_cmp(&y.1).unwrap());
+            for &(b, _) in neighbours.iter().take(NEAREST_LANES) {
+                add_lane(&mut systems, a, b);
+            }
+        }
+
+        Self { systems }
+    }
+
+    pub fn system(&self, id: SystemId) -> &StarSystem {
+        &self.systems[id]
+    }
+
+    // Distance between two systems in light years
+    pub fn distance(&self, a: SystemId, b: SystemId) -> f32 {
+        (self.systems[a].position - self.systems[b].position).norm()
+    }
+
+    pub fn connected(&self, a: SystemId, b: SystemId) -> bool {
+        self.systems[a].lanes.contains(&b)
+    }
+
+    // Shortest path along the jump lanes, as the list of systems to jump to in order (not including the start)
+    // Returns None if the destination can't be reached
+    pub fn route(&self, from: SystemId, to: SystemId) -> Option<Vec<SystemId>> {
+        let mut distances = vec![f32::INFINITY; self.systems.len()];
+        let mut previous = vec![None; self.systems.len()];
+        let mut queue = BinaryHeap::new();
+        distances[from] = 0.0;
+        queue.push(RouteNode {
+            system: from,
+            distance: 0.0,
+        });
+
+        // Dijkstra's, using the length of each lane as its cost
+        while let Some(RouteNode { system, distance }) = queue.pop() {
+            if system == to {
+                break;
+            }
+            // Stale entry for a system that's already been reached by a shorter path
+            if distance > distances[system] {
+                continue;
+            }
+            for &next in &self.systems[system].lanes {
+                let next_distance = distance + self.distance(system, next);
+                if next_distance < distances[next] {
+                    distances[next] = next_distance;
+                    previous[next] = Some(system);
+                    queue.push(RouteNode {
+                        system: next,
+                        distance: next_distance,
+                    });
+                }
+            }
+        }
+
+        if distances[to].is_infinite() {
+            return None;
+        }
+        let mut route = Vec::new();
+        let mut current = to;
+        while current != from {
+            route.push(current);
+            current = previous[current]?;
+        }
+        route.reverse();
+        Some(route)
+    }
+}
+
+fn add_lane(systems: &mut [StarSystem], a: SystemId, b: SystemId) {
+    if !systems[a].lanes.contains(&b) {
+        systems[a].lanes.push(b);
+        systems[b].lanes.push(a);
+    }
+}
+
+// Entry in the pathfinding queue, ordered so the heap pops the closest system first
+struct RouteNode {
+    system: SystemId,
+    distance: f32,
+}
+
+impl PartialEq for RouteNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.distance == other.distance
+    }
+}
+
+impl Eq for RouteNode {}
+
+impl PartialOrd for RouteNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for RouteNode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .distance
+            .partial_cmp(&self.distance)
+            .unwrap_or(Ordering::Equal)
+    }
+}
@@ -1,4 +1,54 @@
-use crate::player::{Player, PlayerControls};
+use crate::{
+    asteroid::Asteroid,
+    economy::{Commodity, Economy, StationId, TradeError},
+    event::GameEvent,
+    galaxy::{Galaxy, SystemId},
+    hot_reload::ReloadError,
+    hyperspace::{JumpDrive, JumpError, JumpPhase, JumpState},
+    mining::MiningLaser,
+    mission::{
+        self, MissionContext, MissionError, MissionId, MissionOutcome, MissionStatus, Missions,
+        Objective,
+    },
+    outlaw::{Outlaw, OUTLAW_RADIUS},
+    particles::{self, EffectId, Emitter, ParticleSystem},
+    player::{Player, PlayerControls},
+    rng::Rng,
+    save::{self, SaveData, SaveError},
+    ship::{self, FitError, ModuleDef, Ship, ShipCatalog},
+    targeting::{Target, TargetInfo},
+    weapons::Weapons,
+};
+use log::{info, warn};
+use std::{collections::HashMap, mem, path::Path};
+
+// Seed for everything procedurally generated in a new game
+const WORLD_SEED: u64 = 0x5EED;
+// How close the player has to be to a station to trade with it
+const DOCKING_RANGE: f32 = 3.0;
+// Hull and system the player starts a new game in
+const STARTING_HULL: &str = "shuttle";
+const STARTING_SYSTEM: SystemId = 0;
+// Amount of ship fuel one unit of the fuel commodity is worth
+const FUEL_PER_UNIT: f32 = 10.0;
+const ASTEROID_FIELD_COUNT: usize = 4;
+const ASTEROIDS_PER_FIELD: usize = 25;
+const ASTEROID_FIELD_SPREAD: f32 = 30.0;
+// Ships come out of a jump this far from the centre of the system, on the side facing the system they left
+const ARRIVAL_DISTANCE: f32 = 60.0;
+// Distance from the centre of the ship to its engine nozzle
+const ENGINE_NOZZLE_OFFSET: f32 = 0.5;
+// Ships are treated as a circle this big when bumping into things
+const SHIP_RADIUS: f32 = 0.5;
+// Roughly the size of a station's sprite, for the targeting reticle
+const STATION_RADIUS: f32 = 2.0;
+// Fraction of the impact speed the ship bounces back with after hitting an asteroid
+const COLLISION_RESTITUTION: f32 = 0.4;
+// Hull damage per unit of impact speed, gentle bumps below the threshold are free
+const COLLISION_DAMAGE: f32 = 3.0;
+const COLLISION_DAMAGE_THRESHOLD: f32 = 2.0;
+// Systems the player has left stop simulating, and catch up on at most this many economy ticks when the player returns
+const MAX_CATCH_UP_TICKS: u64 = 600;
 
 #[derive(Default, Debug)]
 pub struct Input {
@@ -6,6 +56,14 @@ pub struct Input {
     pub move_r: bool,
     pub move_f: bool,
     pub move_b: bool,
+    pub strafe_l: bool,
+    pub strafe_r: bool,
+    pub afterburner: bool,
+    pub flight_assist: bool,
+    pub kill_rotation: bool,
+    pub kill_velocity: bool,
+    pub mine: bool,
+    pub fire: bool,
 }
 
 impl Input {
@@ -19,19 +77,104 @@ impl Input {
             move_r: self.move_r,
             move_f: self.move_f,
             move_b: self.move_b,
+            strafe_l: self.strafe_l,
+            strafe_r: self.strafe_r,
+            afterburner: self.afterburner,
+            toggle_flight_assist: self.flight_assist,
+            kill_rotation: self.kill_rotation,
+            kill_velocity: self.kill_velocity,
         }
     }
 }
 
+// Particle effects the game spawns as one off bursts
+struct Effects {
+    explosion: EffectId,
+    debris: EffectId,
+    hit: EffectId,
+}
+
+// The contents of a star system the player isn't in, kept around so it's the same when they come back
+struct UnloadedSystem {
+    economy: Economy,
+    asteroids: Vec<Asteroid>,
+    // Game time the player left at
+    left_at: f64,
+}
+
 pub struct Game {
     player: Player,
+    pub ships: ShipCatalog,
+    pub galaxy: Galaxy,
+    // System the player is in, the economy and asteroids are this system's
+    system: SystemId,
+    pub economy: Economy,
+    pub asteroids: Vec<Asteroid>,
+    // Bounty targets in this system, they only exist while the contract for them is active
+    pub outlaws: Vec<Outlaw>,
+    next_outlaw_id: u32,
+    unloaded: HashMap<SystemId, UnloadedSystem>,
+    pub jump_drive: JumpDrive,
+    // Systems still to jump to on the way to the autopilot's destination, in order
+    route: Vec<SystemId>,
+    pub mining_laser: MiningLaser,
+    pub weapons: Weapons,
+    pub missions: Missions,
+    pub particles: ParticleSystem,
+    effects: Effects,
+    engine_emitter: Emitter,
+    mining_emitter: Emitter,
+    // Events raised since the last update, these are processed and cleared at the end of every update
+    events: Vec<GameEvent>,
+    docked: Option<StationId>,
+    // What the player has locked on to, if anything
+    target: Option<Target>,
+    // Total time the game has been running for
+    time: f64,
     pub input: Input, // Any possible player game input, which is translated and relayed to wherever it's needed
 }
 
 impl Game {
     pub fn new() -> Self {
+        let ships = ShipCatalog::load().expect("Failed to load ship data");
+        let ship = Ship::new(&ships, STARTING_HULL).expect("Failed to build starting ship");
+
+        let galaxy = Galaxy::generate(WORLD_SEED);
+        let UnloadedSystem {
+            economy, asteroids, ..
+        } = generate_system(&galaxy, STARTING_SYSTEM);
+        let missions = Missions::new(WORLD_SEED, STARTING_SYSTEM, &economy.stations)
+            .expect("Failed to load mission data");
+
+        let particles = ParticleSystem::new(WORLD_SEED).expect("Failed to load particle effects");
+        let (effects, engine_effect, mining_effect) =
+            find_effects(&particles).unwrap_or_else(|id| panic!("Missing particle effect {}", id));
+        let engine_emitter = Emitter::new(engine_effect);
+        let mining_emitter = Emitter::new(mining_effect);
+
         Self {
-            player: Player::new(),
+            player: Player::new(ship),
+            ships,
+            galaxy,
+            system: STARTING_SYSTEM,
+            economy,
+            asteroids,
+            outlaws: Vec::new(),
+            next_outlaw_id: 0,
+            unloaded: HashMap::new(),
+            jump_drive: JumpDrive::default(),
+            route: Vec::new(),
+            mining_laser: MiningLaser::default(),
+            weapons: Weapons::default(),
+            missions,
+            particles,
+            effects,
+            engine_emitter,
+            mining_emitter,
+            events: Vec::new(),
+            docked: None,
+            target: None,
+            time: 0.0,
             input: Input::default(),
         }
     }
@@ -39,10 +182,599 @@ impl Game {
     // The root game update function, everything in the game that requires regular updates is called from here at some level
     // e.g. player update, entity update, world update, processing interactions between any of those, etc.
     pub fn update(&mut self, delta: f32) {
-        self.player.update(delta, &self.input.player_controls());
+        self.time += delta as f64;
+
+        // Fuel can run low while the drive is charging, so make sure the jump can still be paid for before it happens
+        if let JumpState::Charging { destination, .. } = self.jump_drive.state() {
+            let cost = JumpDrive::fuel_cost(&self.galaxy, self.system, destination);
+            if self.player.resources().fuel < cost {
+                warn!("Jump cancelled, not enough fuel");
+                self.jump_drive.cancel();
+                self.route.clear();
+            }
+        }
+        match self.jump_drive.update(delta) {
+            Some(JumpPhase::Departed(destination)) => self.depart(destination),
+            Some(JumpPhase::Arrived(destination)) => self.arrive(destination),
+            None => (),
+        }
+
+        // Nothing happens to the player or the system around them while they're between systems
+        if !self.jump_drive.in_transit() {
+            self.player.update(delta, &self.input.player_controls());
+            self.update_outlaws(delta);
+            self.mining_laser.update(
+                delta,
+                self.input.mine,
+                &mut self.player,
+                &mut self.asteroids,
+                &mut self.events,
+            );
+            self.weapons.update(
+                delta,
+                self.input.fire,
+                &mut self.player,
+                &self.asteroids,
+                &mut self.outlaws,
+                &mut self.events,
+            );
+            self.collide_with_asteroids();
+            self.economy.update(delta);
+            self.update_docking();
+            self.update_target();
+            self.update_effects(delta);
+        }
+        self.particles.update(delta);
+
+        let outcomes = self.missions.update(&MissionContext {
+            delta,
+            system: self.system,
+            player: &self.player,
+            stations: &self.economy.stations,
+            events: &self.events,
+        });
+        for outcome in outcomes {
+            self.mission_finished(outcome);
+        }
+
+        self.events.clear();
+    }
+
+    // Asteroids are solid, the ship bounces off them and takes damage if it hits hard enough
+    fn collide_with_asteroids(&mut self) {
+        let player = &mut self.player;
+        for asteroid in &self.asteroids {
+            let offset = player.position - asteroid.position;
+            let distance = offset.norm();
+            let overlap = asteroid.radius() + SHIP_RADIUS - distance;
+            if overlap <= 0.0 || distance <= 0.0 {
+                continue;
+            }
+            let normal = offset / distance;
+            player.position += normal * overlap;
+
+            // Only the part of the velocity heading into the rock is bounced
+            let impact_speed = -player.velocity.dot(&normal);
+            if impact_speed <= 0.0 {
+                continue;
+            }
+            player.velocity += normal * impact_speed * (1.0 + COLLISION_RESTITUTION);
+            if impact_speed > COLLISION_DAMAGE_THRESHOLD {
+                let contact = asteroid.position + normal * asteroid.radius();
+                self.particles
+                    .burst(self.effects.hit, contact, na::Vector2::zeros());
+                if player.damage((impact_speed - COLLISION_DAMAGE_THRESHOLD) * COLLISION_DAMAGE) {
+                    info!("Player ship destroyed");
+                    self.particles
+                        .burst(self.effects.explosion, player.position, player.velocity);
+                    self.particles
+                        .burst(self.effects.debris, player.position, player.velocity);
+                }
+            }
+        }
+    }
+
+    // Bounty targets turn up at their hideout while the contract for them is active in this system, and leave when it isn't
+    fn update_outlaws(&mut self, delta: f32) {
+        let system = self.system;
+        let wanted = self
+            .missions
+            .log
+            .active
+            .iter()
+            .filter(|mission| mission.system == system)
+            .filter_map(|mission| match &mission.objective {
+                Objective::Bounty { target, hideout } => Some((target, *hideout)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        self.outlaws
+            .retain(|outlaw| wanted.iter().any(|&(name, _)| *name == outlaw.name));
+        for (name, hideout) in wanted {
+            if !self.outlaws.iter().any(|outlaw| outlaw.name == *name) {
+                self.outlaws
+                    .push(Outlaw::new(self.next_outlaw_id, name.clone(), hideout));
+                self.next_outlaw_id += 1;
+            }
+        }
+
+        for outlaw in &mut self.outlaws {
+            outlaw.update(delta);
+        }
+    }
+
+    fn update_docking(&mut self) {
+        let docked = self.docked_station();
+        if docked != self.docked {
+            if let Some(station) = self.docked {
+                self.events.push(GameEvent::Undocked(station));
+            }
+            if let Some(station) = docked {
+                self.events.push(GameEvent::Docked(station));
+            }
+            self.docked = docked;
+        }
+    }
+
+    // Asteroids are swap removed when they're destroyed, so an asteroid target has to follow the one that took its place
+    fn update_target(&mut self) {
+        if let Some(Target::Outlaw(id)) = self.target {
+            if !self.outlaws.iter().any(|outlaw| outlaw.id == id) {
+                self.target = None;
+            }
+        }
+        for event in &self.events {
+            if let GameEvent::AsteroidDestroyed { index, .. } = *event {
+                self.target = match self.target {
+                    Some(Target::Asteroid(target)) if target == index => None,
+                    // The last asteroid was moved into the gap
+                    Some(Target::Asteroid(target)) if target == self.asteroids.len() => {
+                        Some(Target::Asteroid(index))
+                    }
+                    target => target,
+                };
+            }
+        }
+    }
+
+    // Spawn particles for whatever happened this frame
+    fn update_effects(&mut self, delta: f32) {
+        let player = &self.player;
+        let heading = player.heading();
+        // Exhaust only comes out of the main engine, so it's only the forward part of the thrust that counts
+        self.engine_emitter.update(
+            &mut self.particles,
+            delta,
+            player.thrust().dot(&heading).max(0.0),
+            player.position - heading * ENGINE_NOZZLE_OFFSET,
+            player.velocity,
+            (-heading.y).atan2(-heading.x),
+        );
+
+        // Sparks fly back off the rock the beam is cutting into
+        let beam = self
+            .mining_laser
+            .beam()
+            .filter(|beam| beam.target.is_some());
+        if let Some(beam) = beam {
+            let back = beam.start - beam.end;
+            self.mining_emitter.update(
+                &mut self.particles,
+                delta,
+                1.0,
+                beam.end,
+                na::Vector2::zeros(),
+                back.y.atan2(back.x),
+            );
+        }
+
+        for event in &self.events {
+            match event {
+                GameEvent::Mined { .. } => {
+                    if let Some(beam) = beam {
+                        self.particles
+                            .burst(self.effects.hit, beam.end, na::Vector2::zeros());
+                    }
+                }
+                GameEvent::ShotHit(position) => {
+                    self.particles
+                        .burst(self.effects.hit, *position, na::Vector2::zeros());
+                }
+                GameEvent::AsteroidDestroyed { position, .. }
+                | GameEvent::ShipDestroyed { position, .. } => {
+                    self.particles
+                        .burst(self.effects.explosion, *position, na::Vector2::zeros());
+                    self.particles
+                        .burst(self.effects.debris, *position, na::Vector2::zeros());
+                }
+                _ => (),
+            }
+        }
+    }
+
+    // The jump drive has fired, swap the current system out for the destination
+    // The player is kept out of the world until the jump finishes, so the destination is ready by the time they arrive
+    fn depart(&mut self, destination: SystemId) {
+        let from = self.system;
+        // Checked before the drive fired
+        let cost = JumpDrive::fuel_cost(&self.galaxy, from, destination);
+        self.player.try_spend_fuel(cost);
+        info!(
+            "Jumping from {} to {}",
+            self.galaxy.system(from).name,
+            self.galaxy.system(destination).name
+        );
+
+        // Leave the old system's station before it goes away
+        self.update_docking();
+
+        let next = match self.unloaded.remove(&destination) {
+            Some(mut system) => {
+                let missed = ((self.time - system.left_at) / Economy::TICK as f64) as u64;
+                system.economy.simulate(missed.min(MAX_CATCH_UP_TICKS));
+                system
+            }
+            None => generate_system(&self.galaxy, destination),
+        };
+        let previous = UnloadedSystem {
+            economy: mem::replace(&mut self.economy, next.economy),
+            asteroids: mem::replace(&mut self.asteroids, next.asteroids),
+            left_at: self.time,
+        };
+        self.unloaded.insert(from, previous);
+        self.system = destination;
+
+        // Anything tied to the old system's contents is no longer valid
+        self.mining_laser = MiningLaser::default();
+        self.weapons.clear();
+        self.outlaws.clear();
+        self.target = None;
+        self.particles.clear();
+        self.missions
+            .enter_system(destination, &self.economy.stations);
+
+        // Arrive at the edge of the new system, facing in towards the centre
+        let direction = (self.galaxy.system(from).position
+            - self.galaxy.system(destination).position)
+            .normalize();
+        self.player.position = direction * ARRIVAL_DISTANCE;
+        self.player.velocity = na::Vector2::zeros();
+        self.player.angle = direction.x.atan2(-direction.y);
+        self.player.angular_velocity = 0.0;
+    }
+
+    fn arrive(&mut self, destination: SystemId) {
+        info!("Arrived in {}", self.galaxy.system(destination).name);
+        self.events.push(GameEvent::Arrived(destination));
+
+        // Carry on along the autopilot route
+        if self.route.first() == Some(&destination) {
+            self.route.remove(0);
+        }
+        if !self.route.is_empty() {
+            if let Err(e) = self.engage_jump() {
+                warn!("Autopilot stopped: {}", e);
+                self.route.clear();
+            }
+        }
+    }
+
+    fn mission_finished(&mut self, outcome: MissionOutcome) {
+        let MissionOutcome { mission, status } = outcome;
+        info!("Mission \"{}\" finished: {:?}", mission.title, status);
+        if status != MissionStatus::Completed {
+            return;
+        }
+
+        self.player.credits += mission.reward;
+        // Delivered goods are handed over
+        if let Objective::Deliver {
+            commodity, amount, ..
+        } = mission.objective
+        {
+            self.player.cargo.remove(commodity, amount);
+        }
     }
 
     pub fn player(&self) -> &Player {
         &self.player
     }
+
+    // Once the hull is gone the game is over, it's up to the caller to stop updating
+    pub fn player_destroyed(&self) -> bool {
+        self.player.resources().hull <= 0.0
+    }
+
+    pub fn system(&self) -> SystemId {
+        self.system
+    }
+
+    pub fn route(&self) -> &[SystemId] {
+        &self.route
+    }
+
+    // Work out the jumps needed to get to a system, these are then followed by `engage_jump`
+    pub fn plot_route(&mut self, destination: SystemId) -> Result<(), JumpError> {
+        self.route = self
+            .galaxy
+            .route(self.system, destination)
+            .ok_or(JumpError::NoRoute)?;
+        Ok(())
+    }
+
+    // Start charging for the next jump along the route, after which the rest of the route is followed automatically
+    pub fn engage_jump(&mut self) -> Result<(), JumpError> {
+        let next = *self.route.first().ok_or(JumpError::NoRoute)?;
+        self.jump_drive.engage(
+            &self.galaxy,
+            self.system,
+            next,
+            self.player.resources().fuel,
+        )
+    }
+
+    // Stop charging and forget the route
+    pub fn cancel_jump(&mut self) {
+        self.jump_drive.cancel();
+        self.route.clear();
+    }
+
+    // The station the player is close enough to trade with, if any
+    pub fn docked_station(&self) -> Option<StationId> {
+        if self.jump_drive.in_transit() {
+            return None;
+        }
+        self.economy
+            .stations
+            .iter()
+            .position(|station| (station.position - self.player.position).norm() <= DOCKING_RANGE)
+    }
+
+    pub fn target(&self) -> Option<Target> {
+        self.target
+    }
+
+    // Lock on to the next closest thing after the current target, or the closest if there isn't one
+    // Cycling past the furthest thing in the system clears the target
+    pub fn cycle_target(&mut self) {
+        if self.jump_drive.in_transit() {
+            return;
+        }
+        let position = self.player.position;
+        let mut candidates = self
+            .economy
+            .stations
+            .iter()
+            .enumerate()
+            .map(|(i, station)| (Target::Station(i), station.position))
+            .chain(
+                self.asteroids
+                    .iter()
+                    .enumerate()
+                    .map(|(i, asteroid)| (Target::Asteroid(i), asteroid.position)),
+            )
+            .chain(
+                self.outlaws
+                    .iter()
+                    .map(|outlaw| (Target::Outlaw(outlaw.id), outlaw.position)),
+            )
+            .map(|(target, at)| (target, (at - position).norm_squared()))
+            .collect::<Vec<_>>();
+        candidates.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+
+        let next = match self
+            .target
+            .and_then(|target| candidates.iter().position(|&(c, _)| c == target))
+        {
+            Some(current) => current + 1,
+            None => 0,
+        };
+        self.target = candidates.get(next).map(|&(target, _)| target);
+    }
+
+    // Where the current target is and how it's moving
+    pub fn target_info(&self) -> Option<TargetInfo> {
+        match self.target? {
+            Target::Station(id) => self.economy.stations.get(id).map(|station| TargetInfo {
+                name: station.name.clone(),
+                position: station.position,
+                velocity: na::Vector2::zeros(),
+                radius: STATION_RADIUS,
+            }),
+            Target::Asteroid(index) => self.asteroids.get(index).map(|asteroid| TargetInfo {
+                name: format!(
+                    "{:?} asteroid ({:.0} left)",
+                    asteroid.kind,
+                    asteroid.remaining()
+                ),
+                position: asteroid.position,
+                velocity: na::Vector2::zeros(),
+                radius: asteroid.radius(),
+            }),
+            Target::Outlaw(id) => {
+                self.outlaws
+                    .iter()
+                    .find(|outlaw| outlaw.id == id)
+                    .map(|outlaw| TargetInfo {
+                        name: outlaw.name.clone(),
+                        position: outlaw.position,
+                        velocity: outlaw.velocity,
+                        radius: OUTLAW_RADIUS,
+                    })
+            }
+        }
+    }
+
+    // Buy from the docked station's market, returning the total price paid
+    pub fn buy(&mut self, commodity: Commodity, amount: u32) -> Result<u32, TradeError> {
+        let station = self.docked_station().ok_or(TradeError::NotDocked)?;
+        let Player { credits, cargo, .. } = &mut self.player;
+        let price = self
+            .economy
+            .buy(station, commodity, amount, credits, cargo)?;
+        self.events.push(GameEvent::Bought {
+            station,
+            commodity,
+            amount,
+        });
+        Ok(price)
+    }
+
+    // Sell to the docked station's market, returning the total payment received
+    pub fn sell(&mut self, commodity: Commodity, amount: u32) -> Result<u32, TradeError> {
+        let station = self.docked_station().ok_or(TradeError::NotDocked)?;
+        let Player { credits, cargo, .. } = &mut self.player;
+        let payment = self
+            .economy
+            .sell(station, commodity, amount, credits, cargo)?;
+        self.events.push(GameEvent::Sold {
+            station,
+            commodity,
+            amount,
+        });
+        Ok(payment)
+    }
+
+    // Top up the player's tank from fuel carried in the cargo hold, returning how many units were used
+    // Units only go in whole, so the tank is left short of full rather than wasting part of one
+    pub fn refuel(&mut self) -> u32 {
+        let mut used = 0;
+        while self.player.cargo.amount(Commodity::Fuel) > 0
+            && self.player.fuel_space() >= FUEL_PER_UNIT
+        {
+            self.player.refuel(FUEL_PER_UNIT);
+            self.player.cargo.remove(Commodity::Fuel, 1);
+            used += 1;
+        }
+        used
+    }
+
+    // Change the player's loadout
+    pub fn fit_module(&mut self, slot: usize, module_id: &str) -> Result<(), FitError> {
+        self.player.fit(&self.ships, slot, module_id)
+    }
+
+    // Empty one of the player's slots, returning what was in it
+    pub fn unfit_module(&mut self, slot: usize) -> Result<Option<ModuleDef>, FitError> {
+        self.player.unfit(slot)
+    }
+
+    // Take a contract from a station's board, the player has to be docked there
+    pub fn accept_contract(
+        &mut self,
+        station: StationId,
+        index: usize,
+    ) -> Result<MissionId, MissionError> {
+        let docked = self.docked_station();
+        self.missions.accept(station, docked, index)
+    }
+
+    pub fn abandon_mission(&mut self, id: MissionId) {
+        if let Some(outcome) = self.missions.abandon(id) {
+            self.mission_finished(outcome);
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), SaveError> {
+        save::write(
+            path,
+            &SaveData::new(
+                self.player.to_save(),
+                self.system,
+                self.missions.log.clone(),
+            ),
+        )
+    }
+
+    // Restore the player's progress from a save
+    // The world itself isn't saved, it's regenerated from the seed
+    pub fn load(&mut self, path: &Path) -> Result<(), SaveError> {
+        let data = save::read(path)?;
+        if data.system >= self.galaxy.systems.len() {
+            return Err(SaveError::InvalidSystem(data.system));
+        }
+        self.player =
+            Player::from_save(&self.ships, &data.player).map_err(SaveError::InvalidShip)?;
+        self.missions.log = data.missions;
+
+        let UnloadedSystem {
+            economy, asteroids, ..
+        } = generate_system(&self.galaxy, data.system);
+        self.system = data.system;
+        self.economy = economy;
+        self.asteroids = asteroids;
+        self.unloaded.clear();
+        self.jump_drive = JumpDrive::default();
+        self.route.clear();
+        self.missions
+            .enter_system(self.system, &self.economy.stations);
+        self.mining_laser = MiningLaser::default();
+        self.weapons.clear();
+        self.outlaws.clear();
+        self.particles.clear();
+        self.docked = None;
+        self.target = None;
+        Ok(())
+    }
+
+    // Swap in a data file that changed on disk, given relative to the assets directory
+    // Returns whether it was one of the game's files, and nothing changes if it fails to load
+    pub fn reload_asset(&mut self, path: &Path) -> Result<bool, ReloadError> {
+        if path == Path::new(ship::HULLS_PATH) || path == Path::new(ship::MODULES_PATH) {
+            // The player's ship is rebuilt with the same loadout so changes show up straight away
+            let ships = ShipCatalog::load()?;
+            self.player.rebuild_ship(&ships)?;
+            self.ships = ships;
+        } else if path == Path::new(particles::EFFECTS_PATH) {
+            // Live particles go with the old effects, since effect ids might not mean the same thing any more
+            let particles = ParticleSystem::new(WORLD_SEED)?;
+            let (effects, engine_effect, mining_effect) =
+                find_effects(&particles).map_err(ReloadError::MissingEffect)?;
+            self.particles = particles;
+            self.effects = effects;
+            self.engine_emitter.effect = engine_effect;
+            self.mining_emitter.effect = mining_effect;
+        } else if path == Path::new(mission::TEMPLATES_PATH) {
+            self.missions.reload_templates()?;
+        } else {
+            return Ok(false);
+        }
+        Ok(true)
+    }
+}
+
+// The effects the game spawns itself, then the engine and mining emitters' effects
+// Fails with the id of the first one missing from the effects data file
+fn find_effects(particles: &ParticleSystem) -> Result<(Effects, EffectId, EffectId), String> {
+    let effect = |id: &str| particles.effect(id).ok_or_else(|| id.to_owned());
+    Ok((
+        Effects {
+            explosion: effect("explosion")?,
+            debris: effect("debris")?,
+            hit: effect("hit")?,
+        },
+        effect("engine_exhaust")?,
+        effect("mining_sparks")?,
+    ))
+}
+
+// Build the contents of a system from its seed
+fn generate_system(galaxy: &Galaxy, id: SystemId) -> UnloadedSystem {
+    let system = galaxy.system(id);
+
+    // Asteroid fields are scattered around the stations
+    let mut rng = Rng::new(system.seed);
+    let asteroids = (0..ASTEROID_FIELD_COUNT)
+        .flat_map(|_| {
+            let angle = rng.range_f32(0.0, std::f32::consts::PI * 2.0);
+            let center = na::Vector2::new(angle.cos(), angle.sin()) * rng.range_f32(40.0, 120.0);
+            Asteroid::generate_field(&mut rng, center, ASTEROID_FIELD_SPREAD, ASTEROIDS_PER_FIELD)
+        })
+        .collect();
+
+    UnloadedSystem {
+        economy: Economy::new(system.seed, &system.name),
+        asteroids,
+        left_at: 0.0,
+    }
 }
@@ -0,0 +1,51 @@
+use crate::ui::Nav;
+#[cfg(feature = "gamepad")]
+use log::error;
+
+// Gamepads are only used to navigate the ui for now
+// Support is behind the "gamepad" feature, since gilrs needs libudev on Linux
+pub struct Gamepads {
+    #[cfg(feature = "gamepad")]
+    gilrs: Option<gilrs::Gilrs>,
+}
+
+impl Gamepads {
+    pub fn new() -> Self {
+        Self {
+            #[cfg(feature = "gamepad")]
+            gilrs: gilrs::Gilrs::new()
+                .map_err(|e| error!("Gamepad support unavailable: {}", e))
+                .ok(),
+        }
+    }
+
+    // The next button press that moves around the ui, the same way the arrow keys do
+    #[cfg(feature = "gamepad")]
+    pub fn next_nav(&mut self) -> Option<Nav> {
+        use gilrs::Button::*;
+        let gilrs = self.gilrs.as_mut()?;
+        while let Some(gilrs::Event { event, .. }) = gilrs.next_event() {
+            let nav = match event {
+                gilrs::EventType::ButtonPressed(button, _) => match button {
+                    DPadUp => Some(Nav::Up),
+                    DPadDown => Some(Nav::Down),
+                    DPadLeft => Some(Nav::Left),
+                    DPadRight => Some(Nav::Right),
+                    South => Some(Nav::Activate),
+                    East => Some(Nav::Back),
+                    _ => None,
+                },
+                _ => None,
+            };
+            if nav.is_some() {
+                return nav;
+            }
+        }
+        None
+    }
+
+    #[cfg(not(feature = "gamepad"))]
+    pub fn next_nav(&mut self) -> Option<Nav> {
+        None
+    }
+}
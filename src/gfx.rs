@@ -1,21 +1,157 @@
 use crate::{
+    assets,
+    asteroid::AsteroidKind,
     game::Game,
     gpu::{
-        main_pipeline::{Instance, MainPipeline, Mesh, Vertex, View},
-        GpuInfo,
+        atlas::AtlasError,
+        font::{Align, Font, TextStyle},
+        main_pipeline::{Instance, MainPipeline, Mesh, MeshBatch, Vertex, View},
+        mesh::{MeshHandle, MeshRegistry},
+        particle_pipeline::{ParticleBatch, ParticleInstance, ParticlePipeline},
+        post_pipeline::{PostParams, PostPipeline},
+        sort_batches,
+        sprite_pipeline::{SpriteAtlas, SpriteBatch, SpriteInstance, SpritePipeline},
+        starfield_pipeline::{StarfieldPipeline, StarfieldView},
+        BlendMode, DrawStats, GpuInfo, Layer, RenderTarget, SortKey,
     },
+    hot_reload::ReloadError,
+    hud,
+    particles::MAX_PARTICLES,
+    radar::{Radar, SystemMap},
+    settings::GraphicsSettings,
+    ui::DrawCommand,
+};
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Instant,
 };
-use std::sync::{Arc, Mutex};
 use winit::window::Window;
 
+const STATION_SCALE: f32 = 4.0;
+const SHIP_SCALE: f32 = 1.0;
+// Sprite artwork, relative to the assets directory
+const SPRITES_DIR: &str = "sprites";
+const STATION_SPRITE: &str = "stations/station";
+// Meshes, for anything without a sprite
+const MESHES_PATH: &str = "meshes/meshes.ron";
+const STATION_MESH: &str = "station";
+const SHIP_MESH: &str = "ship";
+const OUTLAW_MESH: &str = "outlaw";
+const SPRITE_TINT: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+// Glyphs are rasterised once at this size, text drawn much bigger than this on screen gets blurry
+const FONT_PATH: &str = "fonts/DejaVuSansMono.ttf";
+const FONT_PIXEL_SIZE: f32 = 32.0;
+// Labels are measured in world units, so they scale with everything else
+const LABEL_SIZE: f32 = 1.0;
+const LABEL_COLOR: [f32; 4] = [0.7, 0.75, 0.8, 1.0];
+const MAP_LABEL_SIZE: f32 = 3.0;
+const ASTEROID_SIDES: u16 = 8;
+const BEAM_WIDTH: f32 = 0.15;
+const BEAM_COLOR: [f32; 4] = [1.0, 0.35, 0.2, 1.0];
+// Shots are drawn as a short streak trailing behind them
+const SHOT_LENGTH: f32 = 0.8;
+const SHOT_WIDTH: f32 = 0.12;
+const SHOT_COLOR: [f32; 4] = [0.4, 1.0, 0.5, 1.0];
+// A wider, faint streak around each shot, already multiplied by its alpha
+// It's SHOT_COLOR at 30% so where it goes over the streak itself the streak comes out unchanged
+const SHOT_GLOW_WIDTH: f32 = 0.45;
+const SHOT_GLOW_COLOR: [f32; 4] = [0.12, 0.3, 0.15, 0.3];
+// The galaxy map is in light years rather than world units, so it needs a much wider view
+const MAP_ZOOM: f32 = 0.009;
+const MAP_SYSTEM_RADIUS: f32 = 2.0;
+const MAP_LANE_WIDTH: f32 = 0.4;
+const MAP_SYSTEM_COLOR: [f32; 4] = [0.8, 0.8, 0.9, 1.0];
+const MAP_CURRENT_SYSTEM_COLOR: [f32; 4] = [1.0, 0.85, 0.2, 1.0];
+const MAP_LANE_COLOR: [f32; 4] = [0.25, 0.25, 0.35, 1.0];
+const MAP_ROUTE_COLOR: [f32; 4] = [0.2, 0.8, 1.0, 0.6];
+
+// What fills the screen behind the hud and ui
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scene {
+    // The system the player is in, seen from their ship
+    System,
+    SystemMap,
+    GalaxyMap,
+}
+
+// What part of the world is on screen
+#[derive(Clone, Copy, Debug)]
+pub struct Camera {
+    pub position: na::Vector2<f32>,
+    // From world units to the -1 to 1 range of the screen on each axis, including aspect correction
+    pub scale: na::Vector2<f32>,
+    pub screen_size: na::Vector2<f32>,
+}
+
+impl Camera {
+    pub fn view_matrix(&self) -> na::Matrix3<f32> {
+        na::Matrix3::new_nonuniform_scaling(&self.scale).prepend_translation(&-self.position)
+    }
+
+    // Where a point in the world is on screen, in pixels from the top left with y pointing down like the ui
+    pub fn world_to_screen(&self, point: na::Vector2<f32>) -> na::Vector2<f32> {
+        let ndc = (point - self.position).component_mul(&self.scale);
+        na::Vector2::new(
+            (ndc.x + 1.0) / 2.0 * self.screen_size.x,
+            (1.0 - ndc.y) / 2.0 * self.screen_size.y,
+        )
+    }
+
+    // The aspect correction keeps this the same on both axes
+    pub fn pixels_per_unit(&self) -> f32 {
+        self.scale.x * self.screen_size.x / 2.0
+    }
+}
+
 pub struct GameRenderer {
     gpu_info: Arc<Mutex<GpuInfo>>,
+    starfield_pipeline: StarfieldPipeline,
     main_pipeline: MainPipeline,
+    particle_pipeline: ParticlePipeline,
+    sprite_pipeline: SpritePipeline,
+    post_pipeline: PostPipeline,
+    // Every sprite in the game, loaded once at startup
+    sprites: SpriteAtlas,
+    // Shared with the ui, which needs it to measure text
+    font: Arc<Font>,
+    font_atlas: SpriteAtlas,
+    // Reused every frame for laying out text
+    text_sprites: Vec<SpriteInstance>,
+    hud_commands: Vec<DrawCommand>,
+    // Reused every frame so converting particles for the gpu doesn't allocate
+    particle_instances: Vec<ParticleInstance>,
+    // Time rendering started, for anything animated on the gpu
+    start: Instant,
+    last_frame: Instant,
+    // Instances in the world drawn and culled last frame
+    draw_stats: DrawStats,
+
+    // Meshes from the data files, with the ones the renderer uses itself looked up once here
+    meshes: MeshRegistry,
+    station_mesh: MeshHandle,
+    ship_mesh: MeshHandle,
+    outlaw_mesh: MeshHandle,
+    // One per asteroid kind, indexed by the kind
+    asteroid_meshes: Vec<Mesh>,
+    // Unit length beam running along the x axis, stretched out to the right length per instance
+    beam_mesh: Mesh,
+    // Same shape as the beam, for the player's shots
+    shot_mesh: Mesh,
+    shot_glow_mesh: Mesh,
 
-    // Temporary mesh for testing rendering
-    // In the future, all meshes should be located at some level within this module
-    // Meshes should not be created outside of here, for organizational purposes
-    test_mesh: Mesh,
+    // Galaxy map markers and lines
+    map_system_mesh: Mesh,
+    map_current_system_mesh: Mesh,
+    map_lane_mesh: Mesh,
+    map_route_mesh: Mesh,
+    // Set by the game states every frame
+    pub scene: Scene,
+    pub show_hud: bool,
+    pub system_map: SystemMap,
+    pub radar: Radar,
+    // Scale from world units to the screen, before aspect correction
+    zoom: f32,
 }
 
 impl GameRenderer {
@@ -26,63 +162,680 @@ impl GameRenderer {
         let mut main_pipeline =
             MainPipeline::new(gpu_info.clone(), View::new(na::Matrix3::identity())); // --???
 
+        let meshes = MeshRegistry::load(&mut main_pipeline, MESHES_PATH)
+            .unwrap_or_else(|e| panic!("Failed to load meshes: {}", e));
+        let mesh_handle = |id| {
+            meshes
+                .handle(id)
+                .unwrap_or_else(|| panic!("No mesh called {} in {}", id, MESHES_PATH))
+        };
+        let station_mesh = mesh_handle(STATION_MESH);
+        let ship_mesh = mesh_handle(SHIP_MESH);
+        let outlaw_mesh = mesh_handle(OUTLAW_MESH);
+
+        // Asteroids are a plain polygon with a radius of 1, coloured by what they're made of
+        let asteroid_meshes = [
+            AsteroidKind::Rocky,
+            AsteroidKind::Icy,
+            AsteroidKind::Metallic,
+        ]
+        .iter()
+        .map(|&kind| {
+            let color = match kind {
+                AsteroidKind::Rocky => [0.45, 0.4, 0.35, 1.0],
+                AsteroidKind::Icy => [0.7, 0.85, 0.95, 1.0],
+                AsteroidKind::Metallic => [0.6, 0.55, 0.3, 1.0],
+            };
+            create_polygon(&mut main_pipeline, ASTEROID_SIDES, color)
+        })
+        .collect();
+
+        let starfield_pipeline = StarfieldPipeline::new(
+            gpu_info.clone(),
+            StarfieldView::new(na::Vector2::zeros(), na::Vector2::new(1.0, 1.0), 0.0),
+        );
+
+        let particle_pipeline = ParticlePipeline::new(
+            gpu_info.clone(),
+            View::new(na::Matrix3::identity()),
+            MAX_PARTICLES,
+        );
+
+        let mut sprite_pipeline =
+            SpritePipeline::new(gpu_info.clone(), View::new(na::Matrix3::identity()));
+        let sprites = load_sprites(&mut sprite_pipeline)
+            .unwrap_or_else(|e| panic!("Failed to load sprites: {}", e));
+        let (font, glyphs) = Font::load(&assets::asset_path(FONT_PATH), FONT_PIXEL_SIZE)
+            .unwrap_or_else(|e| panic!("Failed to load font: {}", e));
+        // Unlike the sprites, text is scaled to all sorts of sizes so it needs smoothing
+        let font_atlas = sprite_pipeline.upload_atlas(glyphs, wgpu::FilterMode::Linear);
+
+        // Everything above draws into the post-processing's framebuffers rather than the swapchain
+        let post_pipeline = PostPipeline::new(
+            gpu_info.clone(),
+            PostParams::new(&GraphicsSettings::default()),
+        );
+
         Self {
             gpu_info,
-            test_mesh: main_pipeline.create_mesh(
-                &[
-                    Vertex::new(na::Vector2::new(0.0, 0.0), [1.0, 1.0, 1.0, 1.0]),
-                    Vertex::new(na::Vector2::new(1.0, 0.0), [1.0, 1.0, 1.0, 1.0]),
-                    Vertex::new(na::Vector2::new(0.0, 1.0), [1.0, 1.0, 1.0, 1.0]),
-                    Vertex::new(na::Vector2::new(1.0, 1.0), [1.0, 1.0, 1.0, 1.0]),
-                ],
-                &[0, 1, 2, 2, 1, 3],
+            starfield_pipeline,
+            particle_pipeline,
+            sprite_pipeline,
+            post_pipeline,
+            sprites,
+            font: Arc::new(font),
+            font_atlas,
+            text_sprites: Vec::new(),
+            hud_commands: Vec::new(),
+            particle_instances: Vec::with_capacity(MAX_PARTICLES),
+            start: Instant::now(),
+            last_frame: Instant::now(),
+            draw_stats: DrawStats::default(),
+            asteroid_meshes,
+            beam_mesh: create_line(&mut main_pipeline, BEAM_COLOR),
+            shot_mesh: create_line(&mut main_pipeline, SHOT_COLOR),
+            shot_glow_mesh: create_line(&mut main_pipeline, SHOT_GLOW_COLOR),
+            map_system_mesh: create_polygon(&mut main_pipeline, 12, MAP_SYSTEM_COLOR),
+            map_current_system_mesh: create_polygon(
+                &mut main_pipeline,
+                12,
+                MAP_CURRENT_SYSTEM_COLOR,
             ),
+            map_lane_mesh: create_line(&mut main_pipeline, MAP_LANE_COLOR),
+            map_route_mesh: create_line(&mut main_pipeline, MAP_ROUTE_COLOR),
+            scene: Scene::System,
+            show_hud: true,
+            system_map: SystemMap::default(),
+            radar: Radar::default(),
+            zoom: GraphicsSettings::default().zoom,
+            meshes,
+            station_mesh,
+            ship_mesh,
+            outlaw_mesh,
             main_pipeline,
         }
     }
 
-    // Render simply takes a reference to a game and draws it
+    pub fn font(&self) -> Arc<Font> {
+        self.font.clone()
+    }
+
+    // Take on any graphics settings that can change without restarting
+    pub fn apply_settings(&mut self, settings: &GraphicsSettings) {
+        self.zoom = settings.zoom;
+        self.post_pipeline.params = PostParams::new(settings);
+        self.gpu_info.lock().unwrap().set_vsync(settings.vsync);
+    }
+
+    pub fn resize(&mut self, size: na::Vector2<u32>) {
+        self.gpu_info.lock().unwrap().resize(size);
+        self.post_pipeline.resize();
+    }
+
+    // Swap in an asset that changed on disk, given relative to the assets directory
+    // Returns whether it was one of the renderer's files, and everything stays as it was if it fails to load
+    pub fn reload_asset(&mut self, path: &Path) -> Result<bool, ReloadError> {
+        if path == Path::new(MESHES_PATH) {
+            self.meshes.reload(&mut self.main_pipeline, MESHES_PATH)?;
+        } else if path.starts_with(SPRITES_DIR) && path.extension() == Some("png".as_ref()) {
+            // Every sprite is packed into the one atlas, so the whole thing is rebuilt
+            self.sprites = load_sprites(&mut self.sprite_pipeline)?;
+        } else {
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    // Render simply takes a reference to a game and draws it, along with the ui built for this frame
     // Any information that needs to be accessed here should be publicly exposed in Game
-    pub fn render(&mut self, game: &Game, window: &Window) {
+    pub fn render(&mut self, game: &Game, window: &Window, ui: &[DrawCommand]) {
         // Update camera
         // Step 1. Aspect correction
         // the viewport coordinates are between -1 and 1 for each axis, but the window's width and height is not always the same
         // This causes the image to appear stretched (usually on the x axis, since width is often greater than height), so we create a matrix that corrects this
         let size = window.inner_size();
         let aspect = size.width as f32 / size.height as f32;
-        let mut transform = na::Matrix3::new_nonuniform_scaling(&if aspect >= 1.0 {
+        let aspect_scaling = if aspect >= 1.0 {
             na::Vector2::new(1.0, aspect)
         } else {
             na::Vector2::new(1.0 / aspect, 1.0)
-        });
+        };
         // Step 2. Apply zoom
-        transform.append_scaling_mut(0.2);
-        self.main_pipeline.view = View::new(
-            // --???
-            // The primary matrix accounts for the aspect ratio
-            transform,
+        let zoom = if self.scene == Scene::GalaxyMap {
+            MAP_ZOOM
+        } else {
+            self.zoom
+        };
+        // Step 3. Centre on the player, the galaxy map is centred on the middle of the galaxy instead
+        let screen_size = na::Vector2::new(size.width as f32, size.height as f32);
+        let camera = Camera {
+            position: if self.scene == Scene::GalaxyMap {
+                na::Vector2::zeros()
+            } else {
+                game.player().position
+            },
+            scale: aspect_scaling * zoom,
+            screen_size,
+        };
+        self.main_pipeline.view = View::new(camera.view_matrix());
+
+        // The stars always show the space around the player, even behind the galaxy map
+        self.starfield_pipeline.view = StarfieldView::new(
+            game.player().position,
+            aspect_scaling.map(|scale| 1.0 / (scale * self.zoom)),
+            self.start.elapsed().as_secs_f32(),
         );
 
         // Acquire target framebuffer to render into
-        let target = &self
-            .gpu_info
-            .lock()
-            .unwrap()
-            .swapchain
-            .get_current_frame()
-            .unwrap()
-            .output
-            .view;
-
-        // Render test mesh
+        // Nothing is drawn if there isn't one, e.g. while the swapchain is rebuilt after a display mode change
+        let frame = match self.gpu_info.lock().unwrap().next_frame() {
+            Some(frame) => frame,
+            None => return,
+        };
+        let framebuffers = self.post_pipeline.framebuffers();
+        let target = framebuffers.scene_target();
+
+        self.starfield_pipeline.render(target);
+
+        self.main_pipeline.stats = DrawStats::default();
+        self.sprite_pipeline.stats = DrawStats::default();
+        self.particle_pipeline.stats = DrawStats::default();
+        match self.scene {
+            Scene::GalaxyMap => self.render_galaxy_map(game, target),
+            // Space between systems is empty
+            Scene::System if !game.jump_drive.in_transit() => self.render_system(game, target),
+            // The system map is drawn with the hud, and covers up the world
+            _ => (),
+        }
+        // Only the world counts, everything in the overlay is on screen by definition
+        self.draw_stats = self.main_pipeline.stats;
+        self.draw_stats += self.sprite_pipeline.stats;
+        self.draw_stats += self.particle_pipeline.stats;
+
+        // The hud and ui on the screen go over everything else, whatever's being shown
+        // Markers over the world only make sense when the world is what's on screen
+        let frame_time = self.last_frame.elapsed();
+        self.last_frame = Instant::now();
+        self.hud_commands.clear();
+        if self.scene == Scene::SystemMap {
+            self.system_map
+                .draw(game, screen_size, &mut self.hud_commands);
+        }
+        if self.show_hud {
+            hud::draw(
+                game,
+                Some(&camera).filter(|_| self.scene == Scene::System),
+                &self.radar,
+                screen_size,
+                frame_time,
+                self.draw_stats,
+                &mut self.hud_commands,
+            );
+        }
+        self.post_pipeline.clear_overlay();
+        self.render_overlay(framebuffers.overlay_target(), screen_size, ui);
+
+        self.post_pipeline.render(&frame.output.view);
+    }
+
+    // Everything in the system the player is in
+    fn render_system(&mut self, game: &Game, target: RenderTarget) {
+        // Ships and stations use their sprite if there is one, and fall back to their mesh if not
+        let mut station_sprite_instances = Vec::new();
+        let mut station_instances = Vec::new();
+        for station in &game.economy.stations {
+            match self.sprites.region(STATION_SPRITE) {
+                Some(region) => station_sprite_instances.push(SpriteInstance::new(
+                    na::Matrix3::new_nonuniform_scaling(&region.scale(STATION_SCALE))
+                        .append_translation(&station.position),
+                    region,
+                    SPRITE_TINT,
+                )),
+                None => station_instances.push(Instance::new(
+                    na::Matrix3::new_scaling(STATION_SCALE).append_translation(&station.position),
+                )),
+            }
+        }
+
+        let mut asteroid_instances = vec![Vec::new(); self.asteroid_meshes.len()];
+        for asteroid in &game.asteroids {
+            asteroid_instances[asteroid.kind as usize].push(Instance::new(
+                na::Matrix3::new_rotation(asteroid.angle)
+                    .prepend_scaling(asteroid.radius())
+                    .append_translation(&asteroid.position),
+            ));
+        }
+
+        // The mining beam is the beam mesh stretched from the ship to wherever it ends
+        let beam_instances = game
+            .mining_laser
+            .beam()
+            .map(|beam| line_instance(beam.start, beam.end, BEAM_WIDTH))
+            .into_iter()
+            .collect::<Vec<_>>();
+        let shot_trails = game
+            .weapons
+            .projectiles()
+            .iter()
+            .map(|shot| {
+                let trail = shot.velocity.try_normalize(0.0).unwrap_or_default() * SHOT_LENGTH;
+                (shot.position - trail, shot.position)
+            })
+            .collect::<Vec<_>>();
+        let shot_instances = shot_trails
+            .iter()
+            .map(|&(start, end)| line_instance(start, end, SHOT_WIDTH))
+            .collect::<Vec<_>>();
+        let shot_glow_instances = shot_trails
+            .iter()
+            .map(|&(start, end)| line_instance(start, end, SHOT_GLOW_WIDTH))
+            .collect::<Vec<_>>();
+
+        // Asteroids are the nearest thing to planets for now, and sit under everything built or flown
+        let mut meshes_with_instances = self
+            .asteroid_meshes
+            .iter()
+            .zip(&asteroid_instances)
+            .map(|(mesh, instances)| {
+                (
+                    mesh,
+                    instances.as_slice(),
+                    BlendMode::Opaque,
+                    SortKey::new(Layer::Planets),
+                )
+            })
+            .collect::<Vec<_>>();
+        meshes_with_instances.push((
+            self.meshes.get(self.station_mesh),
+            &station_instances[..],
+            BlendMode::Opaque,
+            SortKey::new(Layer::Stations),
+        ));
+        // Under the player so the beam comes out from under the ship
+        meshes_with_instances.push((
+            &self.beam_mesh,
+            &beam_instances[..],
+            BlendMode::Opaque,
+            SortKey::new(Layer::Ships).z(-1.0),
+        ));
+        meshes_with_instances.push((
+            &self.shot_mesh,
+            &shot_instances[..],
+            BlendMode::Opaque,
+            SortKey::new(Layer::Projectiles),
+        ));
+        // Translucent, so it's drawn after the streaks and over them
+        meshes_with_instances.push((
+            &self.shot_glow_mesh,
+            &shot_glow_instances[..],
+            BlendMode::Premultiplied,
+            SortKey::new(Layer::Projectiles),
+        ));
+
+        // Ship sprites are named after their hull and drawn facing along the x axis, a quarter turn off from the ship's heading
+        let player = game.player();
+        let hull_sprite = format!("ships/{}", player.ship().hull().id);
+        let mut player_instance = Vec::new();
+        let mut ship_sprite_instances = Vec::new();
+        match self.sprites.region(&hull_sprite) {
+            Some(region) => ship_sprite_instances.push(SpriteInstance::new(
+                na::Matrix3::new_rotation(player.angle + std::f32::consts::FRAC_PI_2)
+                    .prepend_nonuniform_scaling(&region.scale(SHIP_SCALE))
+                    .append_translation(&player.position),
+                region,
+                SPRITE_TINT,
+            )),
+            None => player_instance.push(Instance::new(
+                na::Matrix3::new_rotation(player.angle)
+                    .prepend_scaling(SHIP_SCALE)
+                    .append_translation(&player.position),
+            )),
+        }
+        meshes_with_instances.push((
+            self.meshes.get(self.ship_mesh),
+            &player_instance[..],
+            BlendMode::Opaque,
+            SortKey::new(Layer::Ships),
+        ));
+        let outlaw_instances = game
+            .outlaws
+            .iter()
+            .map(|outlaw| {
+                Instance::new(
+                    na::Matrix3::new_rotation(outlaw.angle)
+                        .prepend_scaling(SHIP_SCALE)
+                        .append_translation(&outlaw.position),
+                )
+            })
+            .collect::<Vec<_>>();
+        meshes_with_instances.push((
+            self.meshes.get(self.outlaw_mesh),
+            &outlaw_instances[..],
+            BlendMode::Opaque,
+            SortKey::new(Layer::Ships),
+        ));
+
+        // Station names float just above each station
+        self.text_sprites.clear();
+        let label_style = TextStyle::new(LABEL_SIZE, LABEL_COLOR).align(Align::Center);
+        for station in &game.economy.stations {
+            self.font.layout(
+                &station.name,
+                station.position + na::Vector2::new(0.0, STATION_SCALE * 0.5 + LABEL_SIZE * 1.5),
+                &label_style,
+                &mut self.text_sprites,
+            );
+        }
+        // Wanted ships are named too, so it's clear which bounty they're for
+        for outlaw in &game.outlaws {
+            self.font.layout(
+                &outlaw.name,
+                outlaw.position + na::Vector2::new(0.0, SHIP_SCALE + LABEL_SIZE),
+                &label_style,
+                &mut self.text_sprites,
+            );
+        }
+
+        // Sprites have their transparent edges blended in, over meshes in the same layer
+        let sprite_batches = [
+            (
+                &self.sprites,
+                &station_sprite_instances[..],
+                BlendMode::Alpha,
+                SortKey::new(Layer::Stations),
+            ),
+            (
+                &self.sprites,
+                &ship_sprite_instances[..],
+                BlendMode::Alpha,
+                SortKey::new(Layer::Ships),
+            ),
+            (
+                &self.font_atlas,
+                &self.text_sprites[..],
+                BlendMode::Alpha,
+                SortKey::new(Layer::Ui),
+            ),
+        ];
+
+        self.particle_instances.clear();
+        self.particle_instances
+            .extend(game.particles.particles().iter().map(|particle| {
+                ParticleInstance::new(particle.position, particle.size, particle.color)
+            }));
+
+        // Batches from every pipeline are sorted together and drawn in one pass, so meshes and sprites stack up the same way whichever pipeline draws them
+        // Ordering is per batch, everything in a batch shares its layer and z and is drawn in the order it was given
+        // The starfield has already filled in the background
+        self.sprite_pipeline.view = self.main_pipeline.view;
+        self.particle_pipeline.view = self.main_pipeline.view;
+        let mut batches = self
+            .main_pipeline
+            .prepare(&meshes_with_instances)
+            .into_iter()
+            .map(WorldBatch::Mesh)
+            .chain(
+                self.sprite_pipeline
+                    .prepare(&sprite_batches)
+                    .into_iter()
+                    .map(WorldBatch::Sprite),
+            )
+            .chain(
+                self.particle_pipeline
+                    .prepare(&self.particle_instances, SortKey::new(Layer::Effects))
+                    .map(WorldBatch::Particles),
+            )
+            .collect::<Vec<_>>();
+        if batches.is_empty() {
+            return;
+        }
+        sort_batches(&mut batches, WorldBatch::order);
+
+        let GpuInfo { device, queue, .. } = &*self.gpu_info.lock().unwrap();
+        let mut cmd = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut rp = cmd.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("World Render Pass"),
+                color_attachments: &[target.color_attachment(wgpu::LoadOp::Load)],
+                depth_stencil_attachment: None,
+            });
+            for batch in &batches {
+                match batch {
+                    WorldBatch::Mesh(batch) => self.main_pipeline.draw(&mut rp, batch),
+                    WorldBatch::Sprite(batch) => self.sprite_pipeline.draw(&mut rp, batch),
+                    WorldBatch::Particles(batch) => self.particle_pipeline.draw(&mut rp, batch),
+                }
+            }
+        }
+        queue.submit(vec![cmd.finish()]);
+    }
+
+    // The hud and then the ui, drawn in screen space measured in pixels up from the bottom left corner of the window
+    // Both are laid out from the top left with y pointing down, so they're flipped over as they're converted to sprites
+    fn render_overlay(
+        &mut self,
+        target: RenderTarget,
+        screen_size: na::Vector2<f32>,
+        ui: &[DrawCommand],
+    ) {
+        // Boxes and lines are drawn with the font's white patch, so everything goes out in one batch and keeps its order
+        self.text_sprites.clear();
+        let flip = |point: na::Vector2<f32>| na::Vector2::new(point.x, screen_size.y - point.y);
+        for command in self.hud_commands.iter().chain(ui) {
+            match command {
+                DrawCommand::Rect { rect, color } => self.text_sprites.push(SpriteInstance::new(
+                    na::Matrix3::new_nonuniform_scaling(&rect.size)
+                        .append_translation(&flip(rect.centre())),
+                    self.font.white(),
+                    *color,
+                )),
+                DrawCommand::Text {
+                    text,
+                    position,
+                    style,
+                } => self
+                    .font
+                    .layout(text, flip(*position), style, &mut self.text_sprites),
+                DrawCommand::Line {
+                    start,
+                    end,
+                    width,
+                    color,
+                } => {
+                    let (start, end) = (flip(*start), flip(*end));
+                    let offset = end - start;
+                    self.text_sprites.push(SpriteInstance::new(
+                        (na::Matrix3::new_rotation(offset.y.atan2(offset.x))
+                            * na::Matrix3::new_nonuniform_scaling(&na::Vector2::new(
+                                offset.norm(),
+                                *width,
+                            )))
+                        .append_translation(&((start + end) / 2.0)),
+                        self.font.white(),
+                        *color,
+                    ))
+                }
+            }
+        }
+
+        self.sprite_pipeline.view = View::new(
+            na::Matrix3::new_nonuniform_scaling(&screen_size.map(|size| 2.0 / size))
+                .append_translation(&na::Vector2::new(-1.0, -1.0)),
+        );
+        self.sprite_pipeline.render(
+            target,
+            &[(
+                &self.font_atlas,
+                &self.text_sprites[..],
+                BlendMode::Alpha,
+                SortKey::new(Layer::Ui),
+            )],
+        );
+    }
+
+    // Every system as a dot with the jump lanes between them, and the autopilot route highlighted
+    fn render_galaxy_map(&mut self, game: &Game, target: RenderTarget) {
+        let galaxy = &game.galaxy;
+
+        // Each lane is stored on both its systems, only draw it once
+        let lane_instances = galaxy
+            .systems
+            .iter()
+            .enumerate()
+            .flat_map(|(a, system)| {
+                system
+                    .lanes
+                    .iter()
+                    .filter(move |&&b| a < b)
+                    .map(move |&b| (a, b))
+            })
+            .map(|(a, b)| {
+                line_instance(
+                    galaxy.system(a).position,
+                    galaxy.system(b).position,
+                    MAP_LANE_WIDTH,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let route_instances = std::iter::once(game.system())
+            .chain(game.route().iter().copied())
+            .collect::<Vec<_>>()
+            .windows(2)
+            .map(|hop| {
+                line_instance(
+                    galaxy.system(hop[0]).position,
+                    galaxy.system(hop[1]).position,
+                    MAP_LANE_WIDTH * 2.0,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let system_instances = galaxy
+            .systems
+            .iter()
+            .map(|system| {
+                Instance::new(
+                    na::Matrix3::new_scaling(MAP_SYSTEM_RADIUS)
+                        .append_translation(&system.position),
+                )
+            })
+            .collect::<Vec<_>>();
+        let current_instance = [Instance::new(
+            na::Matrix3::new_scaling(MAP_SYSTEM_RADIUS * 1.5)
+                .append_translation(&galaxy.system(game.system()).position),
+        )];
+
+        // The whole map is ui, with markers over the lanes between them
         self.main_pipeline.render(
+            target,
+            &[
+                (
+                    &self.map_lane_mesh,
+                    &lane_instances[..],
+                    BlendMode::Opaque,
+                    SortKey::new(Layer::Ui),
+                ),
+                (
+                    &self.map_system_mesh,
+                    &system_instances[..],
+                    BlendMode::Opaque,
+                    SortKey::new(Layer::Ui).z(1.0),
+                ),
+                (
+                    &self.map_current_system_mesh,
+                    &current_instance[..],
+                    BlendMode::Opaque,
+                    SortKey::new(Layer::Ui).z(2.0),
+                ),
+                // The route glows over everything it passes through
+                (
+                    &self.map_route_mesh,
+                    &route_instances[..],
+                    BlendMode::Additive,
+                    SortKey::new(Layer::Ui),
+                ),
+            ],
+        );
+
+        // System names under each marker
+        self.text_sprites.clear();
+        let label_style = TextStyle::new(MAP_LABEL_SIZE, LABEL_COLOR).align(Align::Center);
+        for system in &galaxy.systems {
+            self.font.layout(
+                &system.name,
+                system.position - na::Vector2::new(0.0, MAP_SYSTEM_RADIUS * 2.0),
+                &label_style,
+                &mut self.text_sprites,
+            );
+        }
+        self.sprite_pipeline.view = self.main_pipeline.view;
+        self.sprite_pipeline.render(
             target,
             &[(
-                &self.test_mesh,
-                &[Instance::new(
-                    na::Matrix3::identity().append_translation(&game.player().position),
-                )],
+                &self.font_atlas,
+                &self.text_sprites[..],
+                BlendMode::Alpha,
+                SortKey::new(Layer::Ui),
             )],
-        )
+        );
+    }
+}
+
+// The artwork is pixel art, so it's sampled without any smoothing
+fn load_sprites(sprite_pipeline: &mut SpritePipeline) -> Result<SpriteAtlas, AtlasError> {
+    sprite_pipeline.create_atlas(&assets::asset_path(SPRITES_DIR), wgpu::FilterMode::Nearest)
+}
+
+// A batch from any of the pipelines that draw the world, so they can all be sorted into one pass
+enum WorldBatch<'a> {
+    Mesh(MeshBatch<'a>),
+    Sprite(SpriteBatch<'a>),
+    Particles(ParticleBatch),
+}
+
+impl WorldBatch<'_> {
+    fn order(&self) -> (SortKey, BlendMode) {
+        match self {
+            WorldBatch::Mesh(batch) => (batch.key, batch.blend_mode),
+            WorldBatch::Sprite(batch) => (batch.key, batch.blend_mode),
+            WorldBatch::Particles(batch) => (batch.key, batch.blend_mode),
+        }
     }
 }
+
+// Regular polygon with a radius of 1
+fn create_polygon(pipeline: &mut MainPipeline, sides: u16, color: [f32; 4]) -> Mesh {
+    let vertices = (0..sides)
+        .map(|i| {
+            let angle = i as f32 / sides as f32 * std::f32::consts::PI * 2.0;
+            Vertex::new(na::Vector2::new(angle.cos(), angle.sin()), color)
+        })
+        .collect::<Vec<_>>();
+    let indices = (1..sides - 1)
+        .flat_map(|i| vec![0, i, i + 1])
+        .collect::<Vec<_>>();
+    pipeline.create_mesh(&vertices, &indices)
+}
+
+// Unit length quad along the x axis, centred on it vertically, to be stretched between two points with `line_instance`
+fn create_line(pipeline: &mut MainPipeline, color: [f32; 4]) -> Mesh {
+    pipeline.create_mesh(
+        &[
+            Vertex::new(na::Vector2::new(0.0, -0.5), color),
+            Vertex::new(na::Vector2::new(1.0, -0.5), color),
+            Vertex::new(na::Vector2::new(0.0, 0.5), color),
+            Vertex::new(na::Vector2::new(1.0, 0.5), color),
+        ],
+        &[0, 1, 2, 2, 1, 3],
+    )
+}
+
+fn line_instance(start: na::Vector2<f32>, end: na::Vector2<f32>, width: f32) -> Instance {
+    let offset = end - start;
+    Instance::new(
+        (na::Matrix3::new_rotation(offset.y.atan2(offset.x))
+            * na::Matrix3::new_nonuniform_scaling(&na::Vector2::new(offset.norm(), width)))
+        .append_translation(&start),
+    )
+}
@@ -0,0 +1,208 @@
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+// Largest atlas that will be built, most gpus support at least this size
+const MAX_ATLAS_SIZE: u32 = 4096;
+const MIN_ATLAS_SIZE: u32 = 256;
+// Transparent gap left around every image so filtering never pulls in its neighbours
+const PADDING: u32 = 2;
+
+// Where an image ended up in the atlas, in texture coordinates from 0 to 1
+#[derive(Clone, Copy, Debug)]
+pub struct AtlasRegion {
+    pub offset: [f32; 2],
+    pub size: [f32; 2],
+    // Size of the original image in pixels
+    pub pixels: [u32; 2],
+}
+
+impl AtlasRegion {
+    // Offset and size packed together, as they're passed to the gpu
+    pub fn uv_rect(&self) -> [f32; 4] {
+        [self.offset[0], self.offset[1], self.size[0], self.size[1]]
+    }
+
+    // Scaling for the unit quad that keeps the image's proportions, with its longer side the given length
+    pub fn scale(&self, length: f32) -> na::Vector2<f32> {
+        let longest = self.pixels[0].max(self.pixels[1]).max(1) as f32;
+        na::Vector2::new(self.pixels[0] as f32, self.pixels[1] as f32) * (length / longest)
+    }
+}
+
+// Every image from a directory packed into one square RGBA image
+pub struct PackedAtlas {
+    pub size: u32,
+    pub pixels: Vec<u8>,
+    // Keyed by the image's path relative to the directory, without the extension (e.g. "ships/shuttle")
+    pub regions: HashMap<String, AtlasRegion>,
+}
+
+#[derive(Debug)]
+pub enum AtlasError {
+    Io(PathBuf, io::Error),
+    Decode(PathBuf, png::DecodingError),
+    TooLarge,
+}
+
+impl fmt::Display for AtlasError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AtlasError::Io(path, e) => write!(f, "failed to read {}: {}", path.display(), e),
+            AtlasError::Decode(path, e) => write!(f, "failed to decode {}: {}", path.display(), e),
+            AtlasError::TooLarge => {
+                write!(f, "images don't fit in a {0}x{0} atlas", MAX_ATLAS_SIZE)
+            }
+        }
+    }
+}
+
+// An RGBA image waiting to be packed
+pub struct AtlasImage {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+// Load every PNG in a directory (and its subdirectories) and pack them into an atlas
+pub fn build(dir: &Path) -> Result<PackedAtlas, AtlasError> {
+    let mut paths = Vec::new();
+    find_pngs(dir, &mut paths)?;
+    let images = paths
+        .iter()
+        .map(|path| {
+            let name = path
+                .strip_prefix(dir)
+                .unwrap()
+                .with_extension("")
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            load_png(path, name)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    pack(images)
+}
+
+// Pack images that have already been loaded (or generated) into an atlas
+pub fn pack(mut images: Vec<AtlasImage>) -> Result<PackedAtlas, AtlasError> {
+    // Tallest first packs shelves much more tightly
+    images.sort_by(|a, b| b.height.cmp(&a.height).then(b.width.cmp(&a.width)));
+
+    // Start small and keep doubling until everything fits
+    let mut size = MIN_ATLAS_SIZE;
+    let positions = loop {
+        if let Some(positions) = place(&images, size) {
+            break positions;
+        }
+        size *= 2;
+        if size > MAX_ATLAS_SIZE {
+            return Err(AtlasError::TooLarge);
+        }
+    };
+
+    let mut pixels = vec![0; (size * size * 4) as usize];
+    let mut regions = HashMap::new();
+    for (image, &(x, y)) in images.into_iter().zip(&positions) {
+        let row_length = (image.width * 4) as usize;
+        for row in 0..image.height {
+            let src = (row * image.width * 4) as usize;
+            let dst = (((y + row) * size + x) * 4) as usize;
+            pixels[dst..dst + row_length].copy_from_slice(&image.pixels[src..src + row_length]);
+        }
+        regions.insert(
+            image.name,
+            AtlasRegion {
+                offset: [x as f32 / size as f32, y as f32 / size as f32],
+                size: [
+                    image.width as f32 / size as f32,
+                    image.height as f32 / size as f32,
+                ],
+                pixels: [image.width, image.height],
+            },
+        );
+    }
+
+    Ok(PackedAtlas {
+        size,
+        pixels,
+        regions,
+    })
+}
+
+fn find_pngs(dir: &Path, paths: &mut Vec<PathBuf>) -> Result<(), AtlasError> {
+    let entries = fs::read_dir(dir).map_err(|e| AtlasError::Io(dir.to_owned(), e))?;
+    for entry in entries {
+        let path = entry.map_err(|e| AtlasError::Io(dir.to_owned(), e))?.path();
+        if path.is_dir() {
+            find_pngs(&path, paths)?;
+        } else if path.extension() == Some("png".as_ref()) {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+// Decode a PNG into 8 bit RGBA, whatever format it was saved in
+fn load_png(path: &Path, name: String) -> Result<AtlasImage, AtlasError> {
+    let file = fs::File::open(path).map_err(|e| AtlasError::Io(path.to_owned(), e))?;
+    let mut decoder = png::Decoder::new(file);
+    // Palettes and low bit depths are expanded, and 16 bit channels cut down to 8
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let (info, mut reader) = decoder
+        .read_info()
+        .map_err(|e| AtlasError::Decode(path.to_owned(), e))?;
+    let mut buffer = vec![0; info.buffer_size()];
+    reader
+        .next_frame(&mut buffer)
+        .map_err(|e| AtlasError::Decode(path.to_owned(), e))?;
+
+    let pixels = match info.color_type {
+        png::ColorType::RGBA => buffer,
+        png::ColorType::RGB => buffer
+            .chunks(3)
+            .flat_map(|p| vec![p[0], p[1], p[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buffer
+            .chunks(2)
+            .flat_map(|p| vec![p[0], p[0], p[0], p[1]])
+            .collect(),
+        // Indexed images are expanded to one of the others, so this is only plain greyscale
+        png::ColorType::Grayscale | png::ColorType::Indexed => {
+            buffer.iter().flat_map(|&p| vec![p, p, p, 255]).collect()
+        }
+    };
+
+    Ok(AtlasImage {
+        name,
+        width: info.width,
+        height: info.height,
+        pixels,
+    })
+}
+
+// Shelf packing: images are laid out in rows, starting a new row whenever the current one is full
+// Returns the top left corner of each image, or None if they don't all fit
+fn place(images: &[AtlasImage], size: u32) -> Option<Vec<(u32, u32)>> {
+    let mut positions = Vec::with_capacity(images.len());
+    let (mut x, mut y) = (PADDING, PADDING);
+    let mut shelf_height = 0;
+    for image in images {
+        if x + image.width + PADDING > size {
+            x = PADDING;
+            y += shelf_height + PADDING;
+            shelf_height = 0;
+        }
+        if x + image.width + PADDING > size || y + image.height + PADDING > size {
+            return None;
+        }
+        positions.push((x, y));
+        x += image.width + PADDING;
+        shelf_height = shelf_height.max(image.height);
+    }
+    Some(positions)
+}
@@ -0,0 +1,264 @@
+use ab_glyph::{Font as _, ScaleFont as _};
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::gpu::{
+    atlas::{self, AtlasError, AtlasImage, AtlasRegion, PackedAtlas},
+    sprite_pipeline::SpriteInstance,
+};
+
+// Every printable ascii character is rasterised, anything else is drawn as this
+const FIRST_CHAR: char = ' ';
+const LAST_CHAR: char = '~';
+const FALLBACK_CHAR: char = '?';
+// A patch of solid white is packed alongside the glyphs, so boxes can be drawn in the same batch as text
+const WHITE_NAME: &str = "white";
+const WHITE_SIZE: u32 = 4;
+
+#[derive(Debug)]
+pub enum FontError {
+    Io(PathBuf, io::Error),
+    Invalid(PathBuf),
+    Atlas(AtlasError),
+}
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FontError::Io(path, e) => write!(f, "failed to read {}: {}", path.display(), e),
+            FontError::Invalid(path) => write!(f, "{} is not a valid font", path.display()),
+            FontError::Atlas(e) => write!(f, "failed to pack glyphs: {}", e),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+// How a piece of text is drawn
+// Sizes are in whatever units the text is being drawn in, world units for labels or pixels for the hud
+#[derive(Clone, Copy, Debug)]
+pub struct TextStyle {
+    // Height of a line
+    pub size: f32,
+    pub color: [f32; 4],
+    // Lines are aligned around the x coordinate the text is drawn at
+    pub align: Align,
+    // Lines longer than this are broken at the last space that fits
+    pub wrap_width: Option<f32>,
+}
+
+impl TextStyle {
+    pub fn new(size: f32, color: [f32; 4]) -> Self {
+        Self {
+            size,
+            color,
+            align: Align::Left,
+            wrap_width: None,
+        }
+    }
+
+    pub fn align(mut self, align: Align) -> Self {
+        self.align = align;
+        self
+    }
+
+    pub fn wrap(mut self, width: f32) -> Self {
+        self.wrap_width = Some(width);
+        self
+    }
+}
+
+struct Glyph {
+    // Empty glyphs like spaces aren't in the atlas
+    region: Option<AtlasRegion>,
+    // Edges of the glyph's image relative to the pen on the baseline, in pixels with y pointing down
+    min: [f32; 2],
+    max: [f32; 2],
+    advance: f32,
+}
+
+// A font rasterised at one size, laid out as sprites from the atlas it was packed into
+pub struct Font {
+    glyphs: HashMap<char, Glyph>,
+    // In pixels at the size the font was rasterised
+    line_height: f32,
+    ascent: f32,
+    white: AtlasRegion,
+}
+
+impl Font {
+    // Rasterise a TTF or OTF file, the glyphs come back packed in an atlas that still needs uploading
+    // Text drawn much larger than the pixel size will look blurry
+    pub fn load(path: &Path, pixel_size: f32) -> Result<(Self, PackedAtlas), FontError> {
+        let data = fs::read(path).map_err(|e| FontError::Io(path.to_owned(), e))?;
+        let font = ab_glyph::FontVec::try_from_vec(data)
+            .map_err(|_| FontError::Invalid(path.to_owned()))?;
+        let scaled = font.as_scaled(pixel_size);
+
+        let mut glyphs = HashMap::new();
+        let mut images = Vec::new();
+        for c in FIRST_CHAR..=LAST_CHAR {
+            let id = scaled.glyph_id(c);
+            let mut glyph = Glyph {
+                region: None,
+                min: [0.0, 0.0],
+                max: [0.0, 0.0],
+                advance: scaled.h_advance(id),
+            };
+            if let Some(outline) = font.outline_glyph(id.with_scale(pixel_size)) {
+                let bounds = outline.px_bounds();
+                glyph.min = [bounds.min.x, bounds.min.y];
+                glyph.max = [bounds.max.x, bounds.max.y];
+                let (width, height) = (bounds.width() as u32, bounds.height() as u32);
+                // White with the coverage as alpha, so the sprite tint sets the colour
+                let mut pixels = vec![255; (width * height * 4) as usize];
+                for alpha in pixels.iter_mut().skip(3).step_by(4) {
+                    *alpha = 0;
+                }
+                outline.draw(|x, y, coverage| {
+                    pixels[((y * width + x) * 4 + 3) as usize] = (coverage * 255.0) as u8;
+                });
+                images.push(AtlasImage {
+                    name: c.to_string(),
+                    width,
+                    height,
+                    pixels,
+                });
+            }
+            glyphs.insert(c, glyph);
+        }
+
+        images.push(AtlasImage {
+            name: WHITE_NAME.to_owned(),
+            width: WHITE_SIZE,
+            height: WHITE_SIZE,
+            pixels: vec![255; (WHITE_SIZE * WHITE_SIZE * 4) as usize],
+        });
+
+        let packed = atlas::pack(images).map_err(FontError::Atlas)?;
+        for (c, glyph) in &mut glyphs {
+            glyph.region = packed.regions.get(&c.to_string()).copied();
+        }
+        // Only the middle of the patch is used, so filtering never blends in the transparent gap around it
+        let mut white = packed.regions[WHITE_NAME];
+        for axis in 0..2 {
+            let texel = white.size[axis] / WHITE_SIZE as f32;
+            white.offset[axis] += texel;
+            white.size[axis] -= texel * 2.0;
+        }
+
+        Ok((
+            Self {
+                glyphs,
+                line_height: scaled.height() + scaled.line_gap(),
+                ascent: scaled.ascent(),
+                white,
+            },
+            packed,
+        ))
+    }
+
+    // Solid white region, tint it to draw boxes
+    pub fn white(&self) -> &AtlasRegion {
+        &self.white
+    }
+
+    fn glyph(&self, c: char) -> &Glyph {
+        self.glyphs
+            .get(&c)
+            .unwrap_or_else(|| &self.glyphs[&FALLBACK_CHAR])
+    }
+
+    // Width and height of the text once it's been wrapped
+    pub fn measure(&self, text: &str, style: &TextStyle) -> na::Vector2<f32> {
+        let lines = self.lines(text, style);
+        let width = lines.iter().map(|&(_, width)| width).fold(0.0, f32::max);
+        na::Vector2::new(width, lines.len() as f32 * style.size)
+    }
+
+    // Add a sprite for every visible character, with the top of the first line at the position
+    // Expects y to point up, so later lines go below earlier ones
+    pub fn layout(
+        &self,
+        text: &str,
+        position: na::Vector2<f32>,
+        style: &TextStyle,
+        sprites: &mut Vec<SpriteInstance>,
+    ) {
+        let scale = style.size / self.line_height;
+        for (i, (line, width)) in self.lines(text, style).into_iter().enumerate() {
+            let mut pen = position.x
+                - match style.align {
+                    Align::Left => 0.0,
+                    Align::Center => width / 2.0,
+                    Align::Right => width,
+                };
+            let baseline = position.y - i as f32 * style.size - self.ascent * scale;
+            for c in line.chars() {
+                let glyph = self.glyph(c);
+                if let Some(region) = &glyph.region {
+                    let size = na::Vector2::new(
+                        (glyph.max[0] - glyph.min[0]) * scale,
+                        (glyph.max[1] - glyph.min[1]) * scale,
+                    );
+                    let centre = na::Vector2::new(
+                        pen + (glyph.min[0] + glyph.max[0]) / 2.0 * scale,
+                        baseline - (glyph.min[1] + glyph.max[1]) / 2.0 * scale,
+                    );
+                    sprites.push(SpriteInstance::new(
+                        na::Matrix3::new_nonuniform_scaling(&size).append_translation(&centre),
+                        region,
+                        style.color,
+                    ));
+                }
+                pen += glyph.advance * scale;
+            }
+        }
+    }
+
+    fn width(&self, text: &str, scale: f32) -> f32 {
+        text.chars().map(|c| self.glyph(c).advance).sum::<f32>() * scale
+    }
+
+    // Split text into lines at newlines and wherever it needs wrapping, along with the width of each line
+    fn lines<'a>(&self, text: &'a str, style: &TextStyle) -> Vec<(&'a str, f32)> {
+        let scale = style.size / self.line_height;
+        let mut lines = Vec::new();
+        for paragraph in text.split('\n') {
+            let wrap_width = match style.wrap_width {
+                Some(wrap_width) => wrap_width,
+                None => {
+                    lines.push((paragraph, self.width(paragraph, scale)));
+                    continue;
+                }
+            };
+
+            // Greedily take as many words as fit on each line
+            // A single word that's too long on its own gets a line to itself rather than being split
+            let mut start = 0;
+            let mut end = 0;
+            for (i, _) in paragraph
+                .match_indices(' ')
+                .chain(std::iter::once((paragraph.len(), "")))
+            {
+                if end > start && self.width(&paragraph[start..i], scale) > wrap_width {
+                    let line = &paragraph[start..end];
+                    lines.push((line, self.width(line, scale)));
+                    start = end + 1;
+                }
+                end = i;
+            }
+            let line = &paragraph[start..];
+            lines.push((line, self.width(line, scale)));
+        }
+        lines
+    }
+}
@@ -1,5 +1,6 @@
 use crevice::std140::{AsStd140, Std140};
 use std::{
+    collections::HashMap,
     convert::TryInto,
     mem::size_of,
     sync::{Arc, Mutex},
@@ -8,7 +9,10 @@ use std::{
 use bytemuck::{Pod, Zeroable};
 use wgpu::util::DeviceExt;
 
-use crate::gpu::GpuInfo;
+use crate::gpu::{
+    cull, multisample_state, sort_batches, BlendMode, Bounds, DrawStats, GpuInfo, RenderTarget,
+    SortKey, HDR_FORMAT,
+};
 
 // View uniform
 // The view is basically the algorithm to convert from game coordinates into actual viewport coordinates for rendering
@@ -24,12 +28,25 @@ impl View {
             camera: camera.into(),
         }
     }
+
+    // The part of the world that ends up on screen, found by taking the corners of the screen back through the view
+    // None if the view flattens everything, when nothing can be seen anyway
+    pub fn visible_bounds(&self) -> Option<Bounds> {
+        let inverse = na::Matrix3::from(self.camera).try_inverse()?;
+        Some(Bounds::around(
+            [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+                .iter()
+                .map(|&(x, y)| inverse.transform_point(&na::Point2::new(x, y)).coords),
+        ))
+    }
 }
 
 // A single unit of vertex information
 #[derive(Clone, Copy)]
 pub struct Vertex {
     pub position: mint::Vector2<f32>,
+    // Only ever read by the shader, alpha included now that meshes can be blended
+    #[allow(dead_code)]
     pub color: [f32; 4],
 }
 unsafe impl Zeroable for Vertex {}
@@ -60,6 +77,8 @@ impl Instance {
 
 // A full gpu-uploaded mesh with instance information
 pub struct Mesh {
+    // Distance from the origin to the furthest vertex, for telling whether an instance is on screen
+    radius: f32,
     index_count: u32,
     vertex_buffer: wgpu::Buffer,
     index_buffer: wgpu::Buffer,
@@ -67,8 +86,13 @@ pub struct Mesh {
 
 pub struct MainPipeline {
     pub view: View,
+    // Added up over every render, until they're reset
+    pub stats: DrawStats,
+    // Scratch space for culling, reused by every batch
+    visible: Vec<Instance>,
     gpu_info: Arc<Mutex<GpuInfo>>,
-    pipeline: wgpu::RenderPipeline,
+    // One variant of the pipeline for each blend mode, they're otherwise identical
+    pipelines: HashMap<BlendMode, wgpu::RenderPipeline>,
     view_buffer: wgpu::Buffer,
     view_bind_group: wgpu::BindGroup,
 }
@@ -77,9 +101,8 @@ impl MainPipeline {
     pub fn new(gpu_info: Arc<Mutex<GpuInfo>>, view: View) -> Self {
         let gpu_info_ = gpu_info.clone();
         let GpuInfo {
-            adapter,
-            surface,
             device,
+            msaa_samples,
             ..
         } = &*gpu_info_.lock().unwrap();
 
@@ -113,52 +136,53 @@ impl MainPipeline {
             push_constant_ranges: &[],
         });
 
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Main Render Pipeline"),
-            layout: Some(&layout),
-            vertex: wgpu::VertexState {
-                entry_point: "main",
-                module: &vert_shader,
-                buffers: &[
-                    wgpu::VertexBufferLayout {
-                        array_stride: size_of::<Vertex>().try_into().unwrap(),
-                        step_mode: wgpu::InputStepMode::Vertex,
-                        attributes: &wgpu::vertex_attr_array![
-                            0 => Float2,
-                            1 => Float4,
-                        ],
-                    },
-                    wgpu::VertexBufferLayout {
-                        array_stride: size_of::<Instance>().try_into().unwrap(),
-                        step_mode: wgpu::InputStepMode::Instance,
-                        attributes: &wgpu::vertex_attr_array![
-                            4 => Float3,
-                            5 => Float3,
-                            6 => Float3,
-                        ],
-                    },
-                ],
-            },
-            fragment: Some(wgpu::FragmentState {
-                entry_point: "main",
-                module: &frag_shader,
-                targets: &[wgpu::ColorTargetState {
-                    format: adapter.get_swap_chain_preferred_format(surface),
-                    alpha_blend: wgpu::BlendState::REPLACE,
-                    color_blend: wgpu::BlendState::REPLACE,
-                    write_mask: wgpu::ColorWrite::ALL,
-                }],
-            }),
-            primitive: wgpu::PrimitiveState {
-                cull_mode: wgpu::CullMode::None, // TODO: correct cull mode
-                front_face: wgpu::FrontFace::Cw,
-                polygon_mode: wgpu::PolygonMode::Fill, // TODO: line
-                strip_index_format: None,
-                topology: wgpu::PrimitiveTopology::TriangleList,
-            },
-            depth_stencil: None,
-            multisample: Default::default(),
-        });
+        let create_pipeline = |blend_mode: BlendMode| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Main Render Pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    entry_point: "main",
+                    module: &vert_shader,
+                    buffers: &[
+                        wgpu::VertexBufferLayout {
+                            array_stride: size_of::<Vertex>().try_into().unwrap(),
+                            step_mode: wgpu::InputStepMode::Vertex,
+                            attributes: &wgpu::vertex_attr_array![
+                                0 => Float2,
+                                1 => Float4,
+                            ],
+                        },
+                        wgpu::VertexBufferLayout {
+                            array_stride: size_of::<Instance>().try_into().unwrap(),
+                            step_mode: wgpu::InputStepMode::Instance,
+                            attributes: &wgpu::vertex_attr_array![
+                                4 => Float3,
+                                5 => Float3,
+                                6 => Float3,
+                            ],
+                        },
+                    ],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    entry_point: "main",
+                    module: &frag_shader,
+                    targets: &[blend_mode.color_target(HDR_FORMAT)],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    cull_mode: wgpu::CullMode::None, // TODO: correct cull mode
+                    front_face: wgpu::FrontFace::Cw,
+                    polygon_mode: wgpu::PolygonMode::Fill, // TODO: line
+                    strip_index_format: None,
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                },
+                depth_stencil: None,
+                multisample: multisample_state(*msaa_samples),
+            })
+        };
+        let pipelines = BlendMode::ALL
+            .iter()
+            .map(|&blend_mode| (blend_mode, create_pipeline(blend_mode)))
+            .collect();
 
         let view_buffer = create_view_buffer(device, &view);
 
@@ -177,9 +201,11 @@ impl MainPipeline {
 
         Self {
             view,
+            stats: DrawStats::default(),
+            visible: Vec::new(),
 
             gpu_info,
-            pipeline,
+            pipelines,
             view_buffer,
             view_bind_group,
         }
@@ -189,59 +215,94 @@ impl MainPipeline {
         let GpuInfo { device, .. } = &*self.gpu_info.lock().unwrap();
 
         Mesh {
+            radius: vertices
+                .iter()
+                .map(|vertex| na::Vector2::from(vertex.position).norm())
+                .fold(0.0, f32::max),
             index_count: indices.len() as u32,
             vertex_buffer: create_vertex_buffer(device, vertices),
             index_buffer: create_index_buffer(device, indices),
         }
     }
 
-    // Render a list of instances for desired meshes
-    pub fn render(
+    // Cull and upload batches of instances ahead of drawing them, batches with nothing on screen are left out
+    pub fn prepare<'a>(
         &mut self,
-        target: &wgpu::TextureView,
-        meshes_with_instances: &[(&Mesh, &[Instance])],
-    ) {
+        meshes_with_instances: &[(&'a Mesh, &[Instance], BlendMode, SortKey)],
+    ) -> Vec<MeshBatch<'a>> {
         let GpuInfo { device, queue, .. } = &*self.gpu_info.lock().unwrap();
 
         // Update uniform
         queue.write_buffer(&self.view_buffer, 0, self.view.as_std140().as_bytes());
 
+        // Instances off screen are left out before anything is uploaded
         // TODO: maybe allocate only one instance buffer and throw all the instances in there
-        let meshes_with_instance_buffers = meshes_with_instances
+        let bounds = self.view.visible_bounds();
+        let stats = &mut self.stats;
+        let visible = &mut self.visible;
+        meshes_with_instances
             .iter()
-            .copied()
-            .map(|(mesh, instances)| {
-                (
+            .filter_map(|&(mesh, instances, blend_mode, key)| {
+                cull(
+                    instances,
+                    stats,
+                    |instance| {
+                        bounds.map_or(false, |bounds| {
+                            bounds.overlaps_instance(&instance.transform, mesh.radius)
+                        })
+                    },
+                    visible,
+                );
+                if visible.is_empty() {
+                    return None;
+                }
+                Some(MeshBatch {
                     mesh,
-                    instances.len() as u32,
-                    create_instance_buffer(device, instances),
-                )
+                    instance_count: visible.len() as u32,
+                    instance_buffer: create_instance_buffer(device, visible),
+                    blend_mode,
+                    key,
+                })
             })
-            .collect::<Vec<(&Mesh, u32, wgpu::Buffer)>>();
+            .collect()
+    }
+
+    // Draw a prepared batch into a render pass, which other pipelines may be drawing into as well
+    pub fn draw<'a>(&'a self, rp: &mut wgpu::RenderPass<'a>, batch: &'a MeshBatch) {
+        rp.set_pipeline(&self.pipelines[&batch.blend_mode]);
+        rp.set_bind_group(0, &self.view_bind_group, &[]);
+        rp.set_vertex_buffer(0, batch.mesh.vertex_buffer.slice(..));
+        rp.set_vertex_buffer(1, batch.instance_buffer.slice(..));
+        rp.set_index_buffer(batch.mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        rp.draw_indexed(0..batch.mesh.index_count, 0, 0..batch.instance_count);
+    }
+
+    // Render a list in a pass of its own of instances for desired meshes, each batch with its own blend mode and place in the draw order
+    // Batches are sorted by layer, then opaque before translucent, then by z, so the order they're given in only breaks ties
+    pub fn render(
+        &mut self,
+        target: RenderTarget,
+        meshes_with_instances: &[(&Mesh, &[Instance], BlendMode, SortKey)],
+    ) {
+        let mut batches = self.prepare(meshes_with_instances);
+        if batches.is_empty() {
+            return;
+        }
+        sort_batches(&mut batches, |batch| (batch.key, batch.blend_mode));
 
-        // Draw all instances of all meshes
+        let GpuInfo { device, queue, .. } = &*self.gpu_info.lock().unwrap();
         let mut cmd = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
         {
             let mut rp = cmd.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Main Render Pass"),
-                color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
-                    attachment: target,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                        store: true,
-                    },
-                    resolve_target: None,
-                }],
+                color_attachments: &[
+                    // Drawn over the background, which is rendered (and cleared) by the starfield pass
+                    target.color_attachment(wgpu::LoadOp::Load),
+                ],
                 depth_stencil_attachment: None,
             });
-            rp.set_pipeline(&self.pipeline);
-            rp.set_bind_group(0, &self.view_bind_group, &[]);
-
-            for (mesh, instance_count, instance_buffer) in &meshes_with_instance_buffers {
-                rp.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
-                rp.set_vertex_buffer(1, instance_buffer.slice(..));
-                rp.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-                rp.draw_indexed(0..mesh.index_count, 0, 0..*instance_count);
+            for batch in &batches {
+                self.draw(&mut rp, batch);
             }
         }
 
@@ -250,6 +311,15 @@ impl MainPipeline {
     }
 }
 
+// Instances of one mesh that have been culled and uploaded, ready to draw
+pub struct MeshBatch<'a> {
+    mesh: &'a Mesh,
+    instance_count: u32,
+    instance_buffer: wgpu::Buffer,
+    pub blend_mode: BlendMode,
+    pub key: SortKey,
+}
+
 fn create_view_buffer(device: &wgpu::Device, view: &View) -> wgpu::Buffer {
     device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
         label: Some("Main View Buffer"),
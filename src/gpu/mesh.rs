@@ -0,0 +1,287 @@
+use crate::{
+    assets::{self, AssetError},
+    gpu::main_pipeline::{MainPipeline, Mesh, Vertex},
+};
+use serde::Deserialize;
+use std::{collections::HashMap, fmt, path::Path};
+
+// A mesh as described in the meshes data file, a single outline filled in with colour
+#[derive(Deserialize, Clone, Debug)]
+pub struct MeshDef {
+    pub id: String,
+    // Corners of the outline in order, either way round
+    // The outline can be concave but mustn't cross itself, and repeating the first point at the end is fine
+    pub points: Vec<(f32, f32)>,
+    // Either one colour for the whole mesh or one per point, blended across the triangles in between
+    pub colors: Vec<[f32; 4]>,
+}
+
+#[derive(Debug)]
+pub enum MeshError {
+    Asset(AssetError),
+    Duplicate(String),
+    TooFewPoints(String),
+    TooManyPoints(String),
+    ColorCount(String),
+    // The outline crosses itself, so there's no way to fill it in
+    Triangulation(String),
+}
+
+impl fmt::Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MeshError::Asset(e) => write!(f, "{}", e),
+            MeshError::Duplicate(id) => write!(f, "more than one mesh is called {}", id),
+            MeshError::TooFewPoints(id) => write!(f, "mesh {} needs at least 3 points", id),
+            MeshError::TooManyPoints(id) => {
+                write!(f, "mesh {} has more than {} points", id, u16::MAX)
+            }
+            MeshError::ColorCount(id) => {
+                write!(f, "mesh {} needs either one colour or one per point", id)
+            }
+            MeshError::Triangulation(id) => {
+                write!(
+                    f,
+                    "mesh {} can't be triangulated, its outline crosses itself",
+                    id
+                )
+            }
+        }
+    }
+}
+
+impl From<AssetError> for MeshError {
+    fn from(e: AssetError) -> Self {
+        MeshError::Asset(e)
+    }
+}
+
+impl MeshDef {
+    // Vertices and triangle indices in the layout MainPipeline::create_mesh expects
+    pub fn build(&self) -> Result<(Vec<Vertex>, Vec<u16>), MeshError> {
+        if self.colors.len() != 1 && self.colors.len() != self.points.len() {
+            return Err(MeshError::ColorCount(self.id.clone()));
+        }
+        // Outlines are often closed off by going back to the first point, which would leave two corners in the same place
+        let mut outline = &self.points[..];
+        let mut colors = &self.colors[..];
+        if outline.len() > 1 && outline.first() == outline.last() {
+            outline = &outline[..outline.len() - 1];
+            colors = &colors[..colors.len().min(outline.len())];
+        }
+        if outline.len() < 3 {
+            return Err(MeshError::TooFewPoints(self.id.clone()));
+        }
+        if outline.len() > u16::MAX as usize {
+            return Err(MeshError::TooManyPoints(self.id.clone()));
+        }
+
+        let points = outline
+            .iter()
+            .map(|&(x, y)| na::Vector2::new(x, y))
+            .collect::<Vec<_>>();
+        let indices =
+            triangulate(&points).ok_or_else(|| MeshError::Triangulation(self.id.clone()))?;
+        let vertices = points
+            .iter()
+            .enumerate()
+            .map(|(i, &point)| Vertex::new(point, colors[i.min(colors.len() - 1)]))
+            .collect();
+        Ok((vertices, indices))
+    }
+}
+
+// Refers to a mesh in a MeshRegistry, cheaper to hold onto and look up than its name
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MeshHandle(usize);
+
+// Every mesh loaded from a data file, uploaded to the gpu and looked up by name
+pub struct MeshRegistry {
+    meshes: Vec<Mesh>,
+    handles: HashMap<String, MeshHandle>,
+}
+
+impl MeshRegistry {
+    // Load a meshes data file, relative to the assets directory
+    pub fn load(pipeline: &mut MainPipeline, path: impl AsRef<Path>) -> Result<Self, MeshError> {
+        let mut registry = Self {
+            meshes: Vec::new(),
+            handles: HashMap::new(),
+        };
+        registry.reload(pipeline, path)?;
+        Ok(registry)
+    }
+
+    // Load the data file again, swapping in the new meshes only once every one of them has built
+    // Handles stay valid, and a mesh that's gone from the file is kept as it was so nothing using it breaks
+    pub fn reload(
+        &mut self,
+        pipeline: &mut MainPipeline,
+        path: impl AsRef<Path>,
+    ) -> Result<(), MeshError> {
+        let defs: Vec<MeshDef> = assets::load_ron(path)?;
+        let mut built = Vec::with_capacity(defs.len());
+        for (i, def) in defs.iter().enumerate() {
+            if defs[..i].iter().any(|other| other.id == def.id) {
+                return Err(MeshError::Duplicate(def.id.clone()));
+            }
+            built.push(def.build()?);
+        }
+
+        for (def, (vertices, indices)) in defs.iter().zip(built) {
+            let mesh = pipeline.create_mesh(&vertices, &indices);
+            match self.handles.get(&def.id) {
+                Some(handle) => self.meshes[handle.0] = mesh,
+                None => {
+                    self.handles
+                        .insert(def.id.clone(), MeshHandle(self.meshes.len()));
+                    self.meshes.push(mesh);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn handle(&self, id: &str) -> Option<MeshHandle> {
+        self.handles.get(id).copied()
+    }
+
+    pub fn get(&self, handle: MeshHandle) -> &Mesh {
+        &self.meshes[handle.0]
+    }
+}
+
+// Split a polygon into triangles by ear clipping
+// An outline that crosses itself can't be filled in properly, and usually runs out of corners to cut off and gives None
+// Triangles always come out anticlockwise, whichever way round the points go
+pub fn triangulate(points: &[na::Vector2<f32>]) -> Option<Vec<u16>> {
+    if points.len() < 3 {
+        return None;
+    }
+    let mut remaining = (0..points.len() as u16).collect::<Vec<_>>();
+    if signed_area(points) < 0.0 {
+        remaining.reverse();
+    }
+
+    // Repeatedly cut off a corner that sticks out and has nothing else inside it, until one triangle is left
+    let mut indices = Vec::with_capacity((points.len() - 2) * 3);
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let corner = |i: usize| {
+            [
+                remaining[(i + count - 1) % count],
+                remaining[i],
+                remaining[(i + 1) % count],
+            ]
+        };
+        let ear = (0..count).find(|&i| {
+            let [a, b, c] = corner(i);
+            let (pa, pb, pc) = (points[a as usize], points[b as usize], points[c as usize]);
+            cross(pb - pa, pc - pb) > 0.0
+                && !remaining.iter().any(|&other| {
+                    ![a, b, c].contains(&other) && in_triangle(points[other as usize], pa, pb, pc)
+                })
+        })?;
+        indices.extend_from_slice(&corner(ear));
+        remaining.remove(ear);
+    }
+    indices.extend_from_slice(&remaining);
+    Some(indices)
+}
+
+// Positive when the points go anticlockwise
+fn signed_area(points: &[na::Vector2<f32>]) -> f32 {
+    points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(a, b)| cross(*a, *b))
+        .sum::<f32>()
+        / 2.0
+}
+
+fn cross(a: na::Vector2<f32>, b: na::Vector2<f32>) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+// Points on the edge count as inside, so a corner touching the triangle stops it being cut off
+fn in_triangle(
+    point: na::Vector2<f32>,
+    a: na::Vector2<f32>,
+    b: na::Vector2<f32>,
+    c: na::Vector2<f32>,
+) -> bool {
+    cross(b - a, point - a) >= 0.0
+        && cross(c - b, point - b) >= 0.0
+        && cross(a - c, point - c) >= 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outline(points: &[(f32, f32)]) -> Vec<na::Vector2<f32>> {
+        points
+            .iter()
+            .map(|&(x, y)| na::Vector2::new(x, y))
+            .collect()
+    }
+
+    // Every triangle has to be anticlockwise, and between them they have to cover exactly the outline's area
+    fn assert_fills(points: &[na::Vector2<f32>], indices: &[u16]) {
+        assert_eq!(indices.len(), (points.len() - 2) * 3);
+        let mut area = 0.0;
+        for triangle in indices.chunks(3) {
+            let (a, b, c) = (
+                points[triangle[0] as usize],
+                points[triangle[1] as usize],
+                points[triangle[2] as usize],
+            );
+            let triangle_area = cross(b - a, c - a) / 2.0;
+            assert!(triangle_area > 0.0, "{:?} isn't anticlockwise", triangle);
+            area += triangle_area;
+        }
+        assert!((area - signed_area(points).abs()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn convex_quad() {
+        let points = outline(&[(0.0, 0.0), (2.0, 0.0), (2.0, 1.0), (0.0, 1.0)]);
+        assert_fills(&points, &triangulate(&points).unwrap());
+    }
+
+    #[test]
+    fn l_shape() {
+        let points = outline(&[
+            (0.0, 0.0),
+            (2.0, 0.0),
+            (2.0, 1.0),
+            (1.0, 1.0),
+            (1.0, 2.0),
+            (0.0, 2.0),
+        ]);
+        assert_fills(&points, &triangulate(&points).unwrap());
+    }
+
+    #[test]
+    fn clockwise_outline() {
+        let points = outline(&[(0.0, 0.0), (0.0, 1.0), (1.0, 1.0), (1.0, 0.0)]);
+        assert!(signed_area(&points) < 0.0);
+        assert_fills(&points, &triangulate(&points).unwrap());
+    }
+
+    #[test]
+    fn repeated_closing_point_is_dropped() {
+        let def = MeshDef {
+            id: "closed".to_string(),
+            points: vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0), (0.0, 0.0)],
+            colors: vec![[1.0; 4]; 5],
+        };
+        let (vertices, indices) = def.build().unwrap();
+        assert_eq!(vertices.len(), 4);
+        let points = vertices
+            .iter()
+            .map(|vertex| na::Vector2::from(vertex.position))
+            .collect::<Vec<_>>();
+        assert_fills(&points, &indices);
+    }
+}
@@ -1,30 +1,254 @@
+pub mod atlas;
+pub mod font;
 pub mod main_pipeline;
+pub mod mesh;
+pub mod particle_pipeline;
+pub mod post_pipeline;
+pub mod sprite_pipeline;
+pub mod starfield_pipeline;
 
-use log::info;
+use crate::settings::{GraphicsSettings, Vsync, MSAA_SAMPLES};
+use log::{info, warn};
 use raw_window_handle::HasRawWindowHandle;
+use std::{cmp::Ordering, ops::AddAssign};
+
+// What the scene is drawn into, room for values over 1 so bright things can bloom
+// Only the post-processing's last pass draws into the swapchain
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+// How a pipeline's output is combined with what's already been drawn
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    // Overwrites whatever is underneath, alpha is ignored
+    Opaque,
+    // Standard transparency using the alpha component of the colour
+    Alpha,
+    // Transparency for colours that have already been multiplied by their alpha
+    Premultiplied,
+    // Adds onto what's underneath, for glows and light
+    Additive,
+}
+
+impl BlendMode {
+    pub const ALL: [BlendMode; 4] = [
+        BlendMode::Opaque,
+        BlendMode::Alpha,
+        BlendMode::Premultiplied,
+        BlendMode::Additive,
+    ];
+
+    pub fn color_target(self, format: wgpu::TextureFormat) -> wgpu::ColorTargetState {
+        let (color_blend, alpha_blend) = match self {
+            BlendMode::Opaque => (wgpu::BlendState::REPLACE, wgpu::BlendState::REPLACE),
+            BlendMode::Alpha => (
+                blend(
+                    wgpu::BlendFactor::SrcAlpha,
+                    wgpu::BlendFactor::OneMinusSrcAlpha,
+                ),
+                blend(wgpu::BlendFactor::One, wgpu::BlendFactor::OneMinusSrcAlpha),
+            ),
+            BlendMode::Premultiplied => (
+                blend(wgpu::BlendFactor::One, wgpu::BlendFactor::OneMinusSrcAlpha),
+                blend(wgpu::BlendFactor::One, wgpu::BlendFactor::OneMinusSrcAlpha),
+            ),
+            BlendMode::Additive => (
+                blend(wgpu::BlendFactor::SrcAlpha, wgpu::BlendFactor::One),
+                blend(wgpu::BlendFactor::One, wgpu::BlendFactor::One),
+            ),
+        };
+        wgpu::ColorTargetState {
+            format,
+            alpha_blend,
+            color_blend,
+            write_mask: wgpu::ColorWrite::ALL,
+        }
+    }
+
+    // Within a layer, batches are drawn in this order so translucent things always end up over the opaque things behind them
+    // Additive blending doesn't care about order, so it goes last where it can brighten everything
+    pub fn draw_order(self) -> u32 {
+        match self {
+            BlendMode::Opaque => 0,
+            BlendMode::Alpha | BlendMode::Premultiplied => 1,
+            BlendMode::Additive => 2,
+        }
+    }
+}
+
+// Every pipeline is built with the same sample count, so they can all draw into the same framebuffer
+pub fn multisample_state(msaa_samples: u32) -> wgpu::MultisampleState {
+    wgpu::MultisampleState {
+        count: msaa_samples,
+        mask: !0,
+        alpha_to_coverage_enabled: false,
+    }
+}
+
+// Broad groups of things, drawn bottom to top whatever order they were submitted in
+// The starfield isn't one of them, it's drawn in a pass of its own before anything else
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Layer {
+    Planets,
+    Stations,
+    Ships,
+    Projectiles,
+    Effects,
+    Ui,
+}
+
+// Where a batch is drawn relative to everything else, by layer and then by z within the layer
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SortKey {
+    pub layer: Layer,
+    pub z: f32,
+}
+
+impl SortKey {
+    pub fn new(layer: Layer) -> Self {
+        Self { layer, z: 0.0 }
+    }
+
+    // Higher is drawn later, over anything lower in the same layer
+    pub fn z(self, z: f32) -> Self {
+        Self { z, ..self }
+    }
+}
+
+// Put batches in the order they're drawn: by layer, then opaque before translucent, then by z
+// Stable, so batches that tie on all of those keep the order they were given in
+pub fn sort_batches<T>(batches: &mut [T], key: impl Fn(&T) -> (SortKey, BlendMode)) {
+    batches.sort_by(|a, b| {
+        let ((a_key, a_blend_mode), (b_key, b_blend_mode)) = (key(a), key(b));
+        a_key
+            .layer
+            .cmp(&b_key.layer)
+            .then(a_blend_mode.draw_order().cmp(&b_blend_mode.draw_order()))
+            .then(a_key.z.partial_cmp(&b_key.z).unwrap_or(Ordering::Equal))
+    });
+}
+
+// An axis aligned rectangle in world space
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds {
+    pub min: na::Vector2<f32>,
+    pub max: na::Vector2<f32>,
+}
+
+impl Bounds {
+    // The smallest bounds around all the points
+    pub fn around(points: impl IntoIterator<Item = na::Vector2<f32>>) -> Self {
+        let mut points = points.into_iter();
+        let first = points.next().unwrap_or_else(na::Vector2::zeros);
+        points.fold(
+            Self {
+                min: first,
+                max: first,
+            },
+            |bounds, point| Self {
+                min: bounds.min.inf(&point),
+                max: bounds.max.sup(&point),
+            },
+        )
+    }
+
+    // Tested against the circle's bounding square, which lets a few things just past the corners through
+    pub fn overlaps_circle(&self, centre: na::Vector2<f32>, radius: f32) -> bool {
+        centre.x + radius >= self.min.x
+            && centre.x - radius <= self.max.x
+            && centre.y + radius >= self.min.y
+            && centre.y - radius <= self.max.y
+    }
+
+    // Whether an instance could be seen, given the radius around its origin it fits in before it's transformed
+    pub fn overlaps_instance(&self, transform: &mint::ColumnMatrix3<f32>, radius: f32) -> bool {
+        // Stretched by whichever axis is scaled the most
+        let scale = na::Vector2::new(transform.x.x, transform.x.y)
+            .norm()
+            .max(na::Vector2::new(transform.y.x, transform.y.y).norm());
+        self.overlaps_circle(
+            na::Vector2::new(transform.z.x, transform.z.y),
+            radius * scale,
+        )
+    }
+}
+
+// How many instances were sent to the gpu, and how many were left out for being off screen
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DrawStats {
+    pub drawn: usize,
+    pub culled: usize,
+}
+
+impl AddAssign for DrawStats {
+    fn add_assign(&mut self, other: Self) {
+        self.drawn += other.drawn;
+        self.culled += other.culled;
+    }
+}
+
+// Fill `visible` with the instances that can be seen, counting them and the ones that can't in the stats
+// The vec is kept between calls, so once it's grown big enough culling doesn't allocate
+pub fn cull<T: Copy>(
+    instances: &[T],
+    stats: &mut DrawStats,
+    is_visible: impl Fn(&T) -> bool,
+    visible: &mut Vec<T>,
+) {
+    visible.clear();
+    visible.extend(
+        instances
+            .iter()
+            .copied()
+            .filter(|instance| is_visible(instance)),
+    );
+    stats.drawn += visible.len();
+    stats.culled += instances.len() - visible.len();
+}
+
+fn blend(src_factor: wgpu::BlendFactor, dst_factor: wgpu::BlendFactor) -> wgpu::BlendState {
+    wgpu::BlendState {
+        src_factor,
+        dst_factor,
+        operation: wgpu::BlendOperation::Add,
+    }
+}
 
 pub struct GpuInfo {
+    // Never used after startup, but the surface was made from it so it's kept alive alongside
+    #[allow(dead_code)]
     pub instance: wgpu::Instance,
     pub surface: wgpu::Surface,
     pub adapter: wgpu::Adapter,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub swapchain: wgpu::SwapChain,
+    // Kept so the swapchain can be rebuilt when the window or settings change
+    pub swapchain_desc: wgpu::SwapChainDescriptor,
+    // Samples per pixel every pipeline is built with, 1 for no multisampling
+    pub msaa_samples: u32,
 }
 
 impl GpuInfo {
-    pub async fn new<W: HasRawWindowHandle>(window: &W, window_size: &na::Vector2<u32>) -> Self {
-        let instance = wgpu::Instance::new(wgpu::BackendBit::PRIMARY);
+    pub async fn new<W: HasRawWindowHandle>(
+        window: &W,
+        window_size: &na::Vector2<u32>,
+        settings: &GraphicsSettings,
+    ) -> Self {
+        let instance = wgpu::Instance::new(settings.backend.backend_bits());
         let surface = unsafe { instance.create_surface(window) };
         let adapter = instance
             .request_adapter(&wgpu::RequestAdapterOptions {
                 compatible_surface: Some(&surface),
-                power_preference: wgpu::PowerPreference::HighPerformance,
+                power_preference: settings.adapter.power_preference(),
             })
             .await
             .expect("Failed to get a suitable render adapter");
 
-        info!("Selected GPU: {}", adapter.get_info().name);
+        info!(
+            "Selected GPU: {} ({:?})",
+            adapter.get_info().name,
+            adapter.get_info().backend
+        );
 
         let (device, queue) = adapter
             .request_device(
@@ -36,16 +260,25 @@ impl GpuInfo {
             .await
             .expect("Failed to create render device");
 
-        let swapchain = device.create_swap_chain(
-            &surface,
-            &wgpu::SwapChainDescriptor {
-                width: window_size.x,
-                height: window_size.y,
-                format: adapter.get_swap_chain_preferred_format(&surface),
-                present_mode: wgpu::PresentMode::Fifo,
-                usage: wgpu::TextureUsage::RENDER_ATTACHMENT
-            },
-        );
+        let swapchain_desc = wgpu::SwapChainDescriptor {
+            width: window_size.x,
+            height: window_size.y,
+            format: adapter.get_swap_chain_preferred_format(&surface),
+            present_mode: present_mode(adapter.get_info().backend, settings.vsync),
+            usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
+        };
+        let swapchain = device.create_swap_chain(&surface, &swapchain_desc);
+
+        // The settings file can hold anything, so fall back to no multisampling rather than fail to build the pipelines
+        let msaa_samples = if MSAA_SAMPLES.contains(&settings.msaa_samples) {
+            settings.msaa_samples
+        } else {
+            warn!(
+                "{}x MSAA isn't supported, turning it off",
+                settings.msaa_samples
+            );
+            1
+        };
 
         Self {
             instance,
@@ -54,6 +287,200 @@ impl GpuInfo {
             device,
             queue,
             swapchain,
+            swapchain_desc,
+            msaa_samples,
+        }
+    }
+
+    pub fn resize(&mut self, size: na::Vector2<u32>) {
+        // Minimised windows have no size, and a swapchain can't be empty
+        if size.x == 0 || size.y == 0 {
+            return;
+        }
+        self.swapchain_desc.width = size.x;
+        self.swapchain_desc.height = size.y;
+        self.recreate_swapchain();
+    }
+
+    pub fn set_vsync(&mut self, vsync: Vsync) {
+        let present_mode = present_mode(self.adapter.get_info().backend, vsync);
+        if self.swapchain_desc.present_mode != present_mode {
+            self.swapchain_desc.present_mode = present_mode;
+            self.recreate_swapchain();
+        }
+    }
+
+    // The next image to draw into, None if there isn't one to draw this frame
+    pub fn next_frame(&mut self) -> Option<wgpu::SwapChainFrame> {
+        match self.swapchain.get_current_frame() {
+            Ok(frame) => Some(frame),
+            // The surface changed underneath the swapchain, e.g. going in or out of exclusive fullscreen
+            Err(wgpu::SwapChainError::Outdated) | Err(wgpu::SwapChainError::Lost) => {
+                self.recreate_swapchain();
+                None
+            }
+            Err(wgpu::SwapChainError::Timeout) => {
+                warn!("Timed out waiting for the next frame");
+                None
+            }
+            Err(e @ wgpu::SwapChainError::OutOfMemory) => {
+                panic!("Failed to get the next frame: {}", e)
+            }
+        }
+    }
+
+    fn recreate_swapchain(&mut self) {
+        self.swapchain = self
+            .device
+            .create_swap_chain(&self.surface, &self.swapchain_desc);
+    }
+}
+
+// Where a pass draws
+// With multisampling that's the multisampled framebuffer, resolved into the framebuffer proper at the end of each pass
+// Every pass resolves, which costs a little bandwidth but means whichever pass happens to be last leaves the finished image
+#[derive(Clone, Copy)]
+pub struct RenderTarget<'a> {
+    view: &'a wgpu::TextureView,
+    resolve_target: Option<&'a wgpu::TextureView>,
+}
+
+impl<'a> RenderTarget<'a> {
+    pub fn new(
+        framebuffer: &'a wgpu::TextureView,
+        msaa_framebuffer: Option<&'a wgpu::TextureView>,
+    ) -> Self {
+        match msaa_framebuffer {
+            Some(msaa_framebuffer) => Self {
+                view: msaa_framebuffer,
+                resolve_target: Some(framebuffer),
+            },
+            None => Self {
+                view: framebuffer,
+                resolve_target: None,
+            },
+        }
+    }
+
+    pub fn color_attachment(
+        self,
+        load: wgpu::LoadOp<wgpu::Color>,
+    ) -> wgpu::RenderPassColorAttachmentDescriptor<'a> {
+        wgpu::RenderPassColorAttachmentDescriptor {
+            attachment: self.view,
+            ops: wgpu::Operations { load, store: true },
+            resolve_target: self.resolve_target,
+        }
+    }
+}
+
+// wgpu can't be asked which present modes a surface supports yet, so this is what each backend is known to offer
+// Vulkan depends on the driver, and if it turns a mode down wgpu falls back to fifo by itself
+fn supported_present_modes(backend: wgpu::Backend) -> &'static [wgpu::PresentMode] {
+    use wgpu::PresentMode::*;
+    match backend {
+        wgpu::Backend::Vulkan => &[Fifo, Mailbox, Immediate],
+        wgpu::Backend::Metal | wgpu::Backend::Dx12 | wgpu::Backend::Dx11 => &[Fifo, Immediate],
+        _ => &[Fifo],
+    }
+}
+
+// The first present mode for the vsync setting that the backend supports
+fn present_mode(backend: wgpu::Backend, vsync: Vsync) -> wgpu::PresentMode {
+    let supported = supported_present_modes(backend);
+    let present_mode = vsync
+        .present_modes()
+        .iter()
+        .copied()
+        .find(|mode| supported.contains(mode))
+        .unwrap_or(wgpu::PresentMode::Fifo);
+    if present_mode != vsync.present_modes()[0] {
+        info!(
+            "{:?} doesn't support {:?} presentation, using {:?}",
+            backend,
+            vsync.present_modes()[0],
+            present_mode
+        );
+    }
+    present_mode
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    // Fisher-Yates, so every order is as likely as any other
+    fn shuffle<T>(items: &mut [T], rng: &mut Rng) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, rng.below(i + 1));
+        }
+    }
+
+    fn ids(batches: &[(usize, SortKey, BlendMode)]) -> Vec<usize> {
+        batches.iter().map(|&(id, ..)| id).collect()
+    }
+
+    #[test]
+    fn order_doesnt_depend_on_submission_order() {
+        // Listed in the order they should be drawn
+        let expected = [
+            (SortKey::new(Layer::Planets), BlendMode::Opaque),
+            (SortKey::new(Layer::Planets), BlendMode::Additive),
+            (SortKey::new(Layer::Stations).z(-1.0), BlendMode::Opaque),
+            (SortKey::new(Layer::Stations), BlendMode::Opaque),
+            (SortKey::new(Layer::Stations).z(-2.0), BlendMode::Alpha),
+            (
+                SortKey::new(Layer::Stations).z(3.0),
+                BlendMode::Premultiplied,
+            ),
+            (SortKey::new(Layer::Ships).z(-1.0), BlendMode::Opaque),
+            (SortKey::new(Layer::Ships), BlendMode::Alpha),
+            (SortKey::new(Layer::Projectiles), BlendMode::Opaque),
+            (SortKey::new(Layer::Projectiles), BlendMode::Premultiplied),
+            (SortKey::new(Layer::Effects).z(1.0), BlendMode::Additive),
+            (SortKey::new(Layer::Effects).z(2.0), BlendMode::Additive),
+            (SortKey::new(Layer::Ui), BlendMode::Alpha),
+        ];
+        let mut batches = expected
+            .iter()
+            .enumerate()
+            .map(|(id, &(key, blend_mode))| (id, key, blend_mode))
+            .collect::<Vec<_>>();
+
+        let mut rng = Rng::new(0x5047);
+        for _ in 0..50 {
+            shuffle(&mut batches, &mut rng);
+            sort_batches(&mut batches, |&(_, key, blend_mode)| (key, blend_mode));
+            assert_eq!(ids(&batches), (0..expected.len()).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn ties_keep_submission_order() {
+        let tied = (SortKey::new(Layer::Ships), BlendMode::Alpha);
+        let mut batches = (0..8)
+            .map(|id| {
+                let (key, blend_mode) = if id % 2 == 0 {
+                    tied
+                } else {
+                    (SortKey::new(Layer::Planets).z(id as f32), BlendMode::Opaque)
+                };
+                (id, key, blend_mode)
+            })
+            .collect::<Vec<_>>();
+
+        let mut rng = Rng::new(0x5047);
+        for _ in 0..50 {
+            shuffle(&mut batches, &mut rng);
+            let submitted = batches
+                .iter()
+                .filter(|&&(_, key, blend_mode)| (key, blend_mode) == tied)
+                .map(|&(id, ..)| id)
+                .collect::<Vec<_>>();
+            sort_batches(&mut batches, |&(_, key, blend_mode)| (key, blend_mode));
+            assert_eq!(ids(&batches[..4]), vec![1, 3, 5, 7]);
+            assert_eq!(ids(&batches[4..]), submitted);
         }
     }
 }
@@ -0,0 +1,219 @@
+use crevice::std140::{AsStd140, Std140};
+use std::{
+    convert::TryInto,
+    mem::size_of,
+    sync::{Arc, Mutex},
+};
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+use crate::gpu::{
+    cull, main_pipeline::View, multisample_state, BlendMode, DrawStats, GpuInfo, SortKey,
+    HDR_FORMAT,
+};
+
+// Everything the gpu needs to draw one particle
+// The quad itself is generated in the vertex shader, so this is the only data uploaded per particle
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct ParticleInstance {
+    pub position: mint::Vector2<f32>,
+    pub size: f32,
+    pub color: [f32; 4],
+}
+unsafe impl Zeroable for ParticleInstance {}
+unsafe impl Pod for ParticleInstance {}
+impl ParticleInstance {
+    pub fn new(position: impl Into<mint::Vector2<f32>>, size: f32, color: [f32; 4]) -> Self {
+        Self {
+            position: position.into(),
+            size,
+            color,
+        }
+    }
+}
+
+// Draws particles as soft additive quads on top of whatever has already been rendered
+pub struct ParticlePipeline {
+    pub view: View,
+    // Added up over every render, until they're reset
+    pub stats: DrawStats,
+    // Scratch space for culling, reused every frame
+    visible: Vec<ParticleInstance>,
+    gpu_info: Arc<Mutex<GpuInfo>>,
+    pipeline: wgpu::RenderPipeline,
+    view_buffer: wgpu::Buffer,
+    view_bind_group: wgpu::BindGroup,
+    // Allocated once at full capacity and rewritten every frame
+    instance_buffer: wgpu::Buffer,
+    capacity: usize,
+}
+
+impl ParticlePipeline {
+    pub fn new(gpu_info: Arc<Mutex<GpuInfo>>, view: View, capacity: usize) -> Self {
+        let gpu_info_ = gpu_info.clone();
+        let GpuInfo {
+            device,
+            msaa_samples,
+            ..
+        } = &*gpu_info_.lock().unwrap();
+
+        let view_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Particle View Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(
+                            size_of::<<View as AsStd140>::Std140Type>()
+                                .try_into()
+                                .unwrap(),
+                        ),
+                    },
+                    count: None,
+                    visibility: wgpu::ShaderStage::VERTEX,
+                }],
+            });
+
+        let vert_shader =
+            device.create_shader_module(&wgpu::include_spirv!("shaders/particle.vert.spv"));
+        let frag_shader =
+            device.create_shader_module(&wgpu::include_spirv!("shaders/particle.frag.spv"));
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Particle Pipeline Layout"),
+            bind_group_layouts: &[&view_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Particle Render Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                entry_point: "main",
+                module: &vert_shader,
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: size_of::<ParticleInstance>().try_into().unwrap(),
+                    step_mode: wgpu::InputStepMode::Instance,
+                    attributes: &wgpu::vertex_attr_array![
+                        0 => Float2,
+                        1 => Float,
+                        2 => Float4,
+                    ],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                entry_point: "main",
+                module: &frag_shader,
+                // Additive, so overlapping particles build up into a glow and draw order doesn't matter
+                targets: &[BlendMode::Additive.color_target(HDR_FORMAT)],
+            }),
+            primitive: wgpu::PrimitiveState {
+                cull_mode: wgpu::CullMode::None,
+                front_face: wgpu::FrontFace::Ccw,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                strip_index_format: None,
+                topology: wgpu::PrimitiveTopology::TriangleList,
+            },
+            depth_stencil: None,
+            multisample: multisample_state(*msaa_samples),
+        });
+
+        let view_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Particle View Buffer"),
+            contents: view.as_std140().as_bytes(),
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        });
+
+        let view_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Particle View Bind Group"),
+            layout: &view_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer {
+                    buffer: &view_buffer,
+                    offset: 0,
+                    size: None,
+                },
+            }],
+        });
+
+        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Particle Instance Buffer"),
+            size: (capacity * size_of::<ParticleInstance>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            view,
+            stats: DrawStats::default(),
+            visible: Vec::new(),
+
+            gpu_info,
+            pipeline,
+            view_buffer,
+            view_bind_group,
+            instance_buffer,
+            capacity,
+        }
+    }
+
+    // Cull and upload particles ahead of drawing them, anything off screen or past the pipeline's capacity is left out
+    // The batch is drawn with additive blending at the given place in the draw order
+    pub fn prepare(
+        &mut self,
+        instances: &[ParticleInstance],
+        key: SortKey,
+    ) -> Option<ParticleBatch> {
+        // Particles are squares, size across
+        let bounds = self.view.visible_bounds();
+        cull(
+            instances,
+            &mut self.stats,
+            |particle| {
+                bounds.map_or(false, |bounds| {
+                    bounds.overlaps_circle(
+                        particle.position.into(),
+                        particle.size * std::f32::consts::FRAC_1_SQRT_2,
+                    )
+                })
+            },
+            &mut self.visible,
+        );
+        let instances = &self.visible[..self.visible.len().min(self.capacity)];
+        if instances.is_empty() {
+            return None;
+        }
+        let GpuInfo { queue, .. } = &*self.gpu_info.lock().unwrap();
+
+        // Update uniform and instances
+        queue.write_buffer(&self.view_buffer, 0, self.view.as_std140().as_bytes());
+        queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(instances));
+
+        Some(ParticleBatch {
+            instance_count: instances.len() as u32,
+            blend_mode: BlendMode::Additive,
+            key,
+        })
+    }
+
+    // Draw the prepared particles into a render pass, which other pipelines may be drawing into as well
+    pub fn draw<'a>(&'a self, rp: &mut wgpu::RenderPass<'a>, batch: &ParticleBatch) {
+        rp.set_pipeline(&self.pipeline);
+        rp.set_bind_group(0, &self.view_bind_group, &[]);
+        rp.set_vertex_buffer(0, self.instance_buffer.slice(..));
+        rp.draw(0..6, 0..batch.instance_count);
+    }
+}
+
+// Particles that have been culled and uploaded into the pipeline's instance buffer, ready to draw
+// There's only ever one of these per frame, since they all share that buffer
+pub struct ParticleBatch {
+    instance_count: u32,
+    pub blend_mode: BlendMode,
+    pub key: SortKey,
+}
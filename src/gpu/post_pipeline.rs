@@ -0,0 +1,484 @@
+use crevice::std140::{AsStd140, Std140};
+use std::{
+    convert::TryInto,
+    mem::size_of,
+    sync::{Arc, Mutex},
+};
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    gpu::{GpuInfo, RenderTarget, HDR_FORMAT},
+    settings::GraphicsSettings,
+};
+
+// Smaller and smaller copies of the bright parts of the scene, each half the size of the last
+const BLOOM_LEVELS: usize = 5;
+// How strong each effect is when it's switched on
+const BLOOM_INTENSITY: f32 = 0.6;
+const VIGNETTE_STRENGTH: f32 = 0.35;
+// Offset of the red and blue channels at the edges, as a fraction of the distance from the centre
+const ABERRATION_STRENGTH: f32 = 0.012;
+
+// Post-processing uniform
+// Effects that are switched off have a strength of 0
+#[derive(AsStd140, Clone, Copy)]
+pub struct PostParams {
+    pub bloom_intensity: f32,
+    pub vignette: f32,
+    pub aberration: f32,
+    // 1 to tone map, 0 to clamp
+    pub tonemap: u32,
+}
+unsafe impl Zeroable for PostParams {}
+unsafe impl Pod for PostParams {}
+impl PostParams {
+    pub fn new(settings: &GraphicsSettings) -> Self {
+        let strength = |enabled: bool, strength: f32| if enabled { strength } else { 0.0 };
+        Self {
+            bloom_intensity: strength(settings.bloom, BLOOM_INTENSITY),
+            vignette: strength(settings.vignette, VIGNETTE_STRENGTH),
+            aberration: strength(settings.chromatic_aberration, ABERRATION_STRENGTH),
+            tonemap: settings.tonemapping as u32,
+        }
+    }
+}
+
+// Everything is drawn into these before post-processing, they're all the size of the swapchain
+pub struct Framebuffers {
+    // The world, in high dynamic range so bright things can go over 1 and bloom
+    scene: wgpu::TextureView,
+    // The hud and ui, kept apart so they aren't tone mapped or blurred
+    overlay: wgpu::TextureView,
+    // Shared by the scene and the overlay, which are drawn and resolved out of it one after the other
+    msaa: Option<wgpu::TextureView>,
+}
+
+impl Framebuffers {
+    pub fn scene_target(&self) -> RenderTarget<'_> {
+        RenderTarget::new(&self.scene, self.msaa.as_ref())
+    }
+
+    pub fn overlay_target(&self) -> RenderTarget<'_> {
+        RenderTarget::new(&self.overlay, self.msaa.as_ref())
+    }
+}
+
+// Everything that depends on the size of the swapchain, rebuilt whenever it changes
+struct SizedResources {
+    // Shared so a frame can hold onto it while the passes borrow the renderer
+    framebuffers: Arc<Framebuffers>,
+    scene_bind_group: wgpu::BindGroup,
+    // Each level's view to draw into, and a bind group to sample it in the next pass
+    bloom_levels: Vec<(wgpu::TextureView, wgpu::BindGroup)>,
+    composite_bind_group: wgpu::BindGroup,
+}
+
+// Turns the scene into the final image: bright-pass, blurred into bloom, tone mapped, vignetted, then the overlay on top
+pub struct PostPipeline {
+    pub params: PostParams,
+    gpu_info: Arc<Mutex<GpuInfo>>,
+    bright_pass_pipeline: wgpu::RenderPipeline,
+    bloom_down_pipeline: wgpu::RenderPipeline,
+    bloom_up_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+    source_bind_group_layout: wgpu::BindGroupLayout,
+    composite_bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    params_buffer: wgpu::Buffer,
+    sized: SizedResources,
+}
+
+impl PostPipeline {
+    pub fn new(gpu_info: Arc<Mutex<GpuInfo>>, params: PostParams) -> Self {
+        let gpu_info_ = gpu_info.clone();
+        let gpu = &*gpu_info_.lock().unwrap();
+        let device = &gpu.device;
+
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+            },
+            count: None,
+            visibility: wgpu::ShaderStage::FRAGMENT,
+        };
+        let sampler_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            ty: wgpu::BindingType::Sampler {
+                filtering: true,
+                comparison: false,
+            },
+            count: None,
+            visibility: wgpu::ShaderStage::FRAGMENT,
+        };
+
+        // One texture to read from, for the bright-pass and blurs
+        let source_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Post Source Bind Group Layout"),
+                entries: &[texture_entry(0), sampler_entry(1)],
+            });
+
+        let composite_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Post Composite Bind Group Layout"),
+                entries: &[
+                    texture_entry(0),
+                    texture_entry(1),
+                    texture_entry(2),
+                    sampler_entry(3),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: wgpu::BufferSize::new(
+                                size_of::<<PostParams as AsStd140>::Std140Type>()
+                                    .try_into()
+                                    .unwrap(),
+                            ),
+                        },
+                        count: None,
+                        visibility: wgpu::ShaderStage::FRAGMENT,
+                    },
+                ],
+            });
+
+        let vert_shader =
+            device.create_shader_module(&wgpu::include_spirv!("shaders/post.vert.spv"));
+        let source_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Post Source Pipeline Layout"),
+            bind_group_layouts: &[&source_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let composite_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Post Composite Pipeline Layout"),
+            bind_group_layouts: &[&composite_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let create_pipeline = |label, layout, frag_shader, format| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(layout),
+                vertex: wgpu::VertexState {
+                    entry_point: "main",
+                    module: &vert_shader,
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    entry_point: "main",
+                    module: &device.create_shader_module(&frag_shader),
+                    targets: &[wgpu::ColorTargetState {
+                        format,
+                        alpha_blend: wgpu::BlendState::REPLACE,
+                        color_blend: wgpu::BlendState::REPLACE,
+                        write_mask: wgpu::ColorWrite::ALL,
+                    }],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    cull_mode: wgpu::CullMode::None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    strip_index_format: None,
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                },
+                depth_stencil: None,
+                // Every pass covers whole pixels of a single sampled texture
+                multisample: Default::default(),
+            })
+        };
+        let bright_pass_pipeline = create_pipeline(
+            "Bright Pass Render Pipeline",
+            &source_layout,
+            wgpu::include_spirv!("shaders/bright_pass.frag.spv"),
+            HDR_FORMAT,
+        );
+        let bloom_down_pipeline = create_pipeline(
+            "Bloom Downsample Render Pipeline",
+            &source_layout,
+            wgpu::include_spirv!("shaders/bloom_down.frag.spv"),
+            HDR_FORMAT,
+        );
+        let bloom_up_pipeline = create_pipeline(
+            "Bloom Upsample Render Pipeline",
+            &source_layout,
+            wgpu::include_spirv!("shaders/bloom_up.frag.spv"),
+            HDR_FORMAT,
+        );
+        let composite_pipeline = create_pipeline(
+            "Composite Render Pipeline",
+            &composite_layout,
+            wgpu::include_spirv!("shaders/composite.frag.spv"),
+            gpu.swapchain_desc.format,
+        );
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Post Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Post Params Buffer"),
+            contents: params.as_std140().as_bytes(),
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        });
+
+        let sized = create_sized_resources(
+            gpu,
+            &source_bind_group_layout,
+            &composite_bind_group_layout,
+            &sampler,
+            &params_buffer,
+        );
+
+        Self {
+            params,
+            gpu_info,
+            bright_pass_pipeline,
+            bloom_down_pipeline,
+            bloom_up_pipeline,
+            composite_pipeline,
+            source_bind_group_layout,
+            composite_bind_group_layout,
+            sampler,
+            params_buffer,
+            sized,
+        }
+    }
+
+    pub fn framebuffers(&self) -> Arc<Framebuffers> {
+        self.sized.framebuffers.clone()
+    }
+
+    // Build the framebuffers again at the swapchain's current size
+    pub fn resize(&mut self) {
+        let gpu = &*self.gpu_info.lock().unwrap();
+        self.sized = create_sized_resources(
+            gpu,
+            &self.source_bind_group_layout,
+            &self.composite_bind_group_layout,
+            &self.sampler,
+            &self.params_buffer,
+        );
+    }
+
+    // The overlay starts off see-through every frame, since the ui's passes draw over what's there
+    pub fn clear_overlay(&self) {
+        let GpuInfo { device, queue, .. } = &*self.gpu_info.lock().unwrap();
+        let framebuffers = &self.sized.framebuffers;
+
+        let mut cmd = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        cmd.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Overlay Clear Pass"),
+            color_attachments: &[framebuffers
+                .overlay_target()
+                .color_attachment(wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT))],
+            depth_stencil_attachment: None,
+        });
+        queue.submit(vec![cmd.finish()]);
+    }
+
+    // Run the whole chain and write the finished frame into the target
+    pub fn render(&mut self, target: &wgpu::TextureView) {
+        let GpuInfo { device, queue, .. } = &*self.gpu_info.lock().unwrap();
+        let sized = &self.sized;
+
+        // Update uniform
+        queue.write_buffer(&self.params_buffer, 0, self.params.as_std140().as_bytes());
+
+        let mut cmd = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        if self.params.bloom_intensity > 0.0 {
+            let levels = &sized.bloom_levels;
+            fullscreen_pass(
+                &mut cmd,
+                "Bright Pass",
+                &self.bright_pass_pipeline,
+                &sized.scene_bind_group,
+                &levels[0].0,
+            );
+            // Blurred on the way down, then again on the way back up, ending at half the screen's size
+            for pair in levels.windows(2) {
+                fullscreen_pass(
+                    &mut cmd,
+                    "Bloom Downsample Pass",
+                    &self.bloom_down_pipeline,
+                    &pair[0].1,
+                    &pair[1].0,
+                );
+            }
+            for pair in levels.windows(2).rev() {
+                fullscreen_pass(
+                    &mut cmd,
+                    "Bloom Upsample Pass",
+                    &self.bloom_up_pipeline,
+                    &pair[1].1,
+                    &pair[0].0,
+                );
+            }
+        }
+        fullscreen_pass(
+            &mut cmd,
+            "Composite Pass",
+            &self.composite_pipeline,
+            &sized.composite_bind_group,
+            target,
+        );
+
+        // Submit
+        queue.submit(vec![cmd.finish()]);
+    }
+}
+
+// Every post-processing pass covers the whole of its target with one triangle
+fn fullscreen_pass(
+    cmd: &mut wgpu::CommandEncoder,
+    label: &str,
+    pipeline: &wgpu::RenderPipeline,
+    bind_group: &wgpu::BindGroup,
+    target: &wgpu::TextureView,
+) {
+    let mut rp = cmd.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+            attachment: target,
+            ops: wgpu::Operations {
+                // Everything gets drawn over, so there's no need to load what was there
+                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                store: true,
+            },
+            resolve_target: None,
+        }],
+        depth_stencil_attachment: None,
+    });
+    rp.set_pipeline(pipeline);
+    rp.set_bind_group(0, bind_group, &[]);
+    rp.draw(0..3, 0..1);
+}
+
+fn create_sized_resources(
+    gpu: &GpuInfo,
+    source_bind_group_layout: &wgpu::BindGroupLayout,
+    composite_bind_group_layout: &wgpu::BindGroupLayout,
+    sampler: &wgpu::Sampler,
+    params_buffer: &wgpu::Buffer,
+) -> SizedResources {
+    let device = &gpu.device;
+    let size = (gpu.swapchain_desc.width, gpu.swapchain_desc.height);
+    let sampled = wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::SAMPLED;
+
+    let framebuffers = Framebuffers {
+        scene: create_framebuffer(device, "Scene Framebuffer", size, 1, sampled),
+        overlay: create_framebuffer(device, "Overlay Framebuffer", size, 1, sampled),
+        msaa: Some(gpu.msaa_samples)
+            .filter(|&samples| samples > 1)
+            .map(|samples| {
+                create_framebuffer(
+                    device,
+                    "Multisampled Framebuffer",
+                    size,
+                    samples,
+                    wgpu::TextureUsage::RENDER_ATTACHMENT,
+                )
+            }),
+    };
+
+    let source_bind_group = |label, view: &wgpu::TextureView| {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(label),
+            layout: source_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+        })
+    };
+
+    let scene_bind_group = source_bind_group("Scene Bind Group", &framebuffers.scene);
+    let bloom_levels = (1..=BLOOM_LEVELS)
+        .map(|level| {
+            let level_size = ((size.0 >> level).max(1), (size.1 >> level).max(1));
+            let view = create_framebuffer(device, "Bloom Framebuffer", level_size, 1, sampled);
+            let bind_group = source_bind_group("Bloom Bind Group", &view);
+            (view, bind_group)
+        })
+        .collect::<Vec<_>>();
+
+    let composite_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Composite Bind Group"),
+        layout: composite_bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&framebuffers.scene),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(&bloom_levels[0].0),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(&framebuffers.overlay),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: wgpu::BindingResource::Buffer {
+                    buffer: params_buffer,
+                    offset: 0,
+                    size: None,
+                },
+            },
+        ],
+    });
+
+    SizedResources {
+        framebuffers: Arc::new(framebuffers),
+        scene_bind_group,
+        bloom_levels,
+        composite_bind_group,
+    }
+}
+
+fn create_framebuffer(
+    device: &wgpu::Device,
+    label: &str,
+    (width, height): (u32, u32),
+    sample_count: u32,
+    usage: wgpu::TextureUsage,
+) -> wgpu::TextureView {
+    device
+        .create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: HDR_FORMAT,
+            usage,
+        })
+        .create_view(&wgpu::TextureViewDescriptor::default())
+}
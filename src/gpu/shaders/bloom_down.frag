@@ -0,0 +1,19 @@
+#version 450
+
+layout(location = 0) in vec2 i_Uv;
+
+layout(set = 0, binding = 0) uniform texture2D t_Source;
+layout(set = 0, binding = 1) uniform sampler s_Linear;
+
+layout(location = 0) out vec4 o_Color;
+
+// Dual filter downsample, the centre plus four corners a pixel away, each blending four pixels of the bigger source
+void main() {
+    vec2 offset = 1.0 / vec2(textureSize(sampler2D(t_Source, s_Linear), 0));
+    vec3 sum = texture(sampler2D(t_Source, s_Linear), i_Uv).rgb * 4.0;
+    sum += texture(sampler2D(t_Source, s_Linear), i_Uv - offset).rgb;
+    sum += texture(sampler2D(t_Source, s_Linear), i_Uv + offset).rgb;
+    sum += texture(sampler2D(t_Source, s_Linear), i_Uv + vec2(offset.x, -offset.y)).rgb;
+    sum += texture(sampler2D(t_Source, s_Linear), i_Uv - vec2(offset.x, -offset.y)).rgb;
+    o_Color = vec4(sum / 8.0, 1.0);
+}
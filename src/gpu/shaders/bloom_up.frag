@@ -0,0 +1,22 @@
+#version 450
+
+layout(location = 0) in vec2 i_Uv;
+
+layout(set = 0, binding = 0) uniform texture2D t_Source;
+layout(set = 0, binding = 1) uniform sampler s_Linear;
+
+layout(location = 0) out vec4 o_Color;
+
+// Dual filter upsample, a ring of eight samples around the pixel from the smaller source, the diagonals weighted double
+void main() {
+    vec2 offset = 0.5 / vec2(textureSize(sampler2D(t_Source, s_Linear), 0));
+    vec3 sum = texture(sampler2D(t_Source, s_Linear), i_Uv + vec2(-offset.x * 2.0, 0.0)).rgb;
+    sum += texture(sampler2D(t_Source, s_Linear), i_Uv + vec2(offset.x * 2.0, 0.0)).rgb;
+    sum += texture(sampler2D(t_Source, s_Linear), i_Uv + vec2(0.0, -offset.y * 2.0)).rgb;
+    sum += texture(sampler2D(t_Source, s_Linear), i_Uv + vec2(0.0, offset.y * 2.0)).rgb;
+    sum += texture(sampler2D(t_Source, s_Linear), i_Uv + vec2(-offset.x, -offset.y)).rgb * 2.0;
+    sum += texture(sampler2D(t_Source, s_Linear), i_Uv + vec2(offset.x, -offset.y)).rgb * 2.0;
+    sum += texture(sampler2D(t_Source, s_Linear), i_Uv + vec2(-offset.x, offset.y)).rgb * 2.0;
+    sum += texture(sampler2D(t_Source, s_Linear), i_Uv + vec2(offset.x, offset.y)).rgb * 2.0;
+    o_Color = vec4(sum / 12.0, 1.0);
+}
@@ -0,0 +1,22 @@
+#version 450
+
+layout(location = 0) in vec2 i_Uv;
+
+layout(set = 0, binding = 0) uniform texture2D t_Source;
+layout(set = 0, binding = 1) uniform sampler s_Linear;
+
+layout(location = 0) out vec4 o_Color;
+
+// Brightness where bloom starts, with a soft knee either side so it fades in rather than popping
+const float c_Threshold = 0.8;
+const float c_Knee = 0.4;
+
+void main() {
+    // Drawn at half size, so each sample lands between four pixels and the filtering averages them
+    vec3 color = texture(sampler2D(t_Source, s_Linear), i_Uv).rgb;
+    float brightness = max(color.r, max(color.g, color.b));
+    float soft = clamp(brightness - c_Threshold + c_Knee, 0.0, 2.0 * c_Knee);
+    soft = soft * soft / (4.0 * c_Knee);
+    float contribution = max(soft, brightness - c_Threshold) / max(brightness, 0.0001);
+    o_Color = vec4(color * contribution, 1.0);
+}
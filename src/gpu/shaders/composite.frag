@@ -0,0 +1,52 @@
+#version 450
+
+layout(location = 0) in vec2 i_Uv;
+
+layout(set = 0, binding = 0) uniform texture2D t_Scene;
+layout(set = 0, binding = 1) uniform texture2D t_Bloom;
+layout(set = 0, binding = 2) uniform texture2D t_Overlay;
+layout(set = 0, binding = 3) uniform sampler s_Linear;
+layout(set = 0, binding = 4) uniform PostParams {
+    float u_BloomIntensity;
+    float u_Vignette;
+    float u_Aberration;
+    uint u_Tonemap;
+};
+
+layout(location = 0) out vec4 o_Color;
+
+vec3 hdr(vec2 uv) {
+    vec3 color = texture(sampler2D(t_Scene, s_Linear), uv).rgb;
+    // The bloom texture is left alone while bloom is off, so it could hold anything
+    if (u_BloomIntensity > 0.0) {
+        color += texture(sampler2D(t_Bloom, s_Linear), uv).rgb * u_BloomIntensity;
+    }
+    return color;
+}
+
+// Filmic curve fitted to ACES, squeezes anything bright back under 1 without flattening the darks
+vec3 tonemap(vec3 color) {
+    return clamp(color * (2.51 * color + 0.03) / (color * (2.43 * color + 0.59) + 0.14), 0.0, 1.0);
+}
+
+void main() {
+    vec2 fromCentre = i_Uv - 0.5;
+
+    // Red and blue are pulled apart towards the edges, like a cheap lens
+    vec3 color;
+    if (u_Aberration > 0.0) {
+        vec2 offset = fromCentre * u_Aberration;
+        color = vec3(hdr(i_Uv + offset).r, hdr(i_Uv).g, hdr(i_Uv - offset).b);
+    } else {
+        color = hdr(i_Uv);
+    }
+
+    if (u_Tonemap != 0u) {
+        color = tonemap(color);
+    }
+    color *= 1.0 - u_Vignette * smoothstep(0.3, 0.75, length(fromCentre));
+
+    // The overlay holds premultiplied colour, and goes over the top untouched
+    vec4 overlay = texture(sampler2D(t_Overlay, s_Linear), i_Uv);
+    o_Color = vec4(color * (1.0 - overlay.a) + overlay.rgb, 1.0);
+}
@@ -0,0 +1,12 @@
+#version 450
+
+layout(location = 0) in vec2 i_Corner;
+layout(location = 1) in vec4 i_Color;
+
+layout(location = 0) out vec4 o_Color;
+
+void main() {
+    // Soft round blob rather than a hard edged square
+    float falloff = 1.0 - smoothstep(0.0, 1.0, length(i_Corner));
+    o_Color = vec4(i_Color.rgb, i_Color.a * falloff);
+}
@@ -0,0 +1,28 @@
+#version 450
+
+// Each particle is a quad built from six corners here, so only the per particle data needs uploading
+const vec2 c_Corners[6] = vec2[6](
+    vec2(-1.0, -1.0),
+    vec2(1.0, -1.0),
+    vec2(-1.0, 1.0),
+    vec2(-1.0, 1.0),
+    vec2(1.0, -1.0),
+    vec2(1.0, 1.0)
+);
+
+layout(location = 0) in vec2 i_Position;
+layout(location = 1) in float i_Size;
+layout(location = 2) in vec4 i_Color;
+
+layout(set = 0, binding = 0) uniform View {
+	mat3 u_Camera;
+};
+
+layout(location = 0) out vec2 o_Corner;
+layout(location = 1) out vec4 o_Color;
+
+void main() {
+    o_Corner = c_Corners[gl_VertexIndex];
+    o_Color = i_Color;
+    gl_Position = vec4(u_Camera * vec3(i_Position + o_Corner * i_Size * 0.5, 1), 1);
+}
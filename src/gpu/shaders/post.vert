@@ -0,0 +1,17 @@
+#version 450
+
+// Covers the whole screen with a single triangle, no vertex buffer needed
+const vec2 c_Positions[3] = vec2[3](
+    vec2(-1.0, -1.0),
+    vec2(3.0, -1.0),
+    vec2(-1.0, 3.0)
+);
+
+// Texture coordinates, which start at the top left and go down
+layout(location = 0) out vec2 o_Uv;
+
+void main() {
+    vec2 position = c_Positions[gl_VertexIndex];
+    o_Uv = vec2(position.x + 1.0, 1.0 - position.y) * 0.5;
+    gl_Position = vec4(position, 0.0, 1.0);
+}
@@ -0,0 +1,13 @@
+#version 450
+
+layout(location = 0) in vec2 i_Uv;
+layout(location = 1) in vec4 i_Tint;
+
+layout(set = 1, binding = 0) uniform texture2D t_Atlas;
+layout(set = 1, binding = 1) uniform sampler s_Atlas;
+
+layout(location = 0) out vec4 o_Color;
+
+void main() {
+    o_Color = texture(sampler2D(t_Atlas, s_Atlas), i_Uv) * i_Tint;
+}
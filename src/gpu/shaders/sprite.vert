@@ -0,0 +1,24 @@
+#version 450
+
+layout(location = 0) in vec2 i_Position;
+layout(location = 1) in vec2 i_Uv;
+layout(location = 4) in vec3 i_Transform0;
+layout(location = 5) in vec3 i_Transform1;
+layout(location = 6) in vec3 i_Transform2;
+// xy is the offset of the image in the atlas, zw its size
+layout(location = 7) in vec4 i_Region;
+layout(location = 8) in vec4 i_Tint;
+mat3 i_Transform = mat3(i_Transform0, i_Transform1, i_Transform2);
+
+layout(set = 0, binding = 0) uniform View {
+	mat3 u_Camera;
+};
+
+layout(location = 0) out vec2 o_Uv;
+layout(location = 1) out vec4 o_Tint;
+
+void main() {
+    gl_Position = vec4(u_Camera * i_Transform * vec3(i_Position, 1), 1);
+    o_Uv = i_Region.xy + i_Uv * i_Region.zw;
+    o_Tint = i_Tint;
+}
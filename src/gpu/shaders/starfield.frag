@@ -0,0 +1,81 @@
+#version 450
+
+// Position on screen from -1 to 1
+layout(location = 0) in vec2 i_Screen;
+
+layout(set = 0, binding = 0) uniform StarfieldView {
+    vec2 u_Camera;
+    vec2 u_Extent;
+    float u_Time;
+};
+
+layout(location = 0) out vec4 o_Color;
+
+const int LAYERS = 3;
+// How far each layer moves relative to the camera, distant layers move less
+const float c_Parallax[LAYERS] = float[LAYERS](0.05, 0.15, 0.35);
+// Size of a cell in world units, each cell holds at most one star
+const float c_CellSize[LAYERS] = float[LAYERS](0.7, 1.2, 2.0);
+// Chance of a cell having a star in it
+const float c_Density[LAYERS] = float[LAYERS](0.35, 0.2, 0.1);
+const float c_Radius[LAYERS] = float[LAYERS](0.012, 0.018, 0.026);
+const float c_Brightness[LAYERS] = float[LAYERS](0.45, 0.7, 1.0);
+// Cell coordinates wrap around after this many cells, so the field tiles seamlessly and the hash only sees a small range
+// Must be a power of two, cells are wrapped by masking so negative coordinates wrap the same way as positive ones
+// This doesn't help with float precision far from the origin, positions are worked out in world space before any wrapping
+const int PERIOD = 1024;
+
+// Integer hash, gives well distributed bits for neighbouring cells
+uvec3 hash(uvec3 v) {
+    v = v * 1664525u + 1013904223u;
+    v.x += v.y * v.z;
+    v.y += v.z * v.x;
+    v.z += v.x * v.y;
+    v ^= v >> 16u;
+    v.x += v.y * v.z;
+    v.y += v.z * v.x;
+    v.z += v.x * v.y;
+    return v;
+}
+
+vec3 random3(uvec3 v) {
+    return vec3(hash(v) & uvec3(0xFFFFu)) / 65535.0;
+}
+
+// Rough black body colour, from cool red stars through white to hot blue ones
+vec3 temperature_color(float temperature) {
+    vec3 cool = vec3(1.0, 0.6, 0.35);
+    vec3 warm = vec3(1.0, 0.95, 0.85);
+    vec3 hot = vec3(0.65, 0.78, 1.0);
+    return temperature < 0.5
+        ? mix(cool, warm, temperature * 2.0)
+        : mix(warm, hot, temperature * 2.0 - 1.0);
+}
+
+void main() {
+    vec3 color = vec3(0.0);
+
+    for (int layer = 0; layer < LAYERS; layer++) {
+        vec2 world = u_Camera * c_Parallax[layer] + i_Screen * u_Extent;
+        vec2 cell_position = world / c_CellSize[layer];
+        ivec2 cell = ivec2(floor(cell_position));
+        uvec3 key = uvec3(uvec2(cell) & uvec2(PERIOD - 1), uint(layer));
+
+        vec3 star = random3(key);
+        if (star.z > c_Density[layer]) {
+            continue;
+        }
+
+        // Keep the star far enough inside its cell that it never gets cut off at the edge
+        vec3 look = random3(key + uvec3(0u, 0u, 7u));
+        float radius = c_Radius[layer] * (0.5 + look.x);
+        vec2 center = mix(vec2(radius), vec2(c_CellSize[layer] - radius), star.xy);
+        float distance = length(fract(cell_position) * c_CellSize[layer] - center);
+        float glow = 1.0 - smoothstep(0.0, radius, distance);
+
+        float twinkle = 0.75 + 0.25 * sin(u_Time * (1.0 + look.y * 3.0) + look.z * 6.2831);
+        color += temperature_color(look.y) * glow * twinkle * c_Brightness[layer];
+    }
+
+    o_Color = vec4(color, 1.0);
+}
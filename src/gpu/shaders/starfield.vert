@@ -0,0 +1,15 @@
+#version 450
+
+// Covers the whole screen with a single triangle, no vertex buffer needed
+const vec2 c_Positions[3] = vec2[3](
+    vec2(-1.0, -1.0),
+    vec2(3.0, -1.0),
+    vec2(-1.0, 3.0)
+);
+
+layout(location = 0) out vec2 o_Screen;
+
+void main() {
+    o_Screen = c_Positions[gl_VertexIndex];
+    gl_Position = vec4(o_Screen, 0.0, 1.0);
+}
@@ -0,0 +1,443 @@
+use crevice::std140::{AsStd140, Std140};
+use std::{
+    collections::HashMap,
+    convert::TryInto,
+    mem::size_of,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+use crate::gpu::{
+    atlas::{self, AtlasError, AtlasRegion, PackedAtlas},
+    cull,
+    main_pipeline::View,
+    multisample_state, sort_batches, BlendMode, DrawStats, GpuInfo, RenderTarget, SortKey,
+    HDR_FORMAT,
+};
+
+// Corner of the unit quad every sprite is drawn with
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SpriteVertex {
+    position: [f32; 2],
+    uv: [f32; 2],
+}
+unsafe impl Zeroable for SpriteVertex {}
+unsafe impl Pod for SpriteVertex {}
+
+// Centred on the origin, one unit across, images are stored top row first so v runs downwards
+const QUAD_VERTICES: [SpriteVertex; 4] = [
+    SpriteVertex {
+        position: [-0.5, -0.5],
+        uv: [0.0, 1.0],
+    },
+    SpriteVertex {
+        position: [0.5, -0.5],
+        uv: [1.0, 1.0],
+    },
+    SpriteVertex {
+        position: [0.5, 0.5],
+        uv: [1.0, 0.0],
+    },
+    SpriteVertex {
+        position: [-0.5, 0.5],
+        uv: [0.0, 0.0],
+    },
+];
+const QUAD_INDICES: [u16; 6] = [0, 1, 2, 0, 2, 3];
+// Distance from the centre of the quad to its corners
+const QUAD_RADIUS: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+// An instance of a sprite to render
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SpriteInstance {
+    // Scale this by the size the sprite should be in world units, the quad is only one unit across
+    pub transform: mint::ColumnMatrix3<f32>,
+    // Offset and size of the image in the atlas, see AtlasRegion::uv_rect
+    pub region: [f32; 4],
+    // Multiplied with the texture colour, white leaves the artwork as it is
+    pub tint: [f32; 4],
+}
+unsafe impl Zeroable for SpriteInstance {}
+unsafe impl Pod for SpriteInstance {}
+impl SpriteInstance {
+    pub fn new(
+        transform: impl Into<mint::ColumnMatrix3<f32>>,
+        region: &AtlasRegion,
+        tint: [f32; 4],
+    ) -> Self {
+        Self {
+            transform: transform.into(),
+            region: region.uv_rect(),
+            tint,
+        }
+    }
+}
+
+// A packed atlas uploaded to the gpu, ready to draw sprites from
+pub struct SpriteAtlas {
+    regions: HashMap<String, AtlasRegion>,
+    bind_group: wgpu::BindGroup,
+}
+
+impl SpriteAtlas {
+    pub fn region(&self, name: &str) -> Option<&AtlasRegion> {
+        self.regions.get(name)
+    }
+}
+
+pub struct SpritePipeline {
+    pub view: View,
+    // Added up over every render, until they're reset
+    pub stats: DrawStats,
+    // Scratch space for culling, reused by every batch
+    visible: Vec<SpriteInstance>,
+    gpu_info: Arc<Mutex<GpuInfo>>,
+    // One variant of the pipeline for each blend mode, they're otherwise identical
+    pipelines: HashMap<BlendMode, wgpu::RenderPipeline>,
+    view_buffer: wgpu::Buffer,
+    view_bind_group: wgpu::BindGroup,
+    atlas_bind_group_layout: wgpu::BindGroupLayout,
+    // Every sprite is the same quad, so there's only ever one of these
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+}
+
+impl SpritePipeline {
+    pub fn new(gpu_info: Arc<Mutex<GpuInfo>>, view: View) -> Self {
+        let gpu_info_ = gpu_info.clone();
+        let GpuInfo {
+            device,
+            msaa_samples,
+            ..
+        } = &*gpu_info_.lock().unwrap();
+
+        let view_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Sprite View Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(
+                            size_of::<<View as AsStd140>::Std140Type>()
+                                .try_into()
+                                .unwrap(),
+                        ),
+                    },
+                    count: None,
+                    visibility: wgpu::ShaderStage::VERTEX,
+                }],
+            });
+
+        let atlas_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Sprite Atlas Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                        },
+                        count: None,
+                        visibility: wgpu::ShaderStage::FRAGMENT,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        ty: wgpu::BindingType::Sampler {
+                            filtering: true,
+                            comparison: false,
+                        },
+                        count: None,
+                        visibility: wgpu::ShaderStage::FRAGMENT,
+                    },
+                ],
+            });
+
+        let vert_shader =
+            device.create_shader_module(&wgpu::include_spirv!("shaders/sprite.vert.spv"));
+        let frag_shader =
+            device.create_shader_module(&wgpu::include_spirv!("shaders/sprite.frag.spv"));
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Sprite Pipeline Layout"),
+            bind_group_layouts: &[&view_bind_group_layout, &atlas_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let create_pipeline = |blend_mode: BlendMode| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Sprite Render Pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    entry_point: "main",
+                    module: &vert_shader,
+                    buffers: &[
+                        wgpu::VertexBufferLayout {
+                            array_stride: size_of::<SpriteVertex>().try_into().unwrap(),
+                            step_mode: wgpu::InputStepMode::Vertex,
+                            attributes: &wgpu::vertex_attr_array![
+                                0 => Float2,
+                                1 => Float2,
+                            ],
+                        },
+                        wgpu::VertexBufferLayout {
+                            array_stride: size_of::<SpriteInstance>().try_into().unwrap(),
+                            step_mode: wgpu::InputStepMode::Instance,
+                            attributes: &wgpu::vertex_attr_array![
+                                4 => Float3,
+                                5 => Float3,
+                                6 => Float3,
+                                7 => Float4,
+                                8 => Float4,
+                            ],
+                        },
+                    ],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    entry_point: "main",
+                    module: &frag_shader,
+                    targets: &[blend_mode.color_target(HDR_FORMAT)],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    cull_mode: wgpu::CullMode::None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    strip_index_format: None,
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                },
+                depth_stencil: None,
+                multisample: multisample_state(*msaa_samples),
+            })
+        };
+        let pipelines = BlendMode::ALL
+            .iter()
+            .map(|&blend_mode| (blend_mode, create_pipeline(blend_mode)))
+            .collect();
+
+        let view_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Sprite View Buffer"),
+            contents: view.as_std140().as_bytes(),
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        });
+
+        let view_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Sprite View Bind Group"),
+            layout: &view_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer {
+                    buffer: &view_buffer,
+                    offset: 0,
+                    size: None,
+                },
+            }],
+        });
+
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Sprite Vertex Buffer"),
+            contents: bytemuck::cast_slice(&QUAD_VERTICES),
+            usage: wgpu::BufferUsage::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Sprite Index Buffer"),
+            contents: bytemuck::cast_slice(&QUAD_INDICES),
+            usage: wgpu::BufferUsage::INDEX,
+        });
+
+        Self {
+            view,
+            stats: DrawStats::default(),
+            visible: Vec::new(),
+
+            gpu_info,
+            pipelines,
+            view_buffer,
+            view_bind_group,
+            atlas_bind_group_layout,
+            vertex_buffer,
+            index_buffer,
+        }
+    }
+
+    // Pack every PNG under a directory into an atlas and upload it
+    // Use nearest filtering for pixel art so it stays crisp, linear for anything else
+    pub fn create_atlas(
+        &mut self,
+        dir: &Path,
+        filter: wgpu::FilterMode,
+    ) -> Result<SpriteAtlas, AtlasError> {
+        Ok(self.upload_atlas(atlas::build(dir)?, filter))
+    }
+
+    // Upload an atlas that's already been packed, e.g. one full of rasterised glyphs
+    pub fn upload_atlas(&mut self, packed: PackedAtlas, filter: wgpu::FilterMode) -> SpriteAtlas {
+        let GpuInfo { device, queue, .. } = &*self.gpu_info.lock().unwrap();
+
+        let extent = wgpu::Extent3d {
+            width: packed.size,
+            height: packed.size,
+            depth: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Sprite Atlas Texture"),
+            size: extent,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            // Artwork is authored in srgb, so it's converted to linear when sampled
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+        });
+        queue.write_texture(
+            wgpu::TextureCopyView {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            &packed.pixels,
+            wgpu::TextureDataLayout {
+                offset: 0,
+                bytes_per_row: packed.size * 4,
+                rows_per_image: packed.size,
+            },
+            extent,
+        );
+        let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Sprite Atlas Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: filter,
+            min_filter: filter,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Sprite Atlas Bind Group"),
+            layout: &self.atlas_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        SpriteAtlas {
+            regions: packed.regions,
+            bind_group,
+        }
+    }
+
+    // Cull and upload batches of sprites ahead of drawing them, batches with nothing on screen are left out
+    pub fn prepare<'a>(
+        &mut self,
+        batches: &[(&'a SpriteAtlas, &[SpriteInstance], BlendMode, SortKey)],
+    ) -> Vec<SpriteBatch<'a>> {
+        let GpuInfo { device, queue, .. } = &*self.gpu_info.lock().unwrap();
+
+        // Update uniform
+        queue.write_buffer(&self.view_buffer, 0, self.view.as_std140().as_bytes());
+
+        // Sprites off screen are left out before anything is uploaded
+        let bounds = self.view.visible_bounds();
+        let stats = &mut self.stats;
+        let visible = &mut self.visible;
+        batches
+            .iter()
+            .filter_map(|&(atlas, instances, blend_mode, key)| {
+                cull(
+                    instances,
+                    stats,
+                    |instance| {
+                        bounds.map_or(false, |bounds| {
+                            bounds.overlaps_instance(&instance.transform, QUAD_RADIUS)
+                        })
+                    },
+                    visible,
+                );
+                if visible.is_empty() {
+                    return None;
+                }
+                let instance_buffer =
+                    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label: Some("Sprite Instance Buffer"),
+                        contents: bytemuck::cast_slice(visible),
+                        usage: wgpu::BufferUsage::VERTEX,
+                    });
+                Some(SpriteBatch {
+                    atlas,
+                    instance_count: visible.len() as u32,
+                    instance_buffer,
+                    blend_mode,
+                    key,
+                })
+            })
+            .collect()
+    }
+
+    // Draw a prepared batch into a render pass, which other pipelines may be drawing into as well
+    pub fn draw<'a>(&'a self, rp: &mut wgpu::RenderPass<'a>, batch: &'a SpriteBatch) {
+        rp.set_pipeline(&self.pipelines[&batch.blend_mode]);
+        rp.set_bind_group(0, &self.view_bind_group, &[]);
+        rp.set_bind_group(1, &batch.atlas.bind_group, &[]);
+        rp.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        rp.set_vertex_buffer(1, batch.instance_buffer.slice(..));
+        rp.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        rp.draw_indexed(0..QUAD_INDICES.len() as u32, 0, 0..batch.instance_count);
+    }
+
+    // Render batches of sprites in a pass of their own, each from one atlas and with its own blend mode and place in the draw order
+    // Batches are ordered the same way as the main pipeline's
+    pub fn render(
+        &mut self,
+        target: RenderTarget,
+        batches: &[(&SpriteAtlas, &[SpriteInstance], BlendMode, SortKey)],
+    ) {
+        let mut batches = self.prepare(batches);
+        if batches.is_empty() {
+            return;
+        }
+        sort_batches(&mut batches, |batch| (batch.key, batch.blend_mode));
+
+        let GpuInfo { device, queue, .. } = &*self.gpu_info.lock().unwrap();
+        let mut cmd = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut rp = cmd.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Sprite Render Pass"),
+                color_attachments: &[target.color_attachment(wgpu::LoadOp::Load)],
+                depth_stencil_attachment: None,
+            });
+            for batch in &batches {
+                self.draw(&mut rp, batch);
+            }
+        }
+
+        // Submit
+        queue.submit(vec![cmd.finish()]);
+    }
+}
+
+// Sprites from one atlas that have been culled and uploaded, ready to draw
+pub struct SpriteBatch<'a> {
+    atlas: &'a SpriteAtlas,
+    instance_count: u32,
+    instance_buffer: wgpu::Buffer,
+    pub blend_mode: BlendMode,
+    pub key: SortKey,
+}
@@ -0,0 +1,170 @@
+use crevice::std140::{AsStd140, Std140};
+use std::{
+    convert::TryInto,
+    mem::size_of,
+    sync::{Arc, Mutex},
+};
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+use crate::gpu::{multisample_state, GpuInfo, RenderTarget, HDR_FORMAT};
+
+// Starfield uniform
+// The stars themselves are generated entirely in the fragment shader, so this is all the information the gpu needs
+#[derive(AsStd140, Clone, Copy)]
+pub struct StarfieldView {
+    // Centre of the screen in world space, each layer scrolls by some fraction of this
+    pub camera: mint::Vector2<f32>,
+    // Half the width and height of the screen in world units
+    pub extent: mint::Vector2<f32>,
+    // Seconds since rendering started, drives the twinkling
+    pub time: f32,
+}
+unsafe impl Zeroable for StarfieldView {}
+unsafe impl Pod for StarfieldView {}
+impl StarfieldView {
+    pub fn new(
+        camera: impl Into<mint::Vector2<f32>>,
+        extent: impl Into<mint::Vector2<f32>>,
+        time: f32,
+    ) -> Self {
+        Self {
+            camera: camera.into(),
+            extent: extent.into(),
+            time,
+        }
+    }
+}
+
+// Draws the background, this pass clears the frame so it has to come before anything else
+pub struct StarfieldPipeline {
+    pub view: StarfieldView,
+    gpu_info: Arc<Mutex<GpuInfo>>,
+    pipeline: wgpu::RenderPipeline,
+    view_buffer: wgpu::Buffer,
+    view_bind_group: wgpu::BindGroup,
+}
+
+impl StarfieldPipeline {
+    pub fn new(gpu_info: Arc<Mutex<GpuInfo>>, view: StarfieldView) -> Self {
+        let gpu_info_ = gpu_info.clone();
+        let GpuInfo {
+            device,
+            msaa_samples,
+            ..
+        } = &*gpu_info_.lock().unwrap();
+
+        let view_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Starfield View Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(
+                            size_of::<<StarfieldView as AsStd140>::Std140Type>()
+                                .try_into()
+                                .unwrap(),
+                        ),
+                    },
+                    count: None,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                }],
+            });
+
+        let vert_shader =
+            device.create_shader_module(&wgpu::include_spirv!("shaders/starfield.vert.spv"));
+        let frag_shader =
+            device.create_shader_module(&wgpu::include_spirv!("shaders/starfield.frag.spv"));
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Starfield Pipeline Layout"),
+            bind_group_layouts: &[&view_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Starfield Render Pipeline"),
+            layout: Some(&layout),
+            // A single fullscreen triangle generated in the vertex shader, so there are no buffers
+            vertex: wgpu::VertexState {
+                entry_point: "main",
+                module: &vert_shader,
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                entry_point: "main",
+                module: &frag_shader,
+                targets: &[wgpu::ColorTargetState {
+                    format: HDR_FORMAT,
+                    alpha_blend: wgpu::BlendState::REPLACE,
+                    color_blend: wgpu::BlendState::REPLACE,
+                    write_mask: wgpu::ColorWrite::ALL,
+                }],
+            }),
+            primitive: wgpu::PrimitiveState {
+                cull_mode: wgpu::CullMode::None,
+                front_face: wgpu::FrontFace::Ccw,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                strip_index_format: None,
+                topology: wgpu::PrimitiveTopology::TriangleList,
+            },
+            depth_stencil: None,
+            multisample: multisample_state(*msaa_samples),
+        });
+
+        let view_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Starfield View Buffer"),
+            contents: view.as_std140().as_bytes(),
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        });
+
+        let view_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Starfield View Bind Group"),
+            layout: &view_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer {
+                    buffer: &view_buffer,
+                    offset: 0,
+                    size: None,
+                },
+            }],
+        });
+
+        Self {
+            view,
+
+            gpu_info,
+            pipeline,
+            view_buffer,
+            view_bind_group,
+        }
+    }
+
+    pub fn render(&mut self, target: RenderTarget) {
+        let GpuInfo { device, queue, .. } = &*self.gpu_info.lock().unwrap();
+
+        // Update uniform
+        queue.write_buffer(&self.view_buffer, 0, self.view.as_std140().as_bytes());
+
+        let mut cmd = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut rp = cmd.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Starfield Render Pass"),
+                color_attachments: &[
+                    target.color_attachment(wgpu::LoadOp::Clear(wgpu::Color::BLACK))
+                ],
+                depth_stencil_attachment: None,
+            });
+            rp.set_pipeline(&self.pipeline);
+            rp.set_bind_group(0, &self.view_bind_group, &[]);
+            rp.draw(0..3, 0..1);
+        }
+
+        // Submit
+        queue.submit(vec![cmd.finish()]);
+    }
+}
@@ -0,0 +1,137 @@
+use crate::{
+    assets::{self, AssetError},
+    gpu::{atlas::AtlasError, mesh::MeshError},
+    ship::FitError,
+};
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+// How often the assets directory is checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug)]
+pub enum ReloadError {
+    Asset(AssetError),
+    Mesh(MeshError),
+    Atlas(AtlasError),
+    // The player's loadout doesn't fit the new ship data
+    Fit(FitError),
+    MissingEffect(String),
+}
+
+impl fmt::Display for ReloadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReloadError::Asset(e) => write!(f, "{}", e),
+            ReloadError::Mesh(e) => write!(f, "{}", e),
+            ReloadError::Atlas(e) => write!(f, "{}", e),
+            ReloadError::Fit(e) => write!(f, "the player's ship no longer fits: {}", e),
+            ReloadError::MissingEffect(id) => write!(f, "missing particle effect {}", id),
+        }
+    }
+}
+
+impl From<AssetError> for ReloadError {
+    fn from(e: AssetError) -> Self {
+        ReloadError::Asset(e)
+    }
+}
+
+impl From<MeshError> for ReloadError {
+    fn from(e: MeshError) -> Self {
+        ReloadError::Mesh(e)
+    }
+}
+
+impl From<AtlasError> for ReloadError {
+    fn from(e: AtlasError) -> Self {
+        ReloadError::Atlas(e)
+    }
+}
+
+impl From<FitError> for ReloadError {
+    fn from(e: FitError) -> Self {
+        ReloadError::Fit(e)
+    }
+}
+
+// Watches the assets directory for files changing while the game runs, so data can be tweaked without restarting
+// This polls the modified time of every file on a background thread, which is plenty for a directory this size
+pub struct AssetWatcher {
+    changes: Receiver<PathBuf>,
+}
+
+impl AssetWatcher {
+    // Files outside the assets directory can be watched too, and are reported by the path they were given as
+    pub fn start(files: Vec<PathBuf>) -> Self {
+        let (sender, changes) = mpsc::channel();
+        thread::spawn(move || {
+            let root = assets::assets_dir();
+            let scan_all = |modified: &mut HashMap<PathBuf, SystemTime>| {
+                scan(&root, modified);
+                for file in &files {
+                    if let Ok(time) = fs::metadata(file).and_then(|metadata| metadata.modified()) {
+                        modified.insert(file.clone(), time);
+                    }
+                }
+            };
+            let mut modified = HashMap::new();
+            scan_all(&mut modified);
+            loop {
+                thread::sleep(POLL_INTERVAL);
+                let mut latest = HashMap::new();
+                scan_all(&mut latest);
+                for (path, time) in &latest {
+                    if modified.get(path) != Some(time) {
+                        let relative = path.strip_prefix(&root).unwrap_or(path).to_owned();
+                        // The watcher's been dropped, so nobody's listening any more
+                        if sender.send(relative).is_err() {
+                            return;
+                        }
+                    }
+                }
+                modified = latest;
+            }
+        });
+        Self { changes }
+    }
+
+    // Files that changed since this was last called, relative to the assets directory if they're in it
+    // Each one is only listed once, however many times it was saved in between
+    pub fn changed(&self) -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+        for path in self.changes.try_iter() {
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+        paths
+    }
+}
+
+// When every file under a directory was last modified
+// Anything that can't be read right now is left out, and shows up as changed once it can be
+fn scan(dir: &Path, modified: &mut HashMap<PathBuf, SystemTime>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        match entry.metadata() {
+            Ok(metadata) if metadata.is_dir() => scan(&path, modified),
+            Ok(metadata) => {
+                if let Ok(time) = metadata.modified() {
+                    modified.insert(path, time);
+                }
+            }
+            Err(_) => (),
+        }
+    }
+}
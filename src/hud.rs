@@ -0,0 +1,375 @@
+use crate::{
+    game::Game,
+    gfx::Camera,
+    gpu::{
+        font::{Align, TextStyle},
+        DrawStats,
+    },
+    radar::Radar,
+    targeting::{self, Target, TargetInfo},
+    ui::{DrawCommand, Rect},
+};
+use std::time::Duration;
+
+// Everything here is in pixels from the top left of the window, the same as the ui
+const TEXT_SIZE: f32 = 18.0;
+pub const SMALL_TEXT_SIZE: f32 = 14.0;
+pub const MARGIN: f32 = 12.0;
+pub const TEXT_COLOR: [f32; 4] = [0.85, 0.9, 1.0, 1.0];
+// Status bars in the bottom left corner
+const BAR_LABEL_WIDTH: f32 = 70.0;
+const BAR_WIDTH: f32 = 160.0;
+const BAR_HEIGHT: f32 = 10.0;
+const BAR_SPACING: f32 = 8.0;
+const BAR_BACKGROUND: [f32; 4] = [0.1, 0.12, 0.16, 0.8];
+const HULL_COLOR: [f32; 4] = [0.85, 0.65, 0.3, 1.0];
+const SHIELD_COLOR: [f32; 4] = [0.3, 0.6, 1.0, 1.0];
+const ENERGY_COLOR: [f32; 4] = [0.95, 0.9, 0.35, 1.0];
+const FUEL_COLOR: [f32; 4] = [0.4, 0.85, 0.45, 1.0];
+const HEAT_COLOR: [f32; 4] = [0.95, 0.45, 0.25, 1.0];
+const OVERHEAT_COLOR: [f32; 4] = [1.0, 0.15, 0.1, 1.0];
+const AFTERBURNER_COLOR: [f32; 4] = [0.45, 0.85, 1.0, 1.0];
+const AFTERBURNER_COOLDOWN_COLOR: [f32; 4] = [0.3, 0.4, 0.5, 1.0];
+// The velocity vector shows where the ship will be this many seconds from now
+const VELOCITY_VECTOR_TIME: f32 = 1.0;
+const VELOCITY_VECTOR_MAX: f32 = 250.0;
+const VELOCITY_COLOR: [f32; 4] = [0.4, 1.0, 0.6, 0.8];
+// A tick on a ring around the ship marks where the nose is pointing
+const HEADING_RING_RADIUS: f32 = 40.0;
+const HEADING_TICK_LENGTH: f32 = 10.0;
+const HEADING_COLOR: [f32; 4] = [0.85, 0.9, 1.0, 0.7];
+const LINE_WIDTH: f32 = 2.0;
+// Brackets around the target, never smaller than the minimum size however far away it is
+const RETICLE_MIN_SIZE: f32 = 16.0;
+const RETICLE_PADDING: f32 = 6.0;
+const RETICLE_CORNER: f32 = 0.35;
+pub const TARGET_COLOR: [f32; 4] = [1.0, 0.4, 0.3, 1.0];
+const LEAD_SIZE: f32 = 6.0;
+// Arrows along the edges of the screen point at things that are off screen
+const INDICATOR_INSET: f32 = 28.0;
+const INDICATOR_SIZE: f32 = 10.0;
+const STATION_INDICATOR_COLOR: [f32; 4] = [0.7, 0.75, 0.8, 0.8];
+// The radar sits in the bottom right corner, above the target readout
+const RADAR_SIZE: f32 = 180.0;
+
+// Build the cockpit display for this frame
+// The camera is only given while the system is on screen, without it nothing is drawn over the world and the radar is hidden
+pub fn draw(
+    game: &Game,
+    camera: Option<&Camera>,
+    radar: &Radar,
+    screen_size: na::Vector2<f32>,
+    frame_time: Duration,
+    draw_stats: DrawStats,
+    commands: &mut Vec<DrawCommand>,
+) {
+    let text_style = TextStyle::new(TEXT_SIZE, TEXT_COLOR);
+
+    // Where the player is, in the top left
+    let mut lines = vec![game.galaxy.system(game.system()).name.clone()];
+    if let Some(station) = game.docked_station() {
+        lines.push(format!("Docked at {}", game.economy.stations[station].name));
+    }
+    lines.push(format!(
+        "Frame: {:.1} ms",
+        frame_time.as_secs_f32() * 1000.0
+    ));
+    lines.push(format!(
+        "Drawn: {}  Culled: {}",
+        draw_stats.drawn, draw_stats.culled
+    ));
+    commands.push(DrawCommand::Text {
+        text: lines.join("\n"),
+        position: na::Vector2::new(MARGIN, MARGIN),
+        style: text_style,
+    });
+
+    draw_status(game, screen_size, commands);
+
+    let target = game.target_info();
+    if let Some(target) = &target {
+        draw_target_readout(game, target, screen_size, commands);
+    }
+
+    if let Some(camera) = camera {
+        if !game.jump_drive.in_transit() {
+            draw_flight_markers(game, camera, commands);
+            // The target gets its own indicator
+            let stations = game
+                .economy
+                .stations
+                .iter()
+                .enumerate()
+                .filter(|&(i, _)| game.target() != Some(Target::Station(i)));
+            for (_, station) in stations {
+                draw_offscreen_indicator(
+                    game,
+                    camera,
+                    station.position,
+                    STATION_INDICATOR_COLOR,
+                    commands,
+                );
+            }
+            if let Some(target) = &target {
+                draw_reticle(game, camera, target, commands);
+                draw_offscreen_indicator(game, camera, target.position, TARGET_COLOR, commands);
+            }
+
+            let rect = Rect::new(
+                screen_size.x - MARGIN - RADAR_SIZE,
+                screen_size.y - MARGIN * 2.0 - TEXT_SIZE * 3.0 - RADAR_SIZE,
+                RADAR_SIZE,
+                RADAR_SIZE,
+            );
+            radar.draw(game, rect, commands);
+        }
+    }
+}
+
+// Speed, heading and the ship's resources as bars, stacked up from the bottom left corner
+fn draw_status(game: &Game, screen_size: na::Vector2<f32>, commands: &mut Vec<DrawCommand>) {
+    let player = game.player();
+    let status = player.resource_status();
+    let afterburner = player.afterburner();
+    let bars = [
+        ("Hull", status.hull, HULL_COLOR),
+        ("Shield", status.shield, SHIELD_COLOR),
+        ("Energy", status.energy, ENERGY_COLOR),
+        ("Fuel", status.fuel, FUEL_COLOR),
+        (
+            "Heat",
+            status.heat,
+            if status.overheated {
+                OVERHEAT_COLOR
+            } else {
+                HEAT_COLOR
+            },
+        ),
+        (
+            "Boost",
+            afterburner.charge(),
+            if afterburner.cooling_down() {
+                AFTERBURNER_COOLDOWN_COLOR
+            } else {
+                AFTERBURNER_COLOR
+            },
+        ),
+    ];
+
+    let label_style = TextStyle::new(SMALL_TEXT_SIZE, TEXT_COLOR);
+    let mut y = screen_size.y - MARGIN - BAR_HEIGHT;
+    for &(label, fraction, color) in bars.iter().rev() {
+        commands.push(DrawCommand::Text {
+            text: label.to_owned(),
+            position: na::Vector2::new(MARGIN, y + (BAR_HEIGHT - SMALL_TEXT_SIZE) / 2.0),
+            style: label_style,
+        });
+        let x = MARGIN + BAR_LABEL_WIDTH;
+        commands.push(DrawCommand::Rect {
+            rect: Rect::new(x, y, BAR_WIDTH, BAR_HEIGHT),
+            color: BAR_BACKGROUND,
+        });
+        commands.push(DrawCommand::Rect {
+            rect: Rect::new(x, y, BAR_WIDTH * fraction.clamp(0.0, 1.0), BAR_HEIGHT),
+            color,
+        });
+        y -= BAR_HEIGHT + BAR_SPACING;
+    }
+
+    // Headings are compass bearings, clockwise from straight up the screen
+    let bearing = ((-player.angle.to_degrees()).round() as i32).rem_euclid(360);
+    let velocity = player.velocity;
+    commands.push(DrawCommand::Text {
+        text: format!(
+            "Speed {:>6.1}  ({:+.1}, {:+.1})\nHeading {:03}  Assist {}",
+            velocity.norm(),
+            velocity.x,
+            velocity.y,
+            bearing,
+            if player.flight_assist() { "On" } else { "Off" }
+        ),
+        position: na::Vector2::new(MARGIN, y + BAR_HEIGHT - TEXT_SIZE * 2.0),
+        style: TextStyle::new(TEXT_SIZE, TEXT_COLOR),
+    });
+}
+
+// Details of the current target in the bottom right corner
+fn draw_target_readout(
+    game: &Game,
+    target: &TargetInfo,
+    screen_size: na::Vector2<f32>,
+    commands: &mut Vec<DrawCommand>,
+) {
+    let player = game.player();
+    let offset = target.position - player.position;
+    let distance = offset.norm();
+    // Positive when the gap is shrinking
+    let closing = if distance > 0.0 {
+        -(target.velocity - player.velocity).dot(&offset) / distance
+    } else {
+        0.0
+    };
+    commands.push(DrawCommand::Text {
+        text: format!(
+            "{}\nDistance {:.0}\nClosing {:+.1}",
+            target.name, distance, closing
+        ),
+        position: na::Vector2::new(
+            screen_size.x - MARGIN,
+            screen_size.y - MARGIN - TEXT_SIZE * 3.0,
+        ),
+        style: TextStyle::new(TEXT_SIZE, TARGET_COLOR).align(Align::Right),
+    });
+}
+
+// Velocity vector and heading tick around the ship, which is always in the middle of the screen
+fn draw_flight_markers(game: &Game, camera: &Camera, commands: &mut Vec<DrawCommand>) {
+    let player = game.player();
+    let centre = camera.world_to_screen(player.position);
+
+    // Screen y points down, so world directions have their y flipped
+    let flip = |v: na::Vector2<f32>| na::Vector2::new(v.x, -v.y);
+    let vector = flip(player.velocity) * VELOCITY_VECTOR_TIME * camera.pixels_per_unit();
+    let length = vector.norm();
+    if length > 1.0 {
+        let end = centre + vector * (length.min(VELOCITY_VECTOR_MAX) / length);
+        line(commands, centre, end, VELOCITY_COLOR);
+        // A small cross marks the end of the vector
+        let half = LEAD_SIZE / 2.0;
+        line(
+            commands,
+            end - na::Vector2::new(half, 0.0),
+            end + na::Vector2::new(half, 0.0),
+            VELOCITY_COLOR,
+        );
+        line(
+            commands,
+            end - na::Vector2::new(0.0, half),
+            end + na::Vector2::new(0.0, half),
+            VELOCITY_COLOR,
+        );
+    }
+
+    let heading = flip(player.heading());
+    line(
+        commands,
+        centre + heading * HEADING_RING_RADIUS,
+        centre + heading * (HEADING_RING_RADIUS + HEADING_TICK_LENGTH),
+        HEADING_COLOR,
+    );
+}
+
+// Corner brackets around the target with its distance underneath, and a diamond where to aim to hit it
+fn draw_reticle(
+    game: &Game,
+    camera: &Camera,
+    target: &TargetInfo,
+    commands: &mut Vec<DrawCommand>,
+) {
+    let player = game.player();
+    let centre = camera.world_to_screen(target.position);
+    // Off screen targets get an edge indicator instead
+    if !Rect::new(0.0, 0.0, camera.screen_size.x, camera.screen_size.y).contains(centre) {
+        return;
+    }
+    let half = (target.radius * camera.pixels_per_unit()).max(RETICLE_MIN_SIZE) + RETICLE_PADDING;
+    let corner = half * 2.0 * RETICLE_CORNER;
+    for &(sx, sy) in &[(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)] {
+        let point = centre + na::Vector2::new(sx, sy) * half;
+        line(
+            commands,
+            point,
+            point - na::Vector2::new(sx * corner, 0.0),
+            TARGET_COLOR,
+        );
+        line(
+            commands,
+            point,
+            point - na::Vector2::new(0.0, sy * corner),
+            TARGET_COLOR,
+        );
+    }
+    commands.push(DrawCommand::Text {
+        text: format!("{:.0}", (target.position - player.position).norm()),
+        position: centre + na::Vector2::new(0.0, half + 2.0),
+        style: TextStyle::new(SMALL_TEXT_SIZE, TARGET_COLOR).align(Align::Center),
+    });
+
+    // Only ships with a weapon fitted get a lead indicator
+    let lead = targeting::lead_position(
+        player.position,
+        player.velocity,
+        target.position,
+        target.velocity,
+        player.ship().stats().projectile_speed,
+    );
+    if let Some(lead) = lead {
+        let lead = camera.world_to_screen(lead);
+        let points = [
+            lead + na::Vector2::new(0.0, -LEAD_SIZE),
+            lead + na::Vector2::new(LEAD_SIZE, 0.0),
+            lead + na::Vector2::new(0.0, LEAD_SIZE),
+            lead + na::Vector2::new(-LEAD_SIZE, 0.0),
+        ];
+        for i in 0..points.len() {
+            line(
+                commands,
+                points[i],
+                points[(i + 1) % points.len()],
+                TARGET_COLOR,
+            );
+        }
+    }
+}
+
+// An arrow on the edge of the screen pointing towards something off screen, along with how far away it is
+fn draw_offscreen_indicator(
+    game: &Game,
+    camera: &Camera,
+    position: na::Vector2<f32>,
+    color: [f32; 4],
+    commands: &mut Vec<DrawCommand>,
+) {
+    let point = camera.world_to_screen(position);
+    let bounds = Rect::new(0.0, 0.0, camera.screen_size.x, camera.screen_size.y);
+    if bounds.contains(point) {
+        return;
+    }
+
+    // Slide in from the point towards the middle of the screen until it's just inside the edge
+    let centre = bounds.centre();
+    let offset = point - centre;
+    let half = (camera.screen_size / 2.0).add_scalar(-INDICATOR_INSET);
+    let scale = (half.x / offset.x.abs()).min(half.y / offset.y.abs());
+    let edge = centre + offset * scale;
+
+    let direction = offset.normalize();
+    let side = na::Vector2::new(-direction.y, direction.x);
+    let tip = edge + direction * INDICATOR_SIZE / 2.0;
+    let back = edge - direction * INDICATOR_SIZE / 2.0;
+    line(commands, back + side * INDICATOR_SIZE / 2.0, tip, color);
+    line(commands, back - side * INDICATOR_SIZE / 2.0, tip, color);
+
+    // The distance sits on the inside of the arrow so it stays on screen
+    commands.push(DrawCommand::Text {
+        text: format!("{:.0}", (position - game.player().position).norm()),
+        position: edge
+            - direction * (INDICATOR_SIZE + SMALL_TEXT_SIZE)
+            - na::Vector2::new(0.0, SMALL_TEXT_SIZE / 2.0),
+        style: TextStyle::new(SMALL_TEXT_SIZE, color).align(Align::Center),
+    });
+}
+
+pub fn line(
+    commands: &mut Vec<DrawCommand>,
+    start: na::Vector2<f32>,
+    end: na::Vector2<f32>,
+    color: [f32; 4],
+) {
+    commands.push(DrawCommand::Line {
+        start,
+        end,
+        width: LINE_WIDTH,
+        color,
+    });
+}
@@ -0,0 +1,156 @@
+use crate::galaxy::{Galaxy, SystemId};
+use std::fmt;
+
+// Seconds the drive has to spool up before the ship jumps
+const CHARGE_TIME: f32 = 4.0;
+// Seconds spent between systems, while the old system is unloaded and the new one is loaded
+const TRANSIT_TIME: f32 = 2.0;
+// Ship fuel burned by every jump, plus an amount per light year travelled
+const JUMP_FUEL: f32 = 5.0;
+const FUEL_PER_LIGHT_YEAR: f32 = 0.8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JumpState {
+    Idle,
+    Charging {
+        destination: SystemId,
+        remaining: f32,
+    },
+    // Between systems, the ship isn't anywhere in particular during this
+    Transit {
+        destination: SystemId,
+        remaining: f32,
+    },
+}
+
+// The points in a jump the game has to react to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JumpPhase {
+    // Charging finished and the ship has left the current system
+    Departed(SystemId),
+    // The ship has arrived in the destination system
+    Arrived(SystemId),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JumpError {
+    AlreadyJumping,
+    NoLane,
+    NoRoute,
+    NotEnoughFuel { required: f32, available: f32 },
+}
+
+impl fmt::Display for JumpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JumpError::AlreadyJumping => write!(f, "a jump is already in progress"),
+            JumpError::NoLane => write!(f, "no jump lane leads to that system"),
+            JumpError::NoRoute => write!(f, "no route to that system"),
+            JumpError::NotEnoughFuel {
+                required,
+                available,
+            } => write!(
+                f,
+                "not enough fuel to jump ({:.0} needed, {:.0} in the tank)",
+                required, available
+            ),
+        }
+    }
+}
+
+pub struct JumpDrive {
+    state: JumpState,
+}
+
+impl Default for JumpDrive {
+    fn default() -> Self {
+        Self {
+            state: JumpState::Idle,
+        }
+    }
+}
+
+impl JumpDrive {
+    pub fn state(&self) -> JumpState {
+        self.state
+    }
+
+    pub fn in_transit(&self) -> bool {
+        matches!(self.state, JumpState::Transit { .. })
+    }
+
+    // Fuel needed to jump between two systems
+    pub fn fuel_cost(galaxy: &Galaxy, from: SystemId, to: SystemId) -> f32 {
+        JUMP_FUEL + galaxy.distance(from, to) * FUEL_PER_LIGHT_YEAR
+    }
+
+    // Start charging for a jump to a neighbouring system
+    // Fuel isn't used until the ship actually departs, so it's checked again then
+    pub fn engage(
+        &mut self,
+        galaxy: &Galaxy,
+        from: SystemId,
+        to: SystemId,
+        fuel: f32,
+    ) -> Result<(), JumpError> {
+        if self.state != JumpState::Idle {
+            return Err(JumpError::AlreadyJumping);
+        }
+        if !galaxy.connected(from, to) {
+            return Err(JumpError::NoLane);
+        }
+        let required = Self::fuel_cost(galaxy, from, to);
+        if fuel < required {
+            return Err(JumpError::NotEnoughFuel {
+                required,
+                available: fuel,
+            });
+        }
+
+        self.state = JumpState::Charging {
+            destination: to,
+            remaining: CHARGE_TIME,
+        };
+        Ok(())
+    }
+
+    // Stop charging, once the ship is in transit it's too late
+    pub fn cancel(&mut self) {
+        if let JumpState::Charging { .. } = self.state {
+            self.state = JumpState::Idle;
+        }
+    }
+
+    pub fn update(&mut self, delta: f32) -> Option<JumpPhase> {
+        match &mut self.state {
+            JumpState::Idle => None,
+            JumpState::Charging {
+                destination,
+                remaining,
+            } => {
+                *remaining -= delta;
+                if *remaining > 0.0 {
+                    return None;
+                }
+                let destination = *destination;
+                self.state = JumpState::Transit {
+                    destination,
+                    remaining: TRANSIT_TIME,
+                };
+                Some(JumpPhase::Departed(destination))
+            }
+            JumpState::Transit {
+                destination,
+                remaining,
+            } => {
+                *remaining -= delta;
+                if *remaining > 0.0 {
+                    return None;
+                }
+                let destination = *destination;
+                self.state = JumpState::Idle;
+                Some(JumpPhase::Arrived(destination))
+            }
+        }
+    }
+}
@@ -1,53 +1,153 @@
 extern crate nalgebra as na;
 
+mod assets;
+mod asteroid;
+mod economy;
+mod event;
+mod frame_limiter;
+mod galaxy;
 mod game;
+mod gamepad;
 mod gfx;
 mod gpu;
+mod hot_reload;
+mod hud;
+mod hyperspace;
+mod menus;
+mod mining;
+mod mission;
+mod outlaw;
+mod particles;
 mod player;
+mod radar;
+mod resources;
+mod rng;
+mod save;
+mod settings;
+mod ship;
+mod states;
+mod targeting;
+mod ui;
+mod user_dirs;
+mod weapons;
 
+use frame_limiter::FrameLimiter;
 use game::Game;
+use gamepad::Gamepads;
 use gfx::GameRenderer;
 use gpu::GpuInfo;
+use hot_reload::AssetWatcher;
+use log::{error, info};
+use settings::{Options, Settings, SettingsError};
+use states::{MainMenu, StateContext, StateInput, StateMachine};
 use std::{
+    env, io, process,
     sync::{Arc, Mutex},
     time::Instant,
 };
+use ui::{Nav, Ui};
 use winit::{
-    dpi::PhysicalSize,
-    event::{ElementState, Event, VirtualKeyCode, WindowEvent},
+    event::{
+        ElementState, Event, ModifiersState, MouseButton, MouseScrollDelta, VirtualKeyCode,
+        WindowEvent,
+    },
     event_loop::{ControlFlow, EventLoop},
     window::WindowBuilder,
 };
 
+// Touchpads scroll in pixels rather than lines, this many make up one line
+const PIXELS_PER_SCROLL_LINE: f32 = 40.0;
+
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
+    // Settings come from the settings file, with anything given on the command line on top
+    let options = Options::parse(env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("{}\n\n{}", e, settings::USAGE);
+        process::exit(2);
+    });
+    if options.help {
+        println!("{}", settings::USAGE);
+        return;
+    }
+    let settings_path = options
+        .settings_path
+        .clone()
+        .unwrap_or_else(settings::settings_path);
+    let (mut settings, settings_error) = match settings::read(&settings_path) {
+        Ok(settings) => (settings, None),
+        Err(e) => (Settings::default(), Some(e)),
+    };
+    options.apply(&mut settings);
+
     // Initialize logger
-    env_logger::init_from_env(env_logger::Env::new().default_filter_or("INFO"));
+    // The filter is one of the settings, so any problem reading them is only logged from here
+    env_logger::init_from_env(env_logger::Env::new().default_filter_or(&settings.log_filter));
+    match settings_error {
+        // First run, write out the defaults so there's a file to edit
+        Some(SettingsError::Io(e)) if e.kind() == io::ErrorKind::NotFound => {
+            match settings::write(&settings_path, &Settings::default()) {
+                Ok(()) => info!("Created settings file {}", settings_path.display()),
+                Err(e) => error!("Failed to create settings file: {}", e),
+            }
+        }
+        Some(e) => error!("Failed to load settings, using defaults: {}", e),
+        None => (),
+    }
 
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new()
-        .with_inner_size(PhysicalSize::new(1366, 768))
+        .with_inner_size(settings.window.size())
         .build(&event_loop)
         .unwrap();
+    settings.window.apply(&window);
 
     // Retrieve gpu information for rendering
     let gpu_info = Arc::new(Mutex::new(
         GpuInfo::new(
             &window,
             &na::Vector2::new(window.inner_size().width, window.inner_size().height),
+            &settings.graphics,
         )
         .await,
     ));
 
     // Game
+    // A game is always running, the main menu just shows it frozen in the background
     let mut game = Game::new();
     let mut game_renderer = GameRenderer::new(gpu_info.clone());
+    game_renderer.apply_settings(&settings.graphics);
+    let mut states = StateMachine::new(Box::new(MainMenu::default()));
+    // Data files are reloaded as they're edited in development builds, so tweaks show up without restarting
+    let asset_watcher = if cfg!(debug_assertions) {
+        Some(AssetWatcher::start(vec![settings_path.clone()]))
+    } else {
+        None
+    };
+
+    // Ui
+    let mut ui = Ui::new(game_renderer.font());
+    ui.set_scale(settings.ui_scale);
+    let mut gamepads = Gamepads::new();
+    let mut cursor = na::Vector2::zeros();
+    let mut modifiers = ModifiersState::empty();
 
     // Timing
     let mut last_tick = Instant::now();
+    let mut frame_limiter = FrameLimiter::default();
+    let mut focused = true;
 
     event_loop.run(move |event, _, control_flow| {
-        *control_flow = ControlFlow::Poll;
+        let size = window.inner_size();
+        let mut ctx = StateContext {
+            game: &mut game,
+            renderer: &mut game_renderer,
+            ui: &mut ui,
+            screen_size: na::Vector2::new(size.width as f32, size.height as f32),
+            cursor,
+            settings: &mut settings,
+            settings_path: &settings_path,
+            window: &window,
+        };
         match event {
             // Exit window when close button is pressed
             Event::WindowEvent {
@@ -55,6 +155,66 @@ async fn main() {
                 window_id,
             } if window_id == window.id() => *control_flow = ControlFlow::Exit,
 
+            // The swapchain has to match the window, whether the player resized it or the settings did
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                window_id,
+            } if window_id == window.id() => ctx
+                .renderer
+                .resize(na::Vector2::new(size.width, size.height)),
+
+            // Mouse and text input go to the ui first, and then whatever state the game is in
+            Event::WindowEvent {
+                event: WindowEvent::CursorMoved { position, .. },
+                window_id,
+            } if window_id == window.id() => {
+                let position = na::Vector2::new(position.x as f32, position.y as f32);
+                let delta = position - cursor;
+                cursor = position;
+                ctx.cursor = position;
+                ctx.ui.cursor_moved(position);
+                states.input(&mut ctx, StateInput::CursorMoved { delta });
+            }
+            Event::WindowEvent {
+                event: WindowEvent::CursorLeft { .. },
+                window_id,
+            } if window_id == window.id() => ctx.ui.cursor_left(),
+            Event::WindowEvent {
+                event: WindowEvent::MouseInput { state, button, .. },
+                window_id,
+            } if window_id == window.id() => {
+                let pressed = state == ElementState::Pressed;
+                // The state decides whether a click on the ui should reach the game, so it goes first
+                states.input(&mut ctx, StateInput::MouseButton(button, pressed));
+                if button == MouseButton::Left {
+                    ctx.ui.mouse_button(pressed);
+                }
+            }
+            Event::WindowEvent {
+                event: WindowEvent::MouseWheel { delta, .. },
+                window_id,
+            } if window_id == window.id() => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => y,
+                    MouseScrollDelta::PixelDelta(position) => {
+                        position.y as f32 / PIXELS_PER_SCROLL_LINE
+                    }
+                };
+                states.input(&mut ctx, StateInput::Scroll(lines));
+            }
+            Event::WindowEvent {
+                event: WindowEvent::Focused(state),
+                window_id,
+            } if window_id == window.id() => focused = state,
+            Event::WindowEvent {
+                event: WindowEvent::ModifiersChanged(state),
+                window_id,
+            } if window_id == window.id() => modifiers = state,
+            Event::WindowEvent {
+                event: WindowEvent::ReceivedCharacter(c),
+                window_id,
+            } if window_id == window.id() => ctx.ui.character(c),
+
             // Handle keyboard input
             Event::WindowEvent {
                 event: WindowEvent::KeyboardInput { input, .. },
@@ -64,38 +224,109 @@ async fn main() {
 
                 if let Some(keycode) = input.virtual_keycode {
                     use VirtualKeyCode::*;
-                    // TODO: scancode instead of virtual keycode
-                    match keycode {
-                        // Close game
-                        Escape => *control_flow = ControlFlow::Exit,
-                        // Game input
-                        A => game.input.move_l = pressed,
-                        D => game.input.move_r = pressed,
-                        S => game.input.move_b = pressed,
-                        W => game.input.move_f = pressed,
-                        _ => (),
+                    // Alt+Enter goes in and out of fullscreen whatever else is going on, and is remembered
+                    if keycode == Return && modifiers.alt() {
+                        if pressed {
+                            ctx.settings.window.toggle_fullscreen();
+                            ctx.apply_window_settings();
+                            ctx.save_window_mode();
+                        }
+                        return;
+                    }
+                    let nav = match keycode {
+                        Up => Some(Nav::Up),
+                        Down => Some(Nav::Down),
+                        Left => Some(Nav::Left),
+                        Right => Some(Nav::Right),
+                        Return => Some(Nav::Activate),
+                        Escape if ctx.ui.has_focus() => Some(Nav::Back),
+                        _ => None,
+                    };
+                    if let Some(nav) = nav {
+                        if pressed {
+                            ctx.ui.navigate(nav);
+                        }
+                        return;
                     }
+                    // While typing into the ui, key presses are text rather than controls
+                    // Releases still go through so keys held before typing started don't get stuck
+                    if pressed && ctx.ui.wants_keyboard() {
+                        return;
+                    }
+                    states.input(&mut ctx, StateInput::Key(keycode, pressed));
                 }
             }
 
             // If there are no remaining window events to handle, update the game
             Event::MainEventsCleared => {
+                // Wait for the next frame if there's a frame limit, waking up early for any input
+                // A minimised window can't be seen, so it counts as being in the background
+                let minimised = size.width == 0 || size.height == 0;
+                let frame_limit = ctx.settings.graphics.frame_limit(focused && !minimised);
+                let frame_due = frame_limiter.frame_due(frame_limit);
+                *control_flow = match frame_limit {
+                    Some(_) => ControlFlow::WaitUntil(frame_limiter.wake_time()),
+                    None => ControlFlow::Poll,
+                };
+                if !frame_due {
+                    return;
+                }
+
                 // calculate delta
                 let current_tick = Instant::now();
                 let delta_time = current_tick.duration_since(last_tick).as_secs_f32();
                 last_tick = current_tick;
 
-                window.set_title(&format!("Delta: {}", delta_time));
+                // A file that fails to reload is only logged, the game carries on with what it had
+                if let Some(asset_watcher) = &asset_watcher {
+                    for path in asset_watcher.changed() {
+                        if path == settings_path {
+                            match ctx.reload_settings(&options) {
+                                Ok(()) => info!("Reloaded {}", path.display()),
+                                Err(e) => error!("Failed to reload {}: {}", path.display(), e),
+                            }
+                            continue;
+                        }
+                        let reloaded = match ctx.game.reload_asset(&path) {
+                            Ok(false) => ctx.renderer.reload_asset(&path),
+                            result => result,
+                        };
+                        match reloaded {
+                            Ok(true) => info!("Reloaded {}", path.display()),
+                            Ok(false) => (),
+                            Err(e) => error!("Failed to reload {}: {}", path.display(), e),
+                        }
+                    }
+                }
 
-                game.update(delta_time);
-                window.request_redraw(); // Queue a RedrawRequested event & render the game
+                // Gamepad buttons navigate the ui the same way the arrow keys do
+                while let Some(nav) = gamepads.next_nav() {
+                    ctx.ui.navigate(nav);
+                }
+
+                // The state on top of the stack updates the game if it should, and builds the ui, which is laid out fresh every frame
+                ctx.ui.begin_frame(ctx.screen_size);
+                states.update(&mut ctx, delta_time);
+                ctx.ui.end_frame();
+
+                // There's nothing to draw into while minimised
+                if !minimised {
+                    window.request_redraw(); // Queue a RedrawRequested event & render the game
+                }
             }
 
             // Render the game
             Event::RedrawRequested(_) => {
-                game_renderer.render(&game, &window);
+                states.render(ctx.renderer);
+                ctx.renderer
+                    .render(ctx.game, &window, ctx.ui.draw_commands());
             }
             _ => (),
         }
+
+        // Quitting from a menu empties the stack
+        if states.is_empty() {
+            *control_flow = ControlFlow::Exit;
+        }
     });
 }
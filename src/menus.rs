@@ -0,0 +1,327 @@
+use crate::{
+    economy::{Commodity, StationId},
+    game::Game,
+    mission::MAX_ACTIVE_MISSIONS,
+    ui::{Rect, Ui},
+};
+use log::{error, info};
+
+const MARGIN: f32 = 12.0;
+const STATION_WIDTH: f32 = 440.0;
+const STATION_HEIGHT: f32 = 540.0;
+const MAX_TRADE_AMOUNT: f32 = 50.0;
+
+// Contract titles are cut short to fit the lists, the full title is shown for whichever one is picked
+const MAX_TITLE_LENGTH: usize = 34;
+
+const SERVICES: [&str; 3] = ["Market", "Contracts", "Outfitting"];
+
+// Window shown while docked, with one of the station's services in it at a time
+#[derive(Default)]
+pub struct StationWindow {
+    // Index into SERVICES
+    service: usize,
+    market: MarketWindow,
+    contracts: ContractsWindow,
+    outfitting: OutfittingWindow,
+}
+
+impl StationWindow {
+    pub fn show(&mut self, ui: &mut Ui, game: &mut Game, screen_size: na::Vector2<f32>) {
+        let station = match game.docked_station() {
+            Some(station) => station,
+            None => return,
+        };
+        let rect = Rect::new(
+            screen_size.x - STATION_WIDTH - MARGIN,
+            MARGIN,
+            STATION_WIDTH,
+            STATION_HEIGHT,
+        );
+        let title = game.economy.stations[station].name.clone();
+
+        ui.panel(&title, rect, |ui| {
+            ui.choice("Service", &SERVICES, &mut self.service);
+            match self.service {
+                0 => self.market.show(ui, game, station),
+                1 => self.contracts.show(ui, game, station),
+                _ => self.outfitting.show(ui, game),
+            }
+        });
+    }
+}
+
+// Trading with the station's market
+// The ui is immediate mode, so this only holds what has to be remembered between frames
+struct MarketWindow {
+    selected: Option<Commodity>,
+    amount: f32,
+    filter: String,
+}
+
+impl Default for MarketWindow {
+    fn default() -> Self {
+        Self {
+            selected: None,
+            amount: 1.0,
+            filter: String::new(),
+        }
+    }
+}
+
+impl MarketWindow {
+    fn show(&mut self, ui: &mut Ui, game: &mut Game, station: StationId) {
+        let player = game.player();
+        ui.label(&format!(
+            "Credits: {}  Cargo: {}/{}",
+            player.credits,
+            player.cargo.used(),
+            player.cargo.capacity
+        ));
+        ui.text_field("Filter", &mut self.filter);
+
+        // The font is monospaced, so padding lines the columns up
+        let filter = self.filter.to_lowercase();
+        let commodities = Commodity::ALL
+            .iter()
+            .copied()
+            .filter(|commodity| commodity.name().to_lowercase().contains(&filter))
+            .collect::<Vec<_>>();
+        let market = &game.economy.stations[station].market;
+        let items = commodities
+            .iter()
+            .map(|&commodity| {
+                format!(
+                    "{:<12}{:>6}{:>6}{:>6}",
+                    commodity.name(),
+                    market.buy_price(commodity),
+                    market.sell_price(commodity),
+                    player.cargo.amount(commodity)
+                )
+            })
+            .collect::<Vec<_>>();
+        ui.label(&format!(
+            " {:<12}{:>6}{:>6}{:>6}",
+            "Commodity", "Buy", "Sell", "Held"
+        ));
+        let mut selected = self
+            .selected
+            .and_then(|selected| commodities.iter().position(|&c| c == selected));
+        if ui.list("Commodities", &items, &mut selected) {
+            self.selected = selected.map(|i| commodities[i]);
+        }
+
+        ui.slider("Amount", &mut self.amount, 1.0, MAX_TRADE_AMOUNT);
+        let amount = self.amount.round() as u32;
+        let selected = self.selected;
+        ui.row(|ui| {
+            if let Some(commodity) = selected {
+                if ui.button("Buy") {
+                    match game.buy(commodity, amount) {
+                        Ok(price) => {
+                            info!("Bought {} {} for {}", amount, commodity.name(), price)
+                        }
+                        Err(e) => error!("Can't buy: {}", e),
+                    }
+                }
+                if ui.button("Sell") {
+                    match game.sell(commodity, amount) {
+                        Ok(payment) => {
+                            info!("Sold {} {} for {}", amount, commodity.name(), payment)
+                        }
+                        Err(e) => error!("Can't sell: {}", e),
+                    }
+                }
+            }
+            if ui.button("Refuel") {
+                info!("Used {} fuel from the hold", game.refuel());
+            }
+        });
+    }
+}
+
+// Taking on the station's contracts, and giving up on any the player already has
+#[derive(Default)]
+struct ContractsWindow {
+    // Index into the station's board
+    offer: Option<usize>,
+    // Index into the player's active missions
+    active: Option<usize>,
+}
+
+impl ContractsWindow {
+    fn show(&mut self, ui: &mut Ui, game: &mut Game, station: StationId) {
+        let log = &game.missions.log;
+        ui.label(&format!(
+            "Reputation: {:.1}  Active: {}/{}",
+            log.reputation(game.system(), station),
+            log.active.len(),
+            MAX_ACTIVE_MISSIONS
+        ));
+
+        // Boards are regenerated every so often, which can leave the selection pointing past the end
+        let board = game
+            .missions
+            .boards
+            .get(station)
+            .map_or(&[][..], Vec::as_slice);
+        self.offer = self.offer.filter(|&i| i < board.len());
+        self.active = self.active.filter(|&i| i < log.active.len());
+
+        ui.label("On offer");
+        let offers = board
+            .iter()
+            .map(|mission| shorten(&mission.title))
+            .collect::<Vec<_>>();
+        if ui.list("Offers", &offers, &mut self.offer) {
+            self.active = None;
+        }
+        ui.label("In progress");
+        let active = log
+            .active
+            .iter()
+            .map(|mission| {
+                format!(
+                    "{} {}",
+                    shorten(&mission.title),
+                    format_time(mission.time_remaining())
+                )
+            })
+            .collect::<Vec<_>>();
+        if ui.list("Active", &active, &mut self.active) {
+            self.offer = None;
+        }
+
+        let selected = match (self.offer, self.active) {
+            (Some(i), _) => board.get(i),
+            (_, Some(i)) => log.active.get(i),
+            _ => None,
+        };
+        if let Some(mission) = selected {
+            ui.paragraph(&mission.title);
+            ui.label(&format!(
+                "Pays {} credits, {} to finish",
+                mission.reward,
+                format_time(mission.time_limit)
+            ));
+        }
+
+        let offer = self.offer;
+        let active = self.active.map(|i| log.active[i].id);
+        ui.row(|ui| {
+            if let Some(index) = offer {
+                if ui.button("Accept") {
+                    match game.accept_contract(station, index) {
+                        Ok(id) => info!("Accepted contract {}", id),
+                        Err(e) => error!("Can't accept contract: {}", e),
+                    }
+                    self.offer = None;
+                }
+            }
+            if let Some(id) = active {
+                if ui.button("Abandon") {
+                    game.abandon_mission(id);
+                    self.active = None;
+                }
+            }
+        });
+    }
+}
+
+// Swapping the modules fitted to the player's ship
+#[derive(Default)]
+struct OutfittingWindow {
+    slot: Option<usize>,
+    // Id of the module picked to go in the slot
+    module: Option<String>,
+}
+
+impl OutfittingWindow {
+    fn show(&mut self, ui: &mut Ui, game: &mut Game) {
+        let ship = game.player().ship();
+        let stats = ship.stats();
+        ui.label(&format!(
+            "{}  Power: {:.0}/{:.0}  CPU: {:.0}/{:.0}",
+            ship.hull().name,
+            stats.power_draw,
+            stats.power_output,
+            stats.cpu_used,
+            stats.cpu_limit
+        ));
+
+        let slots = ship
+            .hull()
+            .slots
+            .iter()
+            .zip(ship.modules())
+            .map(|(kind, module)| {
+                let fitted = module.as_ref().map_or("Empty", |m| m.name.as_str());
+                format!("{:<16}{}", format!("{:?}", kind), fitted)
+            })
+            .collect::<Vec<_>>();
+        if ui.list("Slots", &slots, &mut self.slot) {
+            self.module = None;
+        }
+        let slot = match self.slot {
+            // The ship can change underneath the window, e.g. when the ship data is reloaded
+            Some(slot) if slot < slots.len() => slot,
+            _ => return,
+        };
+
+        // Everything that fits the selected slot, in name order so the list doesn't jump around
+        let kind = ship.hull().slots[slot];
+        let mut modules = game
+            .ships
+            .modules
+            .values()
+            .filter(|module| module.slot == kind)
+            .collect::<Vec<_>>();
+        modules.sort_by(|a, b| a.name.cmp(&b.name));
+        let items = modules
+            .iter()
+            .map(|module| module.name.clone())
+            .collect::<Vec<_>>();
+        let mut selected = self
+            .module
+            .as_ref()
+            .and_then(|id| modules.iter().position(|module| &module.id == id));
+        if ui.list("Modules", &items, &mut selected) {
+            self.module = selected.map(|i| modules[i].id.clone());
+        }
+
+        let module = self.module.clone();
+        ui.row(|ui| {
+            if let Some(id) = module {
+                if ui.button("Fit") {
+                    match game.fit_module(slot, &id) {
+                        Ok(()) => info!("Fitted {} to slot {}", id, slot),
+                        Err(e) => error!("Can't fit {}: {}", id, e),
+                    }
+                }
+            }
+            if ui.button("Unfit") {
+                match game.unfit_module(slot) {
+                    Ok(Some(module)) => info!("Removed {} from slot {}", module.name, slot),
+                    Ok(None) => (),
+                    Err(e) => error!("Can't empty slot {}: {}", slot, e),
+                }
+            }
+        });
+    }
+}
+
+// Cut a title down to fit in a list, padded so anything after it lines up
+fn shorten(title: &str) -> String {
+    if title.chars().count() <= MAX_TITLE_LENGTH {
+        return format!("{:<width$}", title, width = MAX_TITLE_LENGTH);
+    }
+    let mut short = title.chars().take(MAX_TITLE_LENGTH - 3).collect::<String>();
+    short.push_str("...");
+    short
+}
+
+// Minutes and seconds
+fn format_time(seconds: f32) -> String {
+    let seconds = seconds.max(0.0) as u32;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
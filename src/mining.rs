@@ -0,0 +1,100 @@
+use crate::{asteroid::Asteroid, economy::Commodity, event::GameEvent, player::Player};
+
+// Units of material extracted per second by a tier 1 tool on a rock of hardness 1
+const BASE_EXTRACTION_RATE: f32 = 1.0;
+// Energy drained and heat produced per second while the beam is on
+const BEAM_ENERGY: f32 = 1.5;
+const BEAM_HEAT: f32 = 3.0;
+
+// The visible part of the laser, in world space
+#[derive(Clone, Copy, Debug)]
+pub struct MiningBeam {
+    pub start: na::Vector2<f32>,
+    pub end: na::Vector2<f32>,
+    // Index of the asteroid being mined, if the beam is actually touching one
+    pub target: Option<usize>,
+}
+
+// The player's continuous mining beam
+// Fires straight out the front of the ship, and while it's held on an asteroid, material flows into the cargo hold
+#[derive(Default)]
+pub struct MiningLaser {
+    // Partially extracted units of each commodity, these go into the hold once they add up to a whole unit
+    progress: [f32; Commodity::COUNT],
+    beam: Option<MiningBeam>,
+}
+
+impl MiningLaser {
+    pub fn beam(&self) -> Option<&MiningBeam> {
+        self.beam.as_ref()
+    }
+
+    pub fn update(
+        &mut self,
+        delta: f32,
+        firing: bool,
+        player: &mut Player,
+        asteroids: &mut Vec<Asteroid>,
+        events: &mut Vec<GameEvent>,
+    ) {
+        self.beam = None;
+        let stats = *player.ship().stats();
+        if !firing
+            || stats.mining_tier == 0
+            || !player.try_spend_energy(BEAM_ENERGY * delta, BEAM_HEAT * delta)
+        {
+            return;
+        }
+
+        // Find the closest asteroid in the beam's path
+        let origin = player.position;
+        let direction = player.heading();
+        let hit = asteroids
+            .iter()
+            .enumerate()
+            .filter_map(|(i, asteroid)| asteroid.ray_hit(origin, direction).map(|d| (i, d)))
+            .filter(|&(_, distance)| distance <= stats.mining_range)
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+
+        let length = hit.map_or(stats.mining_range, |(_, distance)| distance);
+        self.beam = Some(MiningBeam {
+            start: origin,
+            end: origin + direction * length,
+            target: hit.map(|(i, _)| i),
+        });
+
+        let index = match hit {
+            Some((i, _)) => i,
+            None => return,
+        };
+        // Nothing to do with the rock if there's nowhere to put it
+        if player.cargo.free() == 0 {
+            return;
+        }
+
+        let asteroid = &mut asteroids[index];
+        let rate = BASE_EXTRACTION_RATE * stats.mining_tier as f32 / asteroid.kind.hardness();
+        let extracted = asteroid.extract(rate * delta);
+        for &(commodity, fraction) in asteroid.kind.composition() {
+            let progress = &mut self.progress[commodity.index()];
+            *progress += extracted * fraction;
+            let whole = progress.floor();
+            if whole >= 1.0 {
+                // Whatever doesn't fit is lost
+                let amount = player.cargo.add(commodity, whole as u32);
+                *progress -= whole;
+                if amount > 0 {
+                    events.push(GameEvent::Mined { commodity, amount });
+                }
+            }
+        }
+
+        if asteroid.depleted() {
+            events.push(GameEvent::AsteroidDestroyed {
+                index,
+                position: asteroid.position,
+            });
+            asteroids.swap_remove(index);
+        }
+    }
+}
@@ -0,0 +1,614 @@
+use crate::{
+    assets::{self, AssetError},
+    economy::{Commodity, Station, StationId},
+    event::GameEvent,
+    galaxy::SystemId,
+    player::Player,
+    rng::Rng,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+pub const TEMPLATES_PATH: &str = "missions/templates.ron";
+
+// Number of contracts on offer at each station at any one time
+const BOARD_SIZE: usize = 4;
+// Seconds between contract boards being regenerated
+const BOARD_REFRESH: f32 = 120.0;
+pub const MAX_ACTIVE_MISSIONS: usize = 5;
+// Extra credits paid per world unit between the issuer and the destination, for contracts that involve travel
+const REWARD_PER_DISTANCE: f32 = 2.0;
+// Failing a contract loses this many times the reputation it would have earned
+const FAILURE_REPUTATION_FACTOR: f32 = 1.5;
+
+// Escorted convoys crawl along at this speed, and fail if the player strays too far for too long
+const ESCORT_SPEED: f32 = 4.0;
+const ESCORT_RANGE: f32 = 15.0;
+const ESCORT_GRACE: f32 = 10.0;
+// How close the player has to get to a patrol waypoint for it to count
+const PATROL_RADIUS: f32 = 5.0;
+const PATROL_WAYPOINTS: usize = 3;
+
+// Wanted ships hide out somewhere in this range of distances from the station offering the bounty
+const HIDEOUT_DISTANCE: (f32, f32) = (30.0, 80.0);
+
+const BOUNTY_NAMES: [&str; 6] = [
+    "Red Vex",
+    "Marrow",
+    "The Tinker",
+    "Silas Crane",
+    "Ghost of Io",
+    "Nine Fingers",
+];
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContractKind {
+    Delivery,
+    Bounty,
+    Escort,
+    Patrol,
+    MiningQuota,
+}
+
+// Blueprint for a kind of contract as described in the template data file
+// The title can use {station}, {destination}, {commodity}, {amount} and {target}, which get filled in on generation
+#[derive(Deserialize, Clone, Debug)]
+pub struct ContractTemplate {
+    pub kind: ContractKind,
+    pub title: String,
+    // Range of the base credit reward
+    pub reward: (u32, u32),
+    pub reputation: f32,
+    // Seconds the player has to finish once the contract is accepted
+    pub time_limit: f32,
+    // Relative chance of this template being picked
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+    // Stations only offer this contract to players they trust at least this much
+    #[serde(default)]
+    pub min_reputation: f32,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+pub type MissionId = u32;
+
+// What the player has to do to complete a mission, along with any progress made so far
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum Objective {
+    // Be docked at the destination with the goods in the hold
+    Deliver {
+        commodity: Commodity,
+        amount: u32,
+        destination: StationId,
+    },
+    // Destroy the named ship, which turns up at its hideout in the issuer's system while the contract is active
+    Bounty {
+        target: String,
+        #[serde(default)]
+        hideout: na::Vector2<f32>,
+    },
+    // Stay close to a convoy until it reaches its destination
+    Escort {
+        destination: StationId,
+        convoy: na::Vector2<f32>,
+        straggling: f32,
+    },
+    // Visit each waypoint in order
+    Patrol {
+        waypoints: Vec<na::Vector2<f32>>,
+        next: usize,
+    },
+    // Mine a certain amount of a commodity
+    MiningQuota {
+        commodity: Commodity,
+        amount: u32,
+        mined: u32,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Mission {
+    pub id: MissionId,
+    pub title: String,
+    // Station ids only mean something within a system, so the issuer's system is needed to tell stations apart
+    pub system: SystemId,
+    pub issuer: StationId,
+    pub objective: Objective,
+    pub reward: u32,
+    pub reputation: f32,
+    pub time_limit: f32,
+    pub elapsed: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FailReason {
+    TimedOut,
+    ConvoyLost,
+    Abandoned,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MissionStatus {
+    InProgress,
+    Completed,
+    Failed(FailReason),
+}
+
+// Everything a mission needs to know about the world to track its progress for a frame
+pub struct MissionContext<'a> {
+    pub delta: f32,
+    // System the player is currently in, the stations are the ones in this system
+    pub system: SystemId,
+    pub player: &'a Player,
+    pub stations: &'a [Station],
+    pub events: &'a [GameEvent],
+}
+
+impl Mission {
+    pub fn time_remaining(&self) -> f32 {
+        (self.time_limit - self.elapsed).max(0.0)
+    }
+
+    fn update(&mut self, ctx: &MissionContext) -> MissionStatus {
+        self.elapsed += ctx.delta;
+        // Most objectives can only progress in the system the mission was issued in
+        let here = ctx.system == self.system;
+
+        let status = match &mut self.objective {
+            Objective::Deliver {
+                commodity,
+                amount,
+                destination,
+            } => {
+                // Checked whenever the player arrives at the destination, or picks up goods while already there
+                let at_destination = ctx.events.iter().any(|event| match event {
+                    GameEvent::Docked(station) | GameEvent::Bought { station, .. } => {
+                        station == destination
+                    }
+                    _ => false,
+                });
+                if here && at_destination && ctx.player.cargo.amount(*commodity) >= *amount {
+                    MissionStatus::Completed
+                } else {
+                    MissionStatus::InProgress
+                }
+            }
+            Objective::Bounty { target, .. } => {
+                let destroyed = ctx.events.iter().any(|event| {
+                    matches!(event, GameEvent::ShipDestroyed { name, .. } if name == target)
+                });
+                if destroyed {
+                    MissionStatus::Completed
+                } else {
+                    MissionStatus::InProgress
+                }
+            }
+            Objective::Escort {
+                destination,
+                convoy,
+                straggling,
+            } => {
+                // The convoy waits for nobody, leaving the system counts as falling behind
+                if !here {
+                    *straggling += ctx.delta;
+                    return if *straggling > ESCORT_GRACE {
+                        MissionStatus::Failed(FailReason::ConvoyLost)
+                    } else {
+                        MissionStatus::InProgress
+                    };
+                }
+
+                let end = ctx.stations[*destination].position;
+                let to_end = end - *convoy;
+                let step = ESCORT_SPEED * ctx.delta;
+                if to_end.norm() <= step {
+                    *convoy = end;
+                } else {
+                    *convoy += to_end.normalize() * step;
+                }
+
+                if (ctx.player.position - *convoy).norm() > ESCORT_RANGE {
+                    *straggling += ctx.delta;
+                } else {
+                    *straggling = 0.0;
+                }
+
+                if *straggling > ESCORT_GRACE {
+                    MissionStatus::Failed(FailReason::ConvoyLost)
+                } else if *convoy == end {
+                    MissionStatus::Completed
+                } else {
+                    MissionStatus::InProgress
+                }
+            }
+            Objective::Patrol { waypoints, next } => {
+                // Saves can be edited, so there might not be a waypoint where the patrol thinks it's up to
+                if let Some(&waypoint) = waypoints.get(*next) {
+                    if here && (ctx.player.position - waypoint).norm() <= PATROL_RADIUS {
+                        *next += 1;
+                    }
+                }
+                if *next >= waypoints.len() {
+                    MissionStatus::Completed
+                } else {
+                    MissionStatus::InProgress
+                }
+            }
+            Objective::MiningQuota {
+                commodity,
+                amount,
+                mined,
+            } => {
+                for event in ctx.events {
+                    if let GameEvent::Mined {
+                        commodity: c,
+                        amount: a,
+                    } = event
+                    {
+                        if c == commodity {
+                            *mined += a;
+                        }
+                    }
+                }
+                if mined >= amount {
+                    MissionStatus::Completed
+                } else {
+                    MissionStatus::InProgress
+                }
+            }
+        };
+
+        if status == MissionStatus::InProgress && self.elapsed > self.time_limit {
+            MissionStatus::Failed(FailReason::TimedOut)
+        } else {
+            status
+        }
+    }
+}
+
+// The player's mission history and standing, this is the part of the mission system that gets saved
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct MissionLog {
+    pub active: Vec<Mission>,
+    // Standing with each station, indexed by system id and then station id
+    pub reputation: Vec<Vec<f32>>,
+    pub completed: u32,
+    pub failed: u32,
+    next_id: MissionId,
+}
+
+impl MissionLog {
+    pub fn reputation(&self, system: SystemId, station: StationId) -> f32 {
+        self.reputation
+            .get(system)
+            .and_then(|stations| stations.get(station))
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    fn adjust_reputation(&mut self, system: SystemId, station: StationId, amount: f32) {
+        if self.reputation.len() <= system {
+            self.reputation.resize(system + 1, Vec::new());
+        }
+        let stations = &mut self.reputation[system];
+        if stations.len() <= station {
+            stations.resize(station + 1, 0.0);
+        }
+        stations[station] += amount;
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MissionError {
+    NotDocked,
+    NoSuchContract,
+    TooManyActive,
+}
+
+impl fmt::Display for MissionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            MissionError::NotDocked => {
+                "contracts can only be accepted while docked at the station offering them"
+            }
+            MissionError::NoSuchContract => "that contract is no longer on offer",
+            MissionError::TooManyActive => "too many missions already in progress",
+        })
+    }
+}
+
+// The result of a mission finishing one way or the other
+pub struct MissionOutcome {
+    pub mission: Mission,
+    pub status: MissionStatus,
+}
+
+// Contract boards for every station in the current system plus the player's mission log
+pub struct Missions {
+    pub log: MissionLog,
+    // System the boards belong to
+    system: SystemId,
+    // Contracts on offer, indexed by station id
+    pub boards: Vec<Vec<Mission>>,
+    templates: Vec<ContractTemplate>,
+    rng: Rng,
+    refresh_timer: f32,
+}
+
+impl Missions {
+    pub fn new(seed: u64, system: SystemId, stations: &[Station]) -> Result<Self, AssetError> {
+        let mut missions = Self {
+            log: MissionLog::default(),
+            system,
+            boards: Vec::new(),
+            templates: load_templates()?,
+            rng: Rng::new(seed),
+            refresh_timer: 0.0,
+        };
+        missions.refresh_boards(stations);
+        Ok(missions)
+    }
+
+    // Load the templates again, e.g. after the data file changed
+    // Contracts already on offer or accepted stay as they were, only new ones use the new templates
+    pub fn reload_templates(&mut self) -> Result<(), AssetError> {
+        self.templates = load_templates()?;
+        Ok(())
+    }
+
+    // Put up fresh contract boards for the stations in a newly entered system
+    pub fn enter_system(&mut self, system: SystemId, stations: &[Station]) {
+        self.system = system;
+        self.refresh_timer = 0.0;
+        self.refresh_boards(stations);
+    }
+
+    // Track progress on every active mission
+    // Reputation is handled here, anything else that comes from finishing a mission is returned for the game to deal with
+    pub fn update(&mut self, ctx: &MissionContext) -> Vec<MissionOutcome> {
+        self.refresh_timer += ctx.delta;
+        if self.refresh_timer >= BOARD_REFRESH {
+            self.refresh_timer = 0.0;
+            self.refresh_boards(ctx.stations);
+        }
+
+        let mut outcomes = Vec::new();
+        let mut i = 0;
+        while i < self.log.active.len() {
+            let status = self.log.active[i].update(ctx);
+            if status == MissionStatus::InProgress {
+                i += 1;
+                continue;
+            }
+            let mission = self.log.active.remove(i);
+            self.finish(&mission, status);
+            outcomes.push(MissionOutcome { mission, status });
+        }
+        outcomes
+    }
+
+    // Take a contract off a station's board
+    pub fn accept(
+        &mut self,
+        station: StationId,
+        docked: Option<StationId>,
+        index: usize,
+    ) -> Result<MissionId, MissionError> {
+        if docked != Some(station) {
+            return Err(MissionError::NotDocked);
+        }
+        if self.log.active.len() >= MAX_ACTIVE_MISSIONS {
+            return Err(MissionError::TooManyActive);
+        }
+        let board = self
+            .boards
+            .get_mut(station)
+            .ok_or(MissionError::NoSuchContract)?;
+        if index >= board.len() {
+            return Err(MissionError::NoSuchContract);
+        }
+
+        let mut mission = board.remove(index);
+        mission.id = self.log.next_id;
+        self.log.next_id += 1;
+        let id = mission.id;
+        self.log.active.push(mission);
+        Ok(id)
+    }
+
+    // Give up on a mission, which counts as failing it
+    pub fn abandon(&mut self, id: MissionId) -> Option<MissionOutcome> {
+        let index = self.log.active.iter().position(|m| m.id == id)?;
+        let mission = self.log.active.remove(index);
+        let status = MissionStatus::Failed(FailReason::Abandoned);
+        self.finish(&mission, status);
+        Some(MissionOutcome { mission, status })
+    }
+
+    fn finish(&mut self, mission: &Mission, status: MissionStatus) {
+        if status == MissionStatus::Completed {
+            self.log.completed += 1;
+            self.log
+                .adjust_reputation(mission.system, mission.issuer, mission.reputation);
+        } else {
+            self.log.failed += 1;
+            self.log.adjust_reputation(
+                mission.system,
+                mission.issuer,
+                -mission.reputation * FAILURE_REPUTATION_FACTOR,
+            );
+        }
+    }
+
+    fn refresh_boards(&mut self, stations: &[Station]) {
+        self.boards = (0..stations.len())
+            .map(|issuer| {
+                (0..BOARD_SIZE)
+                    .filter_map(|_| self.generate(issuer, stations))
+                    .collect()
+            })
+            .collect();
+    }
+
+    // Roll up a random contract for a station from the templates it's allowed to offer
+    fn generate(&mut self, issuer: StationId, stations: &[Station]) -> Option<Mission> {
+        let reputation = self.log.reputation(self.system, issuer);
+        let available = self
+            .templates
+            .iter()
+            .filter(|t| t.min_reputation <= reputation)
+            .collect::<Vec<_>>();
+        let total_weight = available.iter().map(|t| t.weight).sum::<u32>();
+        if total_weight == 0 || stations.len() < 2 {
+            return None;
+        }
+        let mut roll = self.rng.below(total_weight as usize) as u32;
+        let template = available
+            .into_iter()
+            .find(|t| {
+                if roll < t.weight {
+                    true
+                } else {
+                    roll -= t.weight;
+                    false
+                }
+            })?
+            .clone();
+
+        let rng = &mut self.rng;
+        let here = stations[issuer].position;
+        // Some other station for contracts that involve going somewhere
+        let destination = (issuer + 1 + rng.below(stations.len() - 1)) % stations.len();
+        // Worked out in u64 so a range that goes all the way up to u32::MAX doesn't overflow
+        let span = u64::from(template.reward.1 - template.reward.0) + 1;
+        let mut reward = template.reward.0 + (rng.next_u64() % span) as u32;
+
+        let mut commodity = None;
+        let mut amount = None;
+        let mut target = None;
+        let objective = match template.kind {
+            ContractKind::Delivery => {
+                // Ask for whichever of the goods the destination uses it's shortest on
+                // A little noise breaks ties, otherwise every fresh market would ask for the same thing
+                let station = &stations[destination];
+                let market = &station.market;
+                let wanted = station
+                    .kind
+                    .recipes()
+                    .iter()
+                    .flat_map(|recipe| recipe.inputs.iter())
+                    .chain(station.kind.upkeep())
+                    .map(|&(commodity, _)| {
+                        let i = commodity.index();
+                        let shortage = market.stock[i] / market.target[i] + rng.range_f32(0.0, 0.1);
+                        (commodity, shortage)
+                    })
+                    .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+                    .map_or(Commodity::Food, |(commodity, _)| commodity);
+                let count = 5 + rng.below(16) as u32;
+                reward =
+                    reward.saturating_add(distance_bonus(here, stations[destination].position));
+                commodity = Some(wanted);
+                amount = Some(count);
+                Objective::Deliver {
+                    commodity: wanted,
+                    amount: count,
+                    destination,
+                }
+            }
+            ContractKind::Bounty => {
+                let name = format!(
+                    "{} #{}",
+                    BOUNTY_NAMES[rng.below(BOUNTY_NAMES.len())],
+                    rng.below(900) + 100
+                );
+                let angle = rng.range_f32(0.0, std::f32::consts::PI * 2.0);
+                let hideout = here
+                    + na::Vector2::new(angle.cos(), angle.sin())
+                        * rng.range_f32(HIDEOUT_DISTANCE.0, HIDEOUT_DISTANCE.1);
+                target = Some(name.clone());
+                Objective::Bounty {
+                    target: name,
+                    hideout,
+                }
+            }
+            ContractKind::Escort => {
+                reward =
+                    reward.saturating_add(distance_bonus(here, stations[destination].position));
+                Objective::Escort {
+                    destination,
+                    convoy: here,
+                    straggling: 0.0,
+                }
+            }
+            ContractKind::Patrol => {
+                let waypoints = (0..PATROL_WAYPOINTS)
+                    .map(|_| {
+                        let angle = rng.range_f32(0.0, std::f32::consts::PI * 2.0);
+                        here + na::Vector2::new(angle.cos(), angle.sin())
+                            * rng.range_f32(20.0, 60.0)
+                    })
+                    .collect();
+                Objective::Patrol { waypoints, next: 0 }
+            }
+            ContractKind::MiningQuota => {
+                let wanted = if rng.below(2) == 0 {
+                    Commodity::Ore
+                } else {
+                    Commodity::Water
+                };
+                let count = 5 + rng.below(11) as u32;
+                commodity = Some(wanted);
+                amount = Some(count);
+                Objective::MiningQuota {
+                    commodity: wanted,
+                    amount: count,
+                    mined: 0,
+                }
+            }
+        };
+
+        let title = template
+            .title
+            .replace("{station}", &stations[issuer].name)
+            .replace("{destination}", &stations[destination].name)
+            .replace("{commodity}", commodity.map_or("", Commodity::name))
+            .replace("{amount}", &amount.map_or(String::new(), |a| a.to_string()))
+            .replace("{target}", target.as_deref().unwrap_or(""));
+
+        Some(Mission {
+            // Offers don't get a real id until they're accepted
+            id: 0,
+            title,
+            system: self.system,
+            issuer,
+            objective,
+            reward,
+            reputation: template.reputation,
+            time_limit: template.time_limit,
+            elapsed: 0.0,
+        })
+    }
+}
+
+// Load the templates data file, checking every template makes sense
+fn load_templates() -> Result<Vec<ContractTemplate>, AssetError> {
+    let templates: Vec<ContractTemplate> = assets::load_ron(TEMPLATES_PATH)?;
+    if let Some(template) = templates.iter().find(|t| t.reward.0 > t.reward.1) {
+        return Err(AssetError::Invalid(
+            assets::asset_path(TEMPLATES_PATH),
+            format!(
+                "the reward range for \"{}\" ends before it starts",
+                template.title
+            ),
+        ));
+    }
+    Ok(templates)
+}
+
+fn distance_bonus(from: na::Vector2<f32>, to: na::Vector2<f32>) -> u32 {
+    ((to - from).norm() * REWARD_PER_DISTANCE) as u32
+}
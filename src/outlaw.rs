@@ -0,0 +1,66 @@
+// Hull points of a wanted ship, it takes a few volleys to bring one down
+const OUTLAW_HULL: f32 = 60.0;
+// Outlaws keep circling their hideout at this distance and speed
+const ORBIT_RADIUS: f32 = 12.0;
+const ORBIT_SPEED: f32 = 6.0;
+// Ships are treated as a circle this big when shots hit them
+pub const OUTLAW_RADIUS: f32 = 0.6;
+
+// A wanted ship that a bounty contract has sent the player after
+// They don't fight back yet, they just fly in circles around their hideout until they're shot down
+#[derive(Clone, Debug)]
+pub struct Outlaw {
+    // Stays the same for as long as the ship is around, for targeting it
+    pub id: u32,
+    pub name: String,
+    pub position: na::Vector2<f32>,
+    pub velocity: na::Vector2<f32>,
+    pub angle: f32,
+    hideout: na::Vector2<f32>,
+    // How far round the orbit the ship is, in radians
+    orbit: f32,
+    hull: f32,
+}
+
+impl Outlaw {
+    pub fn new(id: u32, name: String, hideout: na::Vector2<f32>) -> Self {
+        let mut outlaw = Self {
+            id,
+            name,
+            position: hideout,
+            velocity: na::Vector2::zeros(),
+            angle: 0.0,
+            hideout,
+            orbit: 0.0,
+            hull: OUTLAW_HULL,
+        };
+        outlaw.update(0.0);
+        outlaw
+    }
+
+    pub fn update(&mut self, delta: f32) {
+        self.orbit += ORBIT_SPEED / ORBIT_RADIUS * delta;
+        let out = na::Vector2::new(self.orbit.cos(), self.orbit.sin());
+        self.position = self.hideout + out * ORBIT_RADIUS;
+        // Anticlockwise round the orbit, with the nose pointing the way it's going
+        self.velocity = na::Vector2::new(-out.y, out.x) * ORBIT_SPEED;
+        self.angle = (-self.velocity.x).atan2(self.velocity.y);
+    }
+
+    // Returns true if the hit destroyed the ship
+    pub fn damage(&mut self, amount: f32) -> bool {
+        self.hull = (self.hull - amount).max(0.0);
+        self.hull <= 0.0
+    }
+
+    // Distance along a ray to where it first hits this ship, if it does at all
+    pub fn ray_hit(&self, origin: na::Vector2<f32>, direction: na::Vector2<f32>) -> Option<f32> {
+        let to_center = self.position - origin;
+        let along = to_center.dot(&direction);
+        let miss_squared = to_center.norm_squared() - along * along;
+        if along < 0.0 || miss_squared > OUTLAW_RADIUS * OUTLAW_RADIUS {
+            return None;
+        }
+        Some((along - (OUTLAW_RADIUS * OUTLAW_RADIUS - miss_squared).sqrt()).max(0.0))
+    }
+}
@@ -0,0 +1,227 @@
+use crate::{
+    assets::{self, AssetError},
+    rng::Rng,
+};
+use serde::Deserialize;
+
+pub const EFFECTS_PATH: &str = "effects/particles.ron";
+// Most particles alive at once, anything spawned past this is dropped
+// The pool is allocated once up front at this size, so spawning and killing particles never allocates
+pub const MAX_PARTICLES: usize = 8192;
+
+// Values a curve can blend between
+pub trait Lerp: Copy + Default {
+    fn lerp(self, other: Self, t: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Lerp for [f32; 4] {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        let mut result = self;
+        for (a, b) in result.iter_mut().zip(&other) {
+            *a = a.lerp(*b, t);
+        }
+        result
+    }
+}
+
+// A value that changes over a particle's life
+// Keys are (time, value) pairs in order, where time runs from 0 when the particle spawns to 1 when it dies
+#[derive(Deserialize, Clone, Debug)]
+pub struct Curve<T>(Vec<(f32, T)>);
+
+impl<T: Lerp> Curve<T> {
+    pub fn sample(&self, t: f32) -> T {
+        let keys = &self.0;
+        match (keys.first(), keys.last()) {
+            (Some(first), _) if t <= first.0 => first.1,
+            (_, Some(last)) if t >= last.0 => last.1,
+            (None, _) | (_, None) => T::default(),
+            _ => keys
+                .windows(2)
+                .find(|pair| t <= pair[1].0)
+                .map(|pair| {
+                    let (start, end) = (pair[0], pair[1]);
+                    start.1.lerp(end.1, (t - start.0) / (end.0 - start.0))
+                })
+                .unwrap_or_default(),
+        }
+    }
+}
+
+// How a kind of particle looks and behaves, as described in the effects data file
+#[derive(Deserialize, Clone, Debug)]
+pub struct ParticleEffect {
+    pub id: String,
+    // Seconds each particle lives for, picked from the range
+    pub lifetime: (f32, f32),
+    // Launch speed, on top of the velocity of whatever emitted it
+    pub speed: (f32, f32),
+    // Particles fly off up to this many radians either side of the emit direction, PI sends them everywhere
+    pub spread: f32,
+    // Fraction of a particle's velocity lost every second
+    #[serde(default)]
+    pub drag: f32,
+    // Particles per second from a continuous emitter running at full intensity
+    #[serde(default)]
+    pub rate: f32,
+    // Number of particles in a one off burst
+    #[serde(default)]
+    pub burst: (u32, u32),
+    pub color: Curve<[f32; 4]>,
+    pub size: Curve<f32>,
+}
+
+pub type EffectId = usize;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Particle {
+    pub position: na::Vector2<f32>,
+    pub velocity: na::Vector2<f32>,
+    // Current size and colour, worked out from the effect's curves every update
+    pub size: f32,
+    pub color: [f32; 4],
+    age: f32,
+    lifetime: f32,
+    effect: EffectId,
+}
+
+// Every live particle in the world
+pub struct ParticleSystem {
+    effects: Vec<ParticleEffect>,
+    // Live particles only, dead ones are swapped out so these stay packed together
+    particles: Vec<Particle>,
+    rng: Rng,
+}
+
+impl ParticleSystem {
+    pub fn new(seed: u64) -> Result<Self, AssetError> {
+        Ok(Self {
+            effects: assets::load_ron(EFFECTS_PATH)?,
+            particles: Vec::with_capacity(MAX_PARTICLES),
+            rng: Rng::new(seed),
+        })
+    }
+
+    pub fn effect(&self, id: &str) -> Option<EffectId> {
+        self.effects.iter().position(|effect| effect.id == id)
+    }
+
+    pub fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    pub fn clear(&mut self) {
+        self.particles.clear();
+    }
+
+    // Spawn particles at a point, flying off around a direction (in radians, 0 being along the x axis)
+    pub fn emit(
+        &mut self,
+        effect: EffectId,
+        count: u32,
+        position: na::Vector2<f32>,
+        velocity: na::Vector2<f32>,
+        direction: f32,
+    ) {
+        let Self {
+            effects,
+            particles,
+            rng,
+        } = self;
+        let def = &effects[effect];
+        for _ in 0..count {
+            if particles.len() >= MAX_PARTICLES {
+                return;
+            }
+            let angle = direction + rng.range_f32(-def.spread, def.spread);
+            let speed = rng.range_f32(def.speed.0, def.speed.1);
+            particles.push(Particle {
+                position,
+                velocity: velocity + na::Vector2::new(angle.cos(), angle.sin()) * speed,
+                size: def.size.sample(0.0),
+                color: def.color.sample(0.0),
+                age: 0.0,
+                lifetime: rng.range_f32(def.lifetime.0, def.lifetime.1),
+                effect,
+            });
+        }
+    }
+
+    // A one off burst of an effect's particles in every direction the effect allows
+    pub fn burst(
+        &mut self,
+        effect: EffectId,
+        position: na::Vector2<f32>,
+        velocity: na::Vector2<f32>,
+    ) {
+        let (min, max) = self.effects[effect].burst;
+        let count = min + self.rng.below((max - min + 1) as usize) as u32;
+        let direction = self.rng.range_f32(0.0, std::f32::consts::PI * 2.0);
+        self.emit(effect, count, position, velocity, direction);
+    }
+
+    pub fn update(&mut self, delta: f32) {
+        let Self {
+            effects, particles, ..
+        } = self;
+        let mut i = 0;
+        while i < particles.len() {
+            let particle = &mut particles[i];
+            particle.age += delta;
+            if particle.age >= particle.lifetime {
+                particles.swap_remove(i);
+                continue;
+            }
+
+            let def = &effects[particle.effect];
+            particle.velocity *= (1.0 - def.drag * delta).max(0.0);
+            particle.position += particle.velocity * delta;
+            let t = particle.age / particle.lifetime;
+            particle.size = def.size.sample(t);
+            particle.color = def.color.sample(t);
+            i += 1;
+        }
+    }
+}
+
+// Continuous source of particles attached to something, like an engine
+pub struct Emitter {
+    pub effect: EffectId,
+    // Fractional particles carried over between updates, so low rates still emit at the right average
+    accumulator: f32,
+}
+
+impl Emitter {
+    pub fn new(effect: EffectId) -> Self {
+        Self {
+            effect,
+            accumulator: 0.0,
+        }
+    }
+
+    // Emit at the effect's rate scaled by the intensity, e.g. how hard an engine is firing
+    pub fn update(
+        &mut self,
+        particles: &mut ParticleSystem,
+        delta: f32,
+        intensity: f32,
+        position: na::Vector2<f32>,
+        velocity: na::Vector2<f32>,
+        direction: f32,
+    ) {
+        if intensity <= 0.0 {
+            self.accumulator = 0.0;
+            return;
+        }
+        self.accumulator += particles.effects[self.effect].rate * intensity * delta;
+        let count = self.accumulator.floor();
+        self.accumulator -= count;
+        particles.emit(self.effect, count as u32, position, velocity, direction);
+    }
+}
@@ -1,37 +1,354 @@
+use crate::{
+    economy::CargoHold,
+    resources::{ResourceStatus, ShipResources},
+    save::PlayerSave,
+    ship::{FitError, ModuleDef, Ship, ShipCatalog},
+};
+
+const STARTING_CREDITS: u32 = 1000;
+// Reverse and lateral thrusters are weaker than the main engines
+const REVERSE_THRUST_FACTOR: f32 = 0.5;
+const STRAFE_THRUST_FACTOR: f32 = 0.4;
+// Angular acceleration, as a multiple of the ship's turn rate per second
+const ANGULAR_ACCELERATION: f32 = 4.0;
+
+// Thrust multiplier while the afterburner is lit
+const AFTERBURNER_THRUST_FACTOR: f32 = 2.5;
+// Energy drained per second while burning, on top of the extra fuel and heat from the higher thrust
+const AFTERBURNER_ENERGY: f32 = 12.0;
+// Seconds of burn available in one go, and how long it takes to come back once it's been used
+const AFTERBURNER_DURATION: f32 = 3.0;
+const AFTERBURNER_COOLDOWN: f32 = 5.0;
+
 // Controls state for the player
 pub struct PlayerControls {
     pub move_l: bool,
     pub move_r: bool,
     pub move_f: bool,
     pub move_b: bool,
+    pub strafe_l: bool,
+    pub strafe_r: bool,
+    pub afterburner: bool,
+    // Held state of the flight assist toggle, the player works out when it's first pressed
+    pub toggle_flight_assist: bool,
+    pub kill_rotation: bool,
+    pub kill_velocity: bool,
+}
+
+// A boost that can burn for a limited time before it has to cool down
+pub struct Afterburner {
+    burn_remaining: f32,
+    cooldown: f32,
+}
+
+impl Afterburner {
+    fn new() -> Self {
+        Self {
+            burn_remaining: AFTERBURNER_DURATION,
+            cooldown: 0.0,
+        }
+    }
+
+    // Fraction of a full burn still available
+    pub fn charge(&self) -> f32 {
+        (self.burn_remaining / AFTERBURNER_DURATION).max(0.0)
+    }
+
+    pub fn cooling_down(&self) -> bool {
+        self.cooldown > 0.0
+    }
+
+    // Whether there's any burn left to use right now
+    fn ready(&self) -> bool {
+        !self.cooling_down() && self.burn_remaining > 0.0
+    }
+
+    // Returns whether the afterburner is burning this frame
+    fn update(&mut self, delta: f32, engaged: bool) -> bool {
+        if self.cooling_down() {
+            self.cooldown -= delta;
+            if !self.cooling_down() {
+                self.burn_remaining = AFTERBURNER_DURATION;
+            }
+            return false;
+        }
+
+        if engaged && self.burn_remaining > 0.0 {
+            self.burn_remaining -= delta;
+            if self.burn_remaining <= 0.0 {
+                self.cooldown = AFTERBURNER_COOLDOWN;
+            }
+            return true;
+        }
+
+        // Letting go partway through a burn still needs a cooldown before the next one
+        if self.burn_remaining < AFTERBURNER_DURATION {
+            self.cooldown = AFTERBURNER_COOLDOWN;
+        }
+        false
+    }
 }
 
 pub struct Player {
     pub angle: f32,
+    pub angular_velocity: f32,
     pub position: na::Vector2<f32>,
     pub velocity: na::Vector2<f32>,
+    pub credits: u32,
+    pub cargo: CargoHold,
+    ship: Ship,
+    resources: ShipResources,
+    afterburner: Afterburner,
+    // Assisted flight uses the thrusters to cancel out any drift and spin the pilot isn't asking for
+    // With it off, the ship flies fully Newtonian
+    flight_assist: bool,
+    flight_assist_held: bool,
+    // Thrust actually applied last update, in units of full engine thrust
+    thrust: na::Vector2<f32>,
 }
 
 impl Player {
-    pub fn new() -> Self {
+    pub fn new(ship: Ship) -> Self {
         Self {
             angle: 0.0,
+            angular_velocity: 0.0,
             position: na::Vector2::default(),
             velocity: na::Vector2::default(),
+            credits: STARTING_CREDITS,
+            cargo: CargoHold::new(ship.stats().cargo_capacity),
+            resources: ShipResources::new(ship.stats()),
+            ship,
+            afterburner: Afterburner::new(),
+            flight_assist: true,
+            flight_assist_held: false,
+            thrust: na::Vector2::zeros(),
+        }
+    }
+
+    // Restore a player from saved state, the ship is rebuilt from its saved loadout
+    pub fn from_save(catalog: &ShipCatalog, save: &PlayerSave) -> Result<Self, FitError> {
+        let ship = Ship::with_loadout(catalog, &save.hull, &save.modules)?;
+        let mut player = Self::new(ship);
+        player.position = save.position;
+        player.velocity = save.velocity;
+        player.angle = save.angle;
+        player.credits = save.credits;
+        player.cargo = save.cargo.clone();
+        player.cargo.capacity = player.ship.stats().cargo_capacity;
+        player.resources.fuel = save.fuel;
+        player.resources.hull = (player.resources.hull - save.hull_damage).max(0.0);
+        player.resources.clamp(player.ship.stats());
+        Ok(player)
+    }
+
+    pub fn to_save(&self) -> PlayerSave {
+        PlayerSave {
+            position: self.position,
+            velocity: self.velocity,
+            angle: self.angle,
+            credits: self.credits,
+            cargo: self.cargo.clone(),
+            fuel: self.resources.fuel,
+            hull_damage: self.ship.stats().hull_strength - self.resources.hull,
+            hull: self.ship.hull().id.clone(),
+            modules: self.ship.loadout(),
+        }
+    }
+
+    pub fn thrust(&self) -> na::Vector2<f32> {
+        self.thrust
+    }
+
+    pub fn ship(&self) -> &Ship {
+        &self.ship
+    }
+
+    pub fn afterburner(&self) -> &Afterburner {
+        &self.afterburner
+    }
+
+    pub fn flight_assist(&self) -> bool {
+        self.flight_assist
+    }
+
+    pub fn resources(&self) -> &ShipResources {
+        &self.resources
+    }
+
+    pub fn resource_status(&self) -> ResourceStatus {
+        self.resources.status(self.ship.stats())
+    }
+
+    // Pay for something powered by the capacitors, returns false and spends nothing if it can't be afforded
+    pub fn try_spend_energy(&mut self, energy: f32, heat: f32) -> bool {
+        self.resources.try_spend(self.ship.stats(), energy, heat)
+    }
+
+    // Burn fuel outside of normal flight (e.g. jumping), returning false and leaving the tank alone if there isn't enough
+    pub fn try_spend_fuel(&mut self, amount: f32) -> bool {
+        if self.resources.fuel < amount {
+            return false;
+        }
+        self.resources.fuel -= amount;
+        true
+    }
+
+    // Take a hit, returning true if the hull was breached
+    pub fn damage(&mut self, amount: f32) -> bool {
+        self.resources.damage(amount)
+    }
+
+    // Fill the tank, returning how much fuel was taken
+    pub fn refuel(&mut self, amount: f32) -> f32 {
+        self.resources.refuel(self.ship.stats(), amount)
+    }
+
+    // How much more fuel the tank can take
+    pub fn fuel_space(&self) -> f32 {
+        (self.ship.stats().fuel_capacity - self.resources.fuel).max(0.0)
+    }
+
+    // Unit vector pointing out the front of the ship
+    // An angle of zero faces up the y axis, and positive angles turn counter-clockwise
+    pub fn heading(&self) -> na::Vector2<f32> {
+        na::Vector2::new(-self.angle.sin(), self.angle.cos())
+    }
+
+    // Loadout changes go through the player so anything derived from the ship's stats stays in sync
+    // They're tried out on a copy of the ship first, so a change that leaves the cargo without room changes nothing
+    pub fn fit(
+        &mut self,
+        catalog: &ShipCatalog,
+        slot: usize,
+        module_id: &str,
+    ) -> Result<(), FitError> {
+        let mut ship = self.ship.clone();
+        ship.fit(catalog, slot, module_id)?;
+        self.refit(ship)
+    }
+
+    pub fn unfit(&mut self, slot: usize) -> Result<Option<ModuleDef>, FitError> {
+        let mut ship = self.ship.clone();
+        let module = ship.unfit(slot)?;
+        self.refit(ship)?;
+        Ok(module)
+    }
+
+    // Rebuild the ship from the catalog with the same loadout, e.g. after the ship data files changed
+    // The old ship is kept if the loadout doesn't fit any more
+    pub fn rebuild_ship(&mut self, catalog: &ShipCatalog) -> Result<(), FitError> {
+        let ship = Ship::with_loadout(catalog, &self.ship.hull().id, &self.ship.loadout())?;
+        self.refit(ship)
+    }
+
+    fn refit(&mut self, ship: Ship) -> Result<(), FitError> {
+        let capacity = ship.stats().cargo_capacity;
+        if capacity < self.cargo.used() {
+            return Err(FitError::CargoWontFit {
+                required: self.cargo.used(),
+                capacity,
+            });
         }
+        self.ship = ship;
+        self.cargo.capacity = capacity;
+        self.resources.clamp(self.ship.stats());
+        Ok(())
     }
 
-    // Every frame, takes a controls struct 
+    // Every frame, takes a controls struct
     pub fn update(&mut self, delta: f32, controls: &PlayerControls) {
-        // This is just test movement code that I wrote to see if the player was rendering correctly
-        // Don't worry about how it works, you can just destroy it and replace it
-        let direction = na::Vector2::new(
-            controls.move_r as i32 as f32 - controls.move_l as i32 as f32,
-            controls.move_f as i32 as f32 - controls.move_b as i32 as f32,
-        );
-        // This part just tells the player's velocity to slowly interpolate to match the target velocity based on the combined control directions
-        // It's not necessary to keep this, I was just testing things out, ideally we should switch to a more realistic model
-        self.velocity = self.velocity.lerp(&(direction * 5.0), delta * 2.0); // --??
+        // Nothing can happen in a frame with no time in it, and braking divides by the frame time
+        if delta <= 0.0 {
+            return;
+        }
+        let stats = *self.ship.stats();
+
+        if controls.toggle_flight_assist && !self.flight_assist_held {
+            self.flight_assist = !self.flight_assist;
+        }
+        self.flight_assist_held = controls.toggle_flight_assist;
+
+        // Rotation
+        // Left and right spin the ship up, and either the pilot or the flight assist has to spin it back down
+        let turn = controls.move_l as i32 as f32 - controls.move_r as i32 as f32;
+        let max_spin_change = stats.turn_rate * ANGULAR_ACCELERATION * delta;
+        if controls.kill_rotation {
+            self.angular_velocity = approach(self.angular_velocity, 0.0, max_spin_change);
+        } else if self.flight_assist {
+            self.angular_velocity = approach(
+                self.angular_velocity,
+                turn * stats.turn_rate,
+                max_spin_change,
+            );
+        } else {
+            self.angular_velocity += turn * max_spin_change;
+        }
+        self.angle += self.angular_velocity * delta;
+
+        // Translation
+        // Thrust is worked out as a vector in units of full engine thrust, then limited by what the fuel and heat allow
+        // There's no drag in space, so without flight assist the ship keeps drifting until thrust is applied the other way
+        let heading = self.heading();
+        let right = na::Vector2::new(heading.y, -heading.x);
+        let mut forward =
+            controls.move_f as i32 as f32 - controls.move_b as i32 as f32 * REVERSE_THRUST_FACTOR;
+        let strafe = controls.strafe_r as i32 as f32 - controls.strafe_l as i32 as f32;
+
+        // Burn time is only used up once the energy for it has been paid, running out of energy is the same as letting go
+        let engaged = controls.afterburner
+            && forward > 0.0
+            && self.afterburner.ready()
+            && self
+                .resources
+                .try_spend(&stats, AFTERBURNER_ENERGY * delta, 0.0);
+        let burning = self.afterburner.update(delta, engaged);
+        if burning {
+            forward *= AFTERBURNER_THRUST_FACTOR;
+        }
+
+        let mut demand = heading * forward + right * strafe * STRAFE_THRUST_FACTOR;
+        let acceleration = stats.acceleration();
+        if acceleration > 0.0 {
+            // Braking thrust needed to cancel a velocity this frame, capped at full thrust
+            let brake = |velocity: na::Vector2<f32>| {
+                let thrust = -velocity / (acceleration * delta);
+                let magnitude = thrust.norm();
+                if magnitude > 1.0 {
+                    thrust / magnitude
+                } else {
+                    thrust
+                }
+            };
+            if controls.kill_velocity {
+                demand = brake(self.velocity);
+            } else if self.flight_assist {
+                // Only damp the axes the pilot isn't actively thrusting along
+                if forward == 0.0 {
+                    demand += brake(heading * self.velocity.dot(&heading));
+                }
+                if strafe == 0.0 {
+                    demand += brake(right * self.velocity.dot(&right));
+                }
+            }
+        }
+
+        let throttle = demand.norm();
+        let output = self.resources.update(delta, &stats, throttle);
+        self.thrust = if throttle > 0.0 {
+            demand * (output / throttle)
+        } else {
+            na::Vector2::zeros()
+        };
+        self.velocity += self.thrust * acceleration * delta;
         self.position += self.velocity * delta;
     }
 }
+
+// Move a value towards a target by at most a given step
+fn approach(value: f32, target: f32, step: f32) -> f32 {
+    if value < target {
+        (value + step).min(target)
+    } else {
+        (value - step).max(target)
+    }
+}
@@ -0,0 +1,426 @@
+use crate::{
+    asteroid::AsteroidKind,
+    economy::StationKind,
+    game::Game,
+    gpu::font::{Align, TextStyle},
+    hud::{self, SMALL_TEXT_SIZE, TARGET_COLOR, TEXT_COLOR},
+    settings::{Action, Keybinds},
+    targeting::Target,
+    ui::{DrawCommand, Rect},
+};
+use winit::event::VirtualKeyCode;
+
+// Radar ranges in world units, stepped through with the zoom keys
+const RADAR_RANGES: [f32; 5] = [25.0, 50.0, 100.0, 200.0, 400.0];
+const DEFAULT_RANGE: usize = 2;
+const RADAR_BACKGROUND: [f32; 4] = [0.05, 0.07, 0.1, 0.75];
+const RING_COLOR: [f32; 4] = [0.3, 0.45, 0.5, 0.6];
+const RING_SEGMENTS: usize = 48;
+const PLAYER_MARKER_SIZE: f32 = 8.0;
+const PLAYER_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+// The system map shows the whole system at once, scale is in pixels per world unit
+const MAP_BACKGROUND: [f32; 4] = [0.02, 0.03, 0.05, 0.95];
+const MAP_DEFAULT_SCALE: f32 = 2.0;
+const MAP_MIN_SCALE: f32 = 0.5;
+const MAP_MAX_SCALE: f32 = 40.0;
+// Each step of the scroll wheel zooms by this factor
+const MAP_ZOOM_STEP: f32 = 1.25;
+// Grid lines are spaced so they're always at least this many pixels apart
+const MAP_GRID_MIN_SPACING: f32 = 60.0;
+const MAP_GRID_COLOR: [f32; 4] = [0.15, 0.2, 0.25, 0.6];
+// Blips are drawn this much bigger on the system map than on the radar
+const MAP_BLIP_SCALE: f32 = 1.5;
+const MAP_HELP: &str = "Right drag to pan, scroll to zoom";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RadarMode {
+    // Up on the radar is up in the world
+    NorthUp,
+    // Up on the radar is wherever the ship is pointing
+    HeadingUp,
+}
+
+// What a blip on the radar or map is, which decides how it's drawn
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlipKind {
+    Station(StationKind),
+    // NPC haulers flying between stations
+    Trader,
+    Asteroid(AsteroidKind),
+    // Wanted ships the player has a bounty on
+    Outlaw,
+}
+
+impl BlipKind {
+    pub fn color(self) -> [f32; 4] {
+        match self {
+            BlipKind::Station(kind) => match kind {
+                StationKind::MiningOutpost => [0.85, 0.6, 0.3, 1.0],
+                StationKind::Refinery => [0.9, 0.85, 0.4, 1.0],
+                StationKind::Agricultural => [0.45, 0.85, 0.4, 1.0],
+                StationKind::Industrial => [0.6, 0.65, 0.9, 1.0],
+                StationKind::TradeHub => [0.4, 0.9, 0.95, 1.0],
+            },
+            BlipKind::Trader => [0.3, 0.8, 0.3, 1.0],
+            BlipKind::Asteroid(_) => [0.45, 0.42, 0.4, 1.0],
+            BlipKind::Outlaw => [1.0, 0.25, 0.2, 1.0],
+        }
+    }
+
+    // Size of the blip on the radar in pixels
+    fn size(self) -> f32 {
+        match self {
+            BlipKind::Station(_) => 6.0,
+            BlipKind::Trader => 4.0,
+            BlipKind::Asteroid(_) => 3.0,
+            BlipKind::Outlaw => 4.0,
+        }
+    }
+}
+
+pub struct Blip {
+    pub kind: BlipKind,
+    pub position: na::Vector2<f32>,
+    pub targeted: bool,
+}
+
+// Everything in the system worth showing on the radar or map, biggest things last so they're drawn on top
+pub fn blips(game: &Game) -> Vec<Blip> {
+    let target = game.target();
+    let asteroids = game.asteroids.iter().enumerate().map(|(i, asteroid)| Blip {
+        kind: BlipKind::Asteroid(asteroid.kind),
+        position: asteroid.position,
+        targeted: target == Some(Target::Asteroid(i)),
+    });
+    let stations = &game.economy.stations;
+    let traders = game
+        .economy
+        .traders
+        .iter()
+        .filter_map(|trader| trader.position(stations))
+        .map(|position| Blip {
+            kind: BlipKind::Trader,
+            position,
+            targeted: false,
+        });
+    let outlaws = game.outlaws.iter().map(|outlaw| Blip {
+        kind: BlipKind::Outlaw,
+        position: outlaw.position,
+        targeted: target == Some(Target::Outlaw(outlaw.id)),
+    });
+    let stations = stations.iter().enumerate().map(|(i, station)| Blip {
+        kind: BlipKind::Station(station.kind),
+        position: station.position,
+        targeted: target == Some(Target::Station(i)),
+    });
+    asteroids
+        .chain(traders)
+        .chain(outlaws)
+        .chain(stations)
+        .collect()
+}
+
+// Small map of the space around the player in the corner of the hud
+pub struct Radar {
+    range: usize,
+    pub mode: RadarMode,
+}
+
+impl Default for Radar {
+    fn default() -> Self {
+        Self {
+            range: DEFAULT_RANGE,
+            mode: RadarMode::NorthUp,
+        }
+    }
+}
+
+impl Radar {
+    // Distance from the player to the edge of the radar, in world units
+    pub fn range(&self) -> f32 {
+        RADAR_RANGES[self.range]
+    }
+
+    pub fn zoom_in(&mut self) {
+        self.range = self.range.saturating_sub(1);
+    }
+
+    pub fn zoom_out(&mut self) {
+        self.range = (self.range + 1).min(RADAR_RANGES.len() - 1);
+    }
+
+    pub fn toggle_mode(&mut self) {
+        self.mode = match self.mode {
+            RadarMode::NorthUp => RadarMode::HeadingUp,
+            RadarMode::HeadingUp => RadarMode::NorthUp,
+        };
+    }
+
+    // Draw the radar filling a square on screen
+    pub fn draw(&self, game: &Game, rect: Rect, commands: &mut Vec<DrawCommand>) {
+        let player = game.player();
+        let centre = rect.centre();
+        let radius = rect.size.x.min(rect.size.y) / 2.0;
+        let scale = radius / self.range();
+        // Heading up turns the world so the ship's nose points up the radar
+        let rotation = match self.mode {
+            RadarMode::NorthUp => na::Rotation2::identity(),
+            RadarMode::HeadingUp => na::Rotation2::new(-player.angle),
+        };
+        // World offsets from the player to pixels on screen, which has y pointing down
+        let to_screen = |offset: na::Vector2<f32>| {
+            let offset = rotation * offset * scale;
+            centre + na::Vector2::new(offset.x, -offset.y)
+        };
+
+        commands.push(DrawCommand::Rect {
+            rect,
+            color: RADAR_BACKGROUND,
+        });
+        circle(commands, centre, radius, RING_COLOR);
+        circle(commands, centre, radius / 2.0, RING_COLOR);
+
+        for blip in blips(game) {
+            let mut offset = blip.position - player.position;
+            let distance = offset.norm();
+            if distance > self.range() {
+                // The target stays pinned to the rim when it's out of range so it can still be found
+                if !blip.targeted {
+                    continue;
+                }
+                offset *= self.range() / distance;
+            }
+            draw_blip(commands, to_screen(offset), &blip, 1.0);
+        }
+
+        let heading = rotation * player.heading();
+        player_marker(commands, centre, heading);
+
+        // Which way is north when it isn't up
+        if self.mode == RadarMode::HeadingUp {
+            commands.push(DrawCommand::Text {
+                text: "N".to_owned(),
+                position: to_screen(na::Vector2::new(0.0, self.range() * 0.85))
+                    - na::Vector2::new(0.0, SMALL_TEXT_SIZE / 2.0),
+                style: TextStyle::new(SMALL_TEXT_SIZE, RING_COLOR).align(Align::Center),
+            });
+        }
+        commands.push(DrawCommand::Text {
+            text: format!("{:.0}", self.range()),
+            position: rect.min,
+            style: TextStyle::new(SMALL_TEXT_SIZE, TEXT_COLOR),
+        });
+    }
+}
+
+// Full screen map of the whole system, panned and zoomed with the mouse
+pub struct SystemMap {
+    // World position in the middle of the screen
+    pub centre: na::Vector2<f32>,
+    // Pixels per world unit
+    pub scale: f32,
+    // Whatever the system map is bound to, for the help text
+    close_key: VirtualKeyCode,
+}
+
+impl Default for SystemMap {
+    fn default() -> Self {
+        Self {
+            centre: na::Vector2::zeros(),
+            scale: MAP_DEFAULT_SCALE,
+            close_key: Keybinds::default().key(Action::SystemMap),
+        }
+    }
+}
+
+impl SystemMap {
+    // The map always opens on the player
+    // Keys can be rebound at any time, so the one that closes it is only looked up now
+    pub fn open(&mut self, centre: na::Vector2<f32>, close_key: VirtualKeyCode) {
+        self.centre = centre;
+        self.close_key = close_key;
+    }
+
+    // Drag the map by some number of pixels
+    pub fn pan(&mut self, pixels: na::Vector2<f32>) {
+        self.centre -= na::Vector2::new(pixels.x, -pixels.y) / self.scale;
+    }
+
+    // Zoom in (positive steps) or out, keeping whatever is under the cursor in the same place
+    pub fn zoom(&mut self, steps: f32, cursor: na::Vector2<f32>, screen_size: na::Vector2<f32>) {
+        let anchor = self.screen_to_world(cursor, screen_size);
+        self.scale = (self.scale * MAP_ZOOM_STEP.powf(steps)).clamp(MAP_MIN_SCALE, MAP_MAX_SCALE);
+        self.centre += anchor - self.screen_to_world(cursor, screen_size);
+    }
+
+    pub fn world_to_screen(
+        &self,
+        point: na::Vector2<f32>,
+        screen_size: na::Vector2<f32>,
+    ) -> na::Vector2<f32> {
+        let offset = (point - self.centre) * self.scale;
+        screen_size / 2.0 + na::Vector2::new(offset.x, -offset.y)
+    }
+
+    pub fn screen_to_world(
+        &self,
+        pixel: na::Vector2<f32>,
+        screen_size: na::Vector2<f32>,
+    ) -> na::Vector2<f32> {
+        let offset = (pixel - screen_size / 2.0) / self.scale;
+        self.centre + na::Vector2::new(offset.x, -offset.y)
+    }
+
+    pub fn draw(
+        &self,
+        game: &Game,
+        screen_size: na::Vector2<f32>,
+        commands: &mut Vec<DrawCommand>,
+    ) {
+        commands.push(DrawCommand::Rect {
+            rect: Rect::new(0.0, 0.0, screen_size.x, screen_size.y),
+            color: MAP_BACKGROUND,
+        });
+
+        // Grid lines at a round number of world units, coarser as the map zooms out
+        // Steps go 1, 2, 5, 10, 20, 50...
+        let mut spacing = 1.0;
+        let mut steps = [2.0, 2.5, 2.0].iter().cycle();
+        while spacing * self.scale < MAP_GRID_MIN_SPACING {
+            spacing *= steps.next().unwrap();
+        }
+        let min = self.screen_to_world(na::Vector2::new(0.0, screen_size.y), screen_size);
+        let max = self.screen_to_world(na::Vector2::new(screen_size.x, 0.0), screen_size);
+        let mut x = (min.x / spacing).ceil() * spacing;
+        while x <= max.x {
+            let pixel = self
+                .world_to_screen(na::Vector2::new(x, 0.0), screen_size)
+                .x;
+            grid_line(
+                commands,
+                na::Vector2::new(pixel, 0.0),
+                na::Vector2::new(pixel, screen_size.y),
+            );
+            x += spacing;
+        }
+        let mut y = (min.y / spacing).ceil() * spacing;
+        while y <= max.y {
+            let pixel = self
+                .world_to_screen(na::Vector2::new(0.0, y), screen_size)
+                .y;
+            grid_line(
+                commands,
+                na::Vector2::new(0.0, pixel),
+                na::Vector2::new(screen_size.x, pixel),
+            );
+            y += spacing;
+        }
+
+        for blip in blips(game) {
+            draw_blip(
+                commands,
+                self.world_to_screen(blip.position, screen_size),
+                &blip,
+                MAP_BLIP_SCALE,
+            );
+        }
+        let label_style = TextStyle::new(SMALL_TEXT_SIZE, TEXT_COLOR).align(Align::Center);
+        for station in &game.economy.stations {
+            commands.push(DrawCommand::Text {
+                text: station.name.clone(),
+                position: self.world_to_screen(station.position, screen_size)
+                    + na::Vector2::new(0.0, SMALL_TEXT_SIZE * 0.5),
+                style: label_style,
+            });
+        }
+
+        let player = game.player();
+        player_marker(
+            commands,
+            self.world_to_screen(player.position, screen_size),
+            player.heading(),
+        );
+
+        commands.push(DrawCommand::Text {
+            text: format!(
+                "{}\nGrid {:.0}\n{}, {:?} to close",
+                game.galaxy.system(game.system()).name,
+                spacing,
+                MAP_HELP,
+                self.close_key
+            ),
+            position: na::Vector2::new(screen_size.x / 2.0, hud::MARGIN),
+            style: TextStyle::new(SMALL_TEXT_SIZE, TEXT_COLOR).align(Align::Center),
+        });
+    }
+}
+
+fn draw_blip(commands: &mut Vec<DrawCommand>, at: na::Vector2<f32>, blip: &Blip, scale: f32) {
+    let size = blip.kind.size() * scale;
+    commands.push(DrawCommand::Rect {
+        rect: Rect::new(at.x - size / 2.0, at.y - size / 2.0, size, size),
+        color: blip.kind.color(),
+    });
+    if blip.targeted {
+        let half = size / 2.0 + 3.0;
+        let corners = [
+            at + na::Vector2::new(-half, -half),
+            at + na::Vector2::new(half, -half),
+            at + na::Vector2::new(half, half),
+            at + na::Vector2::new(-half, half),
+        ];
+        for i in 0..corners.len() {
+            hud::line(
+                commands,
+                corners[i],
+                corners[(i + 1) % corners.len()],
+                TARGET_COLOR,
+            );
+        }
+    }
+}
+
+// An arrowhead pointing along the heading, which is in world space with y up
+fn player_marker(commands: &mut Vec<DrawCommand>, at: na::Vector2<f32>, heading: na::Vector2<f32>) {
+    let forward = na::Vector2::new(heading.x, -heading.y);
+    let side = na::Vector2::new(-forward.y, forward.x);
+    let tip = at + forward * PLAYER_MARKER_SIZE;
+    let back = at - forward * PLAYER_MARKER_SIZE * 0.5;
+    hud::line(
+        commands,
+        back + side * PLAYER_MARKER_SIZE * 0.6,
+        tip,
+        PLAYER_COLOR,
+    );
+    hud::line(
+        commands,
+        back - side * PLAYER_MARKER_SIZE * 0.6,
+        tip,
+        PLAYER_COLOR,
+    );
+    hud::line(
+        commands,
+        back + side * PLAYER_MARKER_SIZE * 0.6,
+        back - side * PLAYER_MARKER_SIZE * 0.6,
+        PLAYER_COLOR,
+    );
+}
+
+fn circle(commands: &mut Vec<DrawCommand>, centre: na::Vector2<f32>, radius: f32, color: [f32; 4]) {
+    let point = |i: usize| {
+        let angle = i as f32 / RING_SEGMENTS as f32 * std::f32::consts::PI * 2.0;
+        centre + na::Vector2::new(angle.cos(), angle.sin()) * radius
+    };
+    for i in 0..RING_SEGMENTS {
+        hud::line(commands, point(i), point(i + 1), color);
+    }
+}
+
+fn grid_line(commands: &mut Vec<DrawCommand>, start: na::Vector2<f32>, end: na::Vector2<f32>) {
+    commands.push(DrawCommand::Line {
+        start,
+        end,
+        width: 1.0,
+        color: MAP_GRID_COLOR,
+    });
+}
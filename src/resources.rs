@@ -0,0 +1,155 @@
+use crate::ship::ShipStats;
+
+// While overheated, everything runs at reduced capacity
+const OVERHEAT_THRUST_FACTOR: f32 = 0.4;
+const OVERHEAT_POWER_FACTOR: f32 = 0.5;
+// Once overheated, the ship has to cool back down below this fraction of its heat capacity to recover
+const OVERHEAT_RECOVERY: f32 = 0.5;
+// Seconds after taking damage before the shields start recharging
+const SHIELD_RECHARGE_DELAY: f32 = 3.0;
+
+// The consumable state of a ship: stored energy, fuel, heat, shields and hull
+// Capacities and rates all come from the ship's stats, this only tracks how full everything currently is
+#[derive(Clone, Debug)]
+pub struct ShipResources {
+    pub energy: f32,
+    pub fuel: f32,
+    pub heat: f32,
+    pub shield: f32,
+    pub hull: f32,
+    overheated: bool,
+    // Time left before the shields start recharging again
+    shield_delay: f32,
+}
+
+// A summary of a ship's resources as fractions of capacity, for the HUD and AI to make decisions from
+#[derive(Clone, Copy, Debug)]
+pub struct ResourceStatus {
+    pub energy: f32,
+    pub fuel: f32,
+    pub heat: f32,
+    pub shield: f32,
+    pub hull: f32,
+    pub overheated: bool,
+}
+
+impl ShipResources {
+    // A fresh ship with full capacitors, tanks and shields and an undamaged hull
+    pub fn new(stats: &ShipStats) -> Self {
+        Self {
+            energy: stats.capacitor,
+            fuel: stats.fuel_capacity,
+            heat: 0.0,
+            shield: stats.shield_capacity,
+            hull: stats.hull_strength,
+            overheated: false,
+            shield_delay: 0.0,
+        }
+    }
+
+    pub fn status(&self, stats: &ShipStats) -> ResourceStatus {
+        ResourceStatus {
+            energy: fraction(self.energy, stats.capacitor),
+            fuel: fraction(self.fuel, stats.fuel_capacity),
+            heat: fraction(self.heat, stats.heat_capacity),
+            shield: fraction(self.shield, stats.shield_capacity),
+            hull: fraction(self.hull, stats.hull_strength),
+            overheated: self.overheated,
+        }
+    }
+
+    // Run the ship's systems for one frame
+    // Takes how hard the engines are being pushed (0 to 1) and returns how much of that they can actually deliver,
+    // which is less when the tanks run dry or the ship overheats
+    pub fn update(&mut self, delta: f32, stats: &ShipStats, throttle: f32) -> f32 {
+        // Burn fuel, scaling thrust down if there isn't enough left for the whole frame
+        let mut output = throttle;
+        let fuel_needed = stats.fuel_use * throttle * delta;
+        if fuel_needed > self.fuel {
+            output *= self.fuel / fuel_needed;
+        }
+        self.fuel = (self.fuel - fuel_needed).max(0.0);
+        if self.overheated {
+            output *= OVERHEAT_THRUST_FACTOR;
+        }
+
+        // The reactor charges the capacitors with whatever is left after running the fitted modules
+        let power_factor = if self.overheated {
+            OVERHEAT_POWER_FACTOR
+        } else {
+            1.0
+        };
+        let net_power = stats.power_output * power_factor - stats.power_draw;
+        self.energy = (self.energy + net_power * delta).clamp(0.0, stats.capacitor);
+
+        // Engines heat the ship up, and the hull and heat sinks bleed it off
+        self.heat += (stats.engine_heat * output - stats.heat_dissipation) * delta;
+        self.heat = self.heat.max(0.0);
+        self.update_overheat(stats);
+
+        self.shield_delay = (self.shield_delay - delta).max(0.0);
+        if self.shield_delay <= 0.0 {
+            self.shield = (self.shield + stats.shield_recharge * delta).min(stats.shield_capacity);
+        }
+
+        output
+    }
+
+    // Shields soak up damage first, and whatever gets through comes off the hull
+    // Returns true if the hull was breached
+    pub fn damage(&mut self, amount: f32) -> bool {
+        let absorbed = amount.min(self.shield);
+        self.shield -= absorbed;
+        self.hull = (self.hull - (amount - absorbed)).max(0.0);
+        self.shield_delay = SHIELD_RECHARGE_DELAY;
+        self.hull <= 0.0
+    }
+
+    // Try to pay the cost of an action that uses stored energy, such as firing a weapon
+    // Nothing is spent if there isn't enough energy or the ship is overheated
+    pub fn try_spend(&mut self, stats: &ShipStats, energy: f32, heat: f32) -> bool {
+        if self.overheated || self.energy < energy {
+            return false;
+        }
+        self.energy -= energy;
+        self.add_heat(stats, heat);
+        true
+    }
+
+    pub fn add_heat(&mut self, stats: &ShipStats, heat: f32) {
+        self.heat += heat;
+        self.update_overheat(stats);
+    }
+
+    // Top up the tank, returning how much fuel actually fit
+    pub fn refuel(&mut self, stats: &ShipStats, amount: f32) -> f32 {
+        let added = amount.min(stats.fuel_capacity - self.fuel).max(0.0);
+        self.fuel += added;
+        added
+    }
+
+    // Keep the stored amounts within capacity, needed after a refit shrinks any of them
+    pub fn clamp(&mut self, stats: &ShipStats) {
+        self.energy = self.energy.min(stats.capacitor);
+        self.fuel = self.fuel.min(stats.fuel_capacity);
+        self.shield = self.shield.min(stats.shield_capacity);
+        self.hull = self.hull.min(stats.hull_strength);
+        self.update_overheat(stats);
+    }
+
+    fn update_overheat(&mut self, stats: &ShipStats) {
+        if self.heat >= stats.heat_capacity {
+            self.overheated = true;
+        } else if self.heat <= stats.heat_capacity * OVERHEAT_RECOVERY {
+            self.overheated = false;
+        }
+    }
+}
+
+fn fraction(amount: f32, capacity: f32) -> f32 {
+    if capacity > 0.0 {
+        amount / capacity
+    } else {
+        0.0
+    }
+}
@@ -0,0 +1,60 @@
+// Small deterministic random number generator (xorshift64*)
+// Anything that has to be reproducible from a seed (economy, world generation, etc.) should use this rather than a system rng,
+// so that headless simulations always produce the same results for the same seed
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // The seed is scrambled with splitmix64 so nearby seeds don't start out alike
+        // A zero state would only ever produce zeroes, and splitmix64 only gives zero for one seed, so that one is swapped out
+        let mut state = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        state = (state ^ (state >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        state = (state ^ (state >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        state ^= state >> 31;
+        Self {
+            state: if state == 0 {
+                0x2545_F491_4F6C_DD1D
+            } else {
+                state
+            },
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    // Uniform float in [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    // Uniform float in [min, max)
+    pub fn range_f32(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+
+    // Uniform integer in [0, max)
+    pub fn below(&mut self, max: usize) -> usize {
+        (self.next_u64() % max.max(1) as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_seed_gets_stuck_on_zero() {
+        for &seed in &[0, 1, 0x9E37_79B9_7F4A_7C15, 0x61C8_8646_80B5_83EB, u64::MAX] {
+            let mut rng = Rng::new(seed);
+            assert!((0..4).any(|_| rng.next_u64() != 0), "seed {:#x}", seed);
+        }
+    }
+}
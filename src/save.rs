@@ -0,0 +1,91 @@
+use crate::{economy::CargoHold, galaxy::SystemId, mission::MissionLog, ship::FitError, user_dirs};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+// Bumped whenever the save format changes in a way old saves can't be read
+const SAVE_VERSION: u32 = 2;
+
+// Saves are kept in the player's data directory, not with the game
+pub fn quicksave_path() -> PathBuf {
+    user_dirs::data_dir().join("saves").join("quicksave.ron")
+}
+
+// Everything about a game that persists between sessions
+// The world itself (stations, asteroids, etc.) is regenerated from the seed, so only the player's progress is stored
+#[derive(Serialize, Deserialize)]
+pub struct SaveData {
+    pub version: u32,
+    pub player: PlayerSave,
+    // System the player was in
+    pub system: SystemId,
+    pub missions: MissionLog,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PlayerSave {
+    pub position: na::Vector2<f32>,
+    pub velocity: na::Vector2<f32>,
+    pub angle: f32,
+    pub credits: u32,
+    pub cargo: CargoHold,
+    pub fuel: f32,
+    // Shields recharge on their own so only hull damage is kept, older saves didn't have it
+    #[serde(default)]
+    pub hull_damage: f32,
+    // Loadout is stored as ids and rebuilt from the ship catalog on load
+    pub hull: String,
+    pub modules: Vec<Option<String>>,
+}
+
+impl SaveData {
+    pub fn new(player: PlayerSave, system: SystemId, missions: MissionLog) -> Self {
+        Self {
+            version: SAVE_VERSION,
+            player,
+            system,
+            missions,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    Format(ron::Error),
+    UnsupportedVersion(u32),
+    InvalidShip(FitError),
+    InvalidSystem(SystemId),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Io(e) => write!(f, "{}", e),
+            SaveError::Format(e) => write!(f, "invalid save data: {}", e),
+            SaveError::UnsupportedVersion(v) => write!(f, "unsupported save version {}", v),
+            SaveError::InvalidShip(e) => write!(f, "saved ship is invalid: {}", e),
+            SaveError::InvalidSystem(id) => write!(f, "saved system {} doesn't exist", id),
+        }
+    }
+}
+
+pub fn write(path: &Path, data: &SaveData) -> Result<(), SaveError> {
+    let contents = ron::ser::to_string_pretty(data, ron::ser::PrettyConfig::default())
+        .map_err(SaveError::Format)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(SaveError::Io)?;
+    }
+    fs::write(path, contents).map_err(SaveError::Io)
+}
+
+pub fn read(path: &Path) -> Result<SaveData, SaveError> {
+    let contents = fs::read_to_string(path).map_err(SaveError::Io)?;
+    let data: SaveData = ron::de::from_str(&contents).map_err(SaveError::Format)?;
+    if data.version != SAVE_VERSION {
+        return Err(SaveError::UnsupportedVersion(data.version));
+    }
+    Ok(data)
+}
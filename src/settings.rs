@@ -0,0 +1,761 @@
+use crate::user_dirs;
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Reverse,
+    collections::BTreeMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::VirtualKeyCode,
+    monitor::{MonitorHandle, VideoMode},
+    window::{Fullscreen, Window},
+};
+
+// Smallest values that still give a usable ui and view, anything lower is raised to these
+const MIN_UI_SCALE: f32 = 0.25;
+const MIN_ZOOM: f32 = 0.01;
+
+// Used unless another file is given on the command line
+pub fn settings_path() -> PathBuf {
+    user_dirs::config_dir().join("settings.ron")
+}
+
+pub const USAGE: &str = "Usage: spacevagrant [options]
+Options override the settings file for this run
+
+    --settings <path>     Settings file to use
+    --width <pixels>      Window width
+    --height <pixels>     Window height
+    --windowed            Run in a window
+    --borderless          Run in a borderless window covering the screen
+    --fullscreen          Run in exclusive fullscreen
+    --monitor <name>      Monitor to go fullscreen on
+    --refresh-rate <hz>   Refresh rate in exclusive fullscreen
+    --vsync <mode>        on, mailbox or off
+    --frame-limit <fps>   Most frames a second to draw, or off
+    --adapter <gpu>       high-performance or low-power
+    --backend <api>       auto, vulkan, metal, dx12, dx11 or gl
+    --msaa <samples>      1, 2, 4 or 8
+    --ui-scale <factor>   Size of the ui, 1 is normal
+    --zoom <scale>        How much of the world fits on screen, bigger is closer
+    --log <filter>        Log filter, e.g. info or spacevagrant=debug
+    --help                Show this message";
+
+// Settings enums that are picked from a fixed set, by name in the menu and on the command line
+pub trait Choice: Copy + PartialEq + 'static {
+    const ALL: &'static [Self];
+
+    fn name(self) -> &'static str;
+
+    // Lowercase with dashes, as it's typed on the command line
+    fn arg_name(self) -> String {
+        self.name().to_lowercase().replace(' ', "-")
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WindowMode {
+    Windowed,
+    // A window with no decorations the size of the monitor, quick to switch in and out of
+    Borderless,
+    // Takes over the monitor at the chosen resolution
+    Fullscreen,
+}
+
+impl Choice for WindowMode {
+    const ALL: &'static [Self] = &[
+        WindowMode::Windowed,
+        WindowMode::Borderless,
+        WindowMode::Fullscreen,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            WindowMode::Windowed => "Windowed",
+            WindowMode::Borderless => "Borderless",
+            WindowMode::Fullscreen => "Fullscreen",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Vsync {
+    // Waits for the display, never tears
+    On,
+    // Doesn't wait, but only the newest frame is shown so it still doesn't tear
+    Mailbox,
+    // Frames are shown as soon as they're ready, tearing and all
+    Off,
+}
+
+impl Vsync {
+    // Present modes to try in order, when the first isn't supported
+    // Fifo is always supported, so it's the last resort for everything
+    pub fn present_modes(self) -> &'static [wgpu::PresentMode] {
+        match self {
+            Vsync::On => &[wgpu::PresentMode::Fifo],
+            // Falling back to immediate would bring tearing in, which is what mailbox is meant to avoid
+            Vsync::Mailbox => &[wgpu::PresentMode::Mailbox, wgpu::PresentMode::Fifo],
+            Vsync::Off => &[
+                wgpu::PresentMode::Immediate,
+                wgpu::PresentMode::Mailbox,
+                wgpu::PresentMode::Fifo,
+            ],
+        }
+    }
+}
+
+impl Choice for Vsync {
+    const ALL: &'static [Self] = &[Vsync::On, Vsync::Mailbox, Vsync::Off];
+
+    fn name(self) -> &'static str {
+        match self {
+            Vsync::On => "On",
+            Vsync::Mailbox => "Mailbox",
+            Vsync::Off => "Off",
+        }
+    }
+}
+
+// Which gpu to prefer when there's more than one, e.g. laptops with integrated and dedicated graphics
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AdapterPreference {
+    HighPerformance,
+    LowPower,
+}
+
+impl AdapterPreference {
+    pub fn power_preference(self) -> wgpu::PowerPreference {
+        match self {
+            AdapterPreference::HighPerformance => wgpu::PowerPreference::HighPerformance,
+            AdapterPreference::LowPower => wgpu::PowerPreference::LowPower,
+        }
+    }
+}
+
+impl Choice for AdapterPreference {
+    const ALL: &'static [Self] = &[
+        AdapterPreference::HighPerformance,
+        AdapterPreference::LowPower,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            AdapterPreference::HighPerformance => "High Performance",
+            AdapterPreference::LowPower => "Low Power",
+        }
+    }
+}
+
+// Graphics api to render with, auto picks the best one the platform has
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Backend {
+    Auto,
+    Vulkan,
+    Metal,
+    Dx12,
+    Dx11,
+    Gl,
+}
+
+impl Backend {
+    pub fn backend_bits(self) -> wgpu::BackendBit {
+        match self {
+            Backend::Auto => wgpu::BackendBit::PRIMARY,
+            Backend::Vulkan => wgpu::BackendBit::VULKAN,
+            Backend::Metal => wgpu::BackendBit::METAL,
+            Backend::Dx12 => wgpu::BackendBit::DX12,
+            Backend::Dx11 => wgpu::BackendBit::DX11,
+            Backend::Gl => wgpu::BackendBit::GL,
+        }
+    }
+}
+
+impl Choice for Backend {
+    const ALL: &'static [Self] = &[
+        Backend::Auto,
+        Backend::Vulkan,
+        Backend::Metal,
+        Backend::Dx12,
+        Backend::Dx11,
+        Backend::Gl,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Backend::Auto => "Auto",
+            Backend::Vulkan => "Vulkan",
+            Backend::Metal => "Metal",
+            Backend::Dx12 => "DX12",
+            Backend::Dx11 => "DX11",
+            Backend::Gl => "GL",
+        }
+    }
+}
+
+// Everything the player can bind a key to
+// Escape always opens the pause menu, so it isn't here
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Action {
+    TurnLeft,
+    TurnRight,
+    Forward,
+    Reverse,
+    StrafeLeft,
+    StrafeRight,
+    Afterburner,
+    FlightAssist,
+    KillRotation,
+    KillVelocity,
+    Mine,
+    Fire,
+    CycleTarget,
+    GalaxyMap,
+    SystemMap,
+    RadarMode,
+    RadarZoomIn,
+    RadarZoomOut,
+    PlotRoute,
+    Jump,
+    CancelJump,
+    Quicksave,
+    Quickload,
+}
+
+impl Action {
+    pub fn default_key(self) -> VirtualKeyCode {
+        use VirtualKeyCode::*;
+        match self {
+            Action::TurnLeft => A,
+            Action::TurnRight => D,
+            Action::Forward => W,
+            Action::Reverse => S,
+            Action::StrafeLeft => Q,
+            Action::StrafeRight => E,
+            Action::Afterburner => LShift,
+            Action::FlightAssist => Z,
+            Action::KillRotation => C,
+            Action::KillVelocity => X,
+            Action::Mine => Space,
+            Action::Fire => F,
+            Action::CycleTarget => T,
+            Action::GalaxyMap => M,
+            Action::SystemMap => N,
+            Action::RadarMode => R,
+            Action::RadarZoomIn => Equals,
+            Action::RadarZoomOut => Minus,
+            Action::PlotRoute => Tab,
+            Action::Jump => J,
+            Action::CancelJump => Back,
+            Action::Quicksave => F5,
+            Action::Quickload => F9,
+        }
+    }
+}
+
+impl Choice for Action {
+    const ALL: &'static [Self] = &[
+        Action::TurnLeft,
+        Action::TurnRight,
+        Action::Forward,
+        Action::Reverse,
+        Action::StrafeLeft,
+        Action::StrafeRight,
+        Action::Afterburner,
+        Action::FlightAssist,
+        Action::KillRotation,
+        Action::KillVelocity,
+        Action::Mine,
+        Action::Fire,
+        Action::CycleTarget,
+        Action::GalaxyMap,
+        Action::SystemMap,
+        Action::RadarMode,
+        Action::RadarZoomIn,
+        Action::RadarZoomOut,
+        Action::PlotRoute,
+        Action::Jump,
+        Action::CancelJump,
+        Action::Quicksave,
+        Action::Quickload,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Action::TurnLeft => "Turn Left",
+            Action::TurnRight => "Turn Right",
+            Action::Forward => "Forward",
+            Action::Reverse => "Reverse",
+            Action::StrafeLeft => "Strafe Left",
+            Action::StrafeRight => "Strafe Right",
+            Action::Afterburner => "Afterburner",
+            Action::FlightAssist => "Flight Assist",
+            Action::KillRotation => "Kill Rotation",
+            Action::KillVelocity => "Kill Velocity",
+            Action::Mine => "Mine",
+            Action::Fire => "Fire",
+            Action::CycleTarget => "Cycle Target",
+            Action::GalaxyMap => "Galaxy Map",
+            Action::SystemMap => "System Map",
+            Action::RadarMode => "Radar Mode",
+            Action::RadarZoomIn => "Radar Zoom In",
+            Action::RadarZoomOut => "Radar Zoom Out",
+            Action::PlotRoute => "Plot Route",
+            Action::Jump => "Jump",
+            Action::CancelJump => "Cancel Jump",
+            Action::Quicksave => "Quicksave",
+            Action::Quickload => "Quickload",
+        }
+    }
+}
+
+// One key per action
+// Actions missing from the file keep their default key, so new actions show up in old settings files
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(
+    from = "BTreeMap<Action, VirtualKeyCode>",
+    into = "BTreeMap<Action, VirtualKeyCode>"
+)]
+pub struct Keybinds {
+    keys: BTreeMap<Action, VirtualKeyCode>,
+}
+
+impl Default for Keybinds {
+    fn default() -> Self {
+        BTreeMap::new().into()
+    }
+}
+
+impl From<BTreeMap<Action, VirtualKeyCode>> for Keybinds {
+    fn from(mut keys: BTreeMap<Action, VirtualKeyCode>) -> Self {
+        for &action in Action::ALL {
+            keys.entry(action).or_insert_with(|| action.default_key());
+        }
+        Self { keys }
+    }
+}
+
+impl From<Keybinds> for BTreeMap<Action, VirtualKeyCode> {
+    fn from(keybinds: Keybinds) -> Self {
+        keybinds.keys
+    }
+}
+
+impl Keybinds {
+    pub fn key(&self, action: Action) -> VirtualKeyCode {
+        self.keys[&action]
+    }
+
+    // What a key does, if anything
+    pub fn action(&self, key: VirtualKeyCode) -> Option<Action> {
+        self.keys
+            .iter()
+            .find(|(_, &bound)| bound == key)
+            .map(|(&action, _)| action)
+    }
+
+    // A key can only do one thing, so whatever it was bound to before swaps over to this action's old key
+    pub fn bind(&mut self, action: Action, key: VirtualKeyCode) {
+        let old = self.key(action);
+        if let Some(other) = self.action(key) {
+            self.keys.insert(other, old);
+        }
+        self.keys.insert(action, key);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowSettings {
+    // Size of the window, or the resolution in fullscreen
+    pub width: u32,
+    pub height: u32,
+    pub mode: WindowMode,
+    // The kind of fullscreen alt+enter switches to from a window
+    pub fullscreen_mode: WindowMode,
+    // Monitor to go fullscreen on, by name since the order can change between runs
+    // When it's missing or not plugged in, the window stays on whichever monitor it's on
+    pub monitor: Option<String>,
+    // For exclusive fullscreen, the highest the monitor can do at the resolution if not set
+    pub refresh_rate: Option<u16>,
+}
+
+impl Default for WindowSettings {
+    fn default() -> Self {
+        Self {
+            width: 1366,
+            height: 768,
+            mode: WindowMode::Windowed,
+            fullscreen_mode: WindowMode::Borderless,
+            monitor: None,
+            refresh_rate: None,
+        }
+    }
+}
+
+impl WindowSettings {
+    pub fn size(&self) -> PhysicalSize<u32> {
+        PhysicalSize::new(self.width, self.height)
+    }
+
+    pub fn monitor(&self, window: &Window) -> Option<MonitorHandle> {
+        self.monitor
+            .as_ref()
+            .and_then(|name| {
+                window
+                    .available_monitors()
+                    .find(|monitor| monitor_name(monitor) == *name)
+            })
+            .or_else(|| window.current_monitor())
+            .or_else(|| window.primary_monitor())
+    }
+
+    // The monitor's video mode closest to the chosen resolution and refresh rate
+    pub fn video_mode(&self, monitor: &MonitorHandle) -> Option<VideoMode> {
+        monitor.video_modes().min_by_key(|mode| {
+            let size = mode.size();
+            let refresh_rate = mode.refresh_rate();
+            (
+                (size.width as i64 - self.width as i64).abs()
+                    + (size.height as i64 - self.height as i64).abs(),
+                self.refresh_rate
+                    .map_or(0, |rate| (refresh_rate as i32 - rate as i32).abs()),
+                Reverse(refresh_rate),
+                Reverse(mode.bit_depth()),
+            )
+        })
+    }
+
+    // Resize the window and switch it in or out of fullscreen
+    pub fn apply(&self, window: &Window) {
+        let monitor = self.monitor(window);
+        match self.mode {
+            WindowMode::Windowed => {
+                window.set_fullscreen(None);
+                window.set_inner_size(self.size());
+                // Move over to the chosen monitor, centred on it
+                if let Some(monitor) = monitor.filter(|_| self.monitor.is_some()) {
+                    if window.current_monitor().as_ref() != Some(&monitor) {
+                        let position = monitor.position();
+                        let size = monitor.size();
+                        window.set_outer_position(PhysicalPosition::new(
+                            position.x + (size.width as i32 - self.width as i32).max(0) / 2,
+                            position.y + (size.height as i32 - self.height as i32).max(0) / 2,
+                        ));
+                    }
+                }
+            }
+            WindowMode::Borderless => window.set_fullscreen(Some(Fullscreen::Borderless(monitor))),
+            WindowMode::Fullscreen => {
+                let video_mode = monitor
+                    .as_ref()
+                    .and_then(|monitor| self.video_mode(monitor));
+                window.set_fullscreen(Some(match video_mode {
+                    Some(video_mode) => Fullscreen::Exclusive(video_mode),
+                    // Not every platform can list video modes
+                    None => Fullscreen::Borderless(monitor),
+                }));
+            }
+        }
+    }
+
+    // Between a window and whichever kind of fullscreen was used last
+    pub fn toggle_fullscreen(&mut self) {
+        if self.mode == WindowMode::Windowed {
+            self.mode = self.fullscreen_mode;
+        } else {
+            self.fullscreen_mode = self.mode;
+            self.mode = WindowMode::Windowed;
+        }
+    }
+}
+
+// Monitors don't always have a name, but something has to be shown in the menu
+pub fn monitor_name(monitor: &MonitorHandle) -> String {
+    monitor
+        .name()
+        .unwrap_or_else(|| "Unknown Monitor".to_owned())
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphicsSettings {
+    pub vsync: Vsync,
+    // The adapter and backend are picked once at startup, so changing them needs a restart
+    pub adapter: AdapterPreference,
+    pub backend: Backend,
+    // Samples per pixel for anti-aliasing, 1 is off
+    pub msaa_samples: u32,
+    // Most frames a second to draw, uncapped if not set
+    pub frame_limit: Option<u32>,
+    // Drop right down to BACKGROUND_FRAME_LIMIT while the window isn't focused
+    pub throttle_in_background: bool,
+    // Scale from world units to the screen, before aspect correction
+    pub zoom: f32,
+    // Post-processing effects, all of which can be switched while running
+    pub bloom: bool,
+    pub tonemapping: bool,
+    pub vignette: bool,
+    pub chromatic_aberration: bool,
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        Self {
+            vsync: Vsync::On,
+            adapter: AdapterPreference::HighPerformance,
+            backend: Backend::Auto,
+            msaa_samples: 1,
+            frame_limit: None,
+            throttle_in_background: true,
+            zoom: 0.2,
+            bloom: true,
+            tonemapping: true,
+            vignette: true,
+            chromatic_aberration: false,
+        }
+    }
+}
+
+pub const MSAA_SAMPLES: [u32; 4] = [1, 2, 4, 8];
+pub const BACKGROUND_FRAME_LIMIT: u32 = 10;
+
+impl GraphicsSettings {
+    // The frame rate cap to use right now
+    pub fn frame_limit(&self, focused: bool) -> Option<u32> {
+        if focused || !self.throttle_in_background {
+            self.frame_limit
+        } else {
+            Some(self.frame_limit.map_or(BACKGROUND_FRAME_LIMIT, |limit| {
+                limit.min(BACKGROUND_FRAME_LIMIT)
+            }))
+        }
+    }
+}
+
+// Volumes from 0 to 1, music and effects are multiplied by the master volume
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub master: f32,
+    pub music: f32,
+    pub effects: f32,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            master: 1.0,
+            music: 0.7,
+            effects: 1.0,
+        }
+    }
+}
+
+// Everything the player can configure, kept in a file in the per-user config directory
+// Anything missing from the file is left at its default, so old files keep working as settings are added
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub window: WindowSettings,
+    pub graphics: GraphicsSettings,
+    pub ui_scale: f32,
+    pub audio: AudioSettings,
+    pub keybinds: Keybinds,
+    // Same syntax as RUST_LOG, which still takes priority when it's set
+    pub log_filter: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            window: WindowSettings::default(),
+            graphics: GraphicsSettings::default(),
+            ui_scale: 1.0,
+            audio: AudioSettings::default(),
+            keybinds: Keybinds::default(),
+            log_filter: "INFO".to_owned(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SettingsError {
+    Io(io::Error),
+    Format(ron::Error),
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SettingsError::Io(e) => write!(f, "{}", e),
+            SettingsError::Format(e) => write!(f, "invalid settings: {}", e),
+        }
+    }
+}
+
+pub fn read(path: &Path) -> Result<Settings, SettingsError> {
+    let contents = fs::read_to_string(path).map_err(SettingsError::Io)?;
+    let mut settings: Settings = ron::de::from_str(&contents).map_err(SettingsError::Format)?;
+    // A limit of zero frames a second can't be waited out, so it's taken to mean no limit
+    if settings.graphics.frame_limit == Some(0) {
+        settings.graphics.frame_limit = None;
+    }
+    // The same limits as on the command line, a scale or zoom of zero would divide by zero
+    settings.ui_scale = settings.ui_scale.max(MIN_UI_SCALE);
+    settings.graphics.zoom = settings.graphics.zoom.max(MIN_ZOOM);
+    Ok(settings)
+}
+
+pub fn write(path: &Path, settings: &Settings) -> Result<(), SettingsError> {
+    let contents = ron::ser::to_string_pretty(settings, ron::ser::PrettyConfig::default())
+        .map_err(SettingsError::Format)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(SettingsError::Io)?;
+    }
+    fs::write(path, contents).map_err(SettingsError::Io)
+}
+
+#[derive(Debug)]
+pub enum ArgsError {
+    UnknownOption(String),
+    MissingValue(String),
+    InvalidValue { option: String, value: String },
+}
+
+impl fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArgsError::UnknownOption(option) => write!(f, "unknown option {}", option),
+            ArgsError::MissingValue(option) => write!(f, "{} needs a value", option),
+            ArgsError::InvalidValue { option, value } => {
+                write!(f, "invalid value {} for {}", value, option)
+            }
+        }
+    }
+}
+
+// Command line options, anything set here wins over the settings file
+// Overrides only end up in the file if the settings menu saves it
+#[derive(Default)]
+pub struct Options {
+    pub settings_path: Option<PathBuf>,
+    pub help: bool,
+    width: Option<u32>,
+    height: Option<u32>,
+    window_mode: Option<WindowMode>,
+    monitor: Option<String>,
+    refresh_rate: Option<u16>,
+    vsync: Option<Vsync>,
+    // Some(None) turns the limit off
+    frame_limit: Option<Option<u32>>,
+    adapter: Option<AdapterPreference>,
+    backend: Option<Backend>,
+    msaa_samples: Option<u32>,
+    ui_scale: Option<f32>,
+    zoom: Option<f32>,
+    log_filter: Option<String>,
+}
+
+impl Options {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, ArgsError> {
+        let mut options = Options::default();
+        let mut args = args.into_iter();
+        while let Some(option) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| ArgsError::MissingValue(option.clone()))
+            };
+            match option.as_str() {
+                "--settings" => options.settings_path = Some(value()?.into()),
+                "--width" => options.width = Some(parse_number(&option, value()?, 1.0)?),
+                "--height" => options.height = Some(parse_number(&option, value()?, 1.0)?),
+                "--windowed" => options.window_mode = Some(WindowMode::Windowed),
+                "--borderless" => options.window_mode = Some(WindowMode::Borderless),
+                "--fullscreen" => options.window_mode = Some(WindowMode::Fullscreen),
+                "--monitor" => options.monitor = Some(value()?),
+                "--refresh-rate" => {
+                    options.refresh_rate = Some(parse_number(&option, value()?, 1.0)?)
+                }
+                "--vsync" => options.vsync = Some(parse_choice(&option, value()?)?),
+                "--frame-limit" => {
+                    let value = value()?;
+                    options.frame_limit = Some(if value.eq_ignore_ascii_case("off") {
+                        None
+                    } else {
+                        Some(parse_number(&option, value, 1.0)?)
+                    });
+                }
+                "--adapter" => options.adapter = Some(parse_choice(&option, value()?)?),
+                "--backend" => options.backend = Some(parse_choice(&option, value()?)?),
+                "--msaa" => {
+                    let value = value()?;
+                    match value.parse() {
+                        Ok(samples) if MSAA_SAMPLES.contains(&samples) => {
+                            options.msaa_samples = Some(samples)
+                        }
+                        _ => return Err(ArgsError::InvalidValue { option, value }),
+                    }
+                }
+                "--ui-scale" => {
+                    options.ui_scale = Some(parse_number(&option, value()?, MIN_UI_SCALE.into())?)
+                }
+                "--zoom" => options.zoom = Some(parse_number(&option, value()?, MIN_ZOOM.into())?),
+                "--log" => options.log_filter = Some(value()?),
+                "--help" | "-h" => options.help = true,
+                _ => return Err(ArgsError::UnknownOption(option)),
+            }
+        }
+        Ok(options)
+    }
+
+    pub fn apply(&self, settings: &mut Settings) {
+        let window = &mut settings.window;
+        let graphics = &mut settings.graphics;
+        window.width = self.width.unwrap_or(window.width);
+        window.height = self.height.unwrap_or(window.height);
+        window.mode = self.window_mode.unwrap_or(window.mode);
+        if let Some(monitor) = &self.monitor {
+            window.monitor = Some(monitor.clone());
+        }
+        window.refresh_rate = self.refresh_rate.or(window.refresh_rate);
+        graphics.vsync = self.vsync.unwrap_or(graphics.vsync);
+        graphics.frame_limit = self.frame_limit.unwrap_or(graphics.frame_limit);
+        graphics.adapter = self.adapter.unwrap_or(graphics.adapter);
+        graphics.backend = self.backend.unwrap_or(graphics.backend);
+        graphics.msaa_samples = self.msaa_samples.unwrap_or(graphics.msaa_samples);
+        graphics.zoom = self.zoom.unwrap_or(graphics.zoom);
+        settings.ui_scale = self.ui_scale.unwrap_or(settings.ui_scale);
+        if let Some(filter) = &self.log_filter {
+            settings.log_filter = filter.clone();
+        }
+    }
+}
+
+// Numbers below the minimum are rejected rather than clamped, they're almost certainly a typo
+fn parse_number<T: std::str::FromStr + Into<f64> + Copy>(
+    option: &str,
+    value: String,
+    min: f64,
+) -> Result<T, ArgsError> {
+    match value.parse::<T>() {
+        Ok(number) if number.into() >= min => Ok(number),
+        _ => Err(ArgsError::InvalidValue {
+            option: option.to_owned(),
+            value,
+        }),
+    }
+}
+
+fn parse_choice<T: Choice>(option: &str, value: String) -> Result<T, ArgsError> {
+    T::ALL
+        .iter()
+        .copied()
+        .find(|choice| choice.arg_name() == value.to_lowercase())
+        .ok_or_else(|| ArgsError::InvalidValue {
+            option: option.to_owned(),
+            value,
+        })
+}
@@ -0,0 +1,406 @@
+use crate::assets::{self, AssetError};
+use serde::Deserialize;
+use std::{collections::HashMap, fmt};
+
+pub const HULLS_PATH: &str = "ships/hulls.ron";
+pub const MODULES_PATH: &str = "ships/modules.ron";
+
+// The kinds of hardpoints a hull can have, every module fits exactly one kind
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SlotKind {
+    Engine,
+    Reactor,
+    ShieldGenerator,
+    Weapon,
+    Utility,
+    CargoExpansion,
+}
+
+// A ship frame as described in the hull data file
+#[derive(Deserialize, Clone, Debug)]
+pub struct HullDef {
+    pub id: String,
+    pub name: String,
+    pub mass: f32,
+    // Processing capacity available to fitted modules
+    pub cpu: f32,
+    pub cargo: u32,
+    pub fuel_capacity: f32,
+    // Damage the hull can take once the shields are down
+    pub structure: f32,
+    // How much heat the hull can soak up before overheating
+    pub heat_capacity: f32,
+    // Heat shed per second with no help from modules
+    pub heat_dissipation: f32,
+    pub slots: Vec<SlotKind>,
+    // Module ids fitted to a fresh ship of this hull, each one goes in the first free slot of the right kind
+    #[serde(default)]
+    pub default_modules: Vec<String>,
+}
+
+// A fittable piece of equipment as described in the module data file
+// Everything except the identifying fields is optional, and modules only list what they actually contribute
+#[derive(Deserialize, Clone, Debug)]
+pub struct ModuleDef {
+    pub id: String,
+    pub name: String,
+    pub slot: SlotKind,
+    #[serde(default)]
+    pub mass: f32,
+    #[serde(default)]
+    pub cpu: f32,
+    // Power used by the module
+    #[serde(default)]
+    pub power_draw: f32,
+    // Power generated by the module (reactors)
+    #[serde(default)]
+    pub power_output: f32,
+    #[serde(default)]
+    pub thrust: f32,
+    #[serde(default)]
+    pub turn_torque: f32,
+    #[serde(default)]
+    pub shield_capacity: f32,
+    // Shield points restored per second (shield generators)
+    #[serde(default)]
+    pub shield_recharge: f32,
+    #[serde(default)]
+    pub cargo: u32,
+    // Energy storage
+    #[serde(default)]
+    pub capacitor: f32,
+    #[serde(default)]
+    pub fuel_capacity: f32,
+    #[serde(default)]
+    pub heat_dissipation: f32,
+    // Fuel burnt per second at full thrust (engines)
+    #[serde(default)]
+    pub fuel_use: f32,
+    // Heat produced per second at full thrust (engines)
+    #[serde(default)]
+    pub engine_heat: f32,
+    // Energy and heat per shot (weapons)
+    #[serde(default)]
+    pub shot_energy: f32,
+    #[serde(default)]
+    pub shot_heat: f32,
+    // Damage done to whatever a shot hits (weapons)
+    #[serde(default)]
+    pub shot_damage: f32,
+    // Speed shots leave the ship at, on top of the ship's own velocity (weapons)
+    #[serde(default)]
+    pub projectile_speed: f32,
+    // Mining tools, higher tiers extract faster
+    #[serde(default)]
+    pub mining_tier: u32,
+    #[serde(default)]
+    pub mining_range: f32,
+}
+
+// Every hull and module known to the game, loaded from the data files
+pub struct ShipCatalog {
+    pub hulls: HashMap<String, HullDef>,
+    pub modules: HashMap<String, ModuleDef>,
+}
+
+impl ShipCatalog {
+    pub fn load() -> Result<Self, AssetError> {
+        let hulls: Vec<HullDef> = assets::load_ron(HULLS_PATH)?;
+        let modules: Vec<ModuleDef> = assets::load_ron(MODULES_PATH)?;
+        // Turn rate and acceleration are both divided by mass
+        if let Some(hull) = hulls.iter().find(|hull| hull.mass <= 0.0) {
+            return Err(AssetError::Invalid(
+                assets::asset_path(HULLS_PATH),
+                format!("hull {} needs a positive mass", hull.id),
+            ));
+        }
+
+        Ok(Self {
+            hulls: hulls.into_iter().map(|h| (h.id.clone(), h)).collect(),
+            modules: modules.into_iter().map(|m| (m.id.clone(), m)).collect(),
+        })
+    }
+
+    pub fn hull(&self, id: &str) -> Option<&HullDef> {
+        self.hulls.get(id)
+    }
+
+    pub fn module(&self, id: &str) -> Option<&ModuleDef> {
+        self.modules.get(id)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FitError {
+    UnknownHull(String),
+    UnknownModule(String),
+    NoSuchSlot(usize),
+    NoFreeSlot(SlotKind),
+    WrongSlotKind { expected: SlotKind, found: SlotKind },
+    NotEnoughPower { required: f32, available: f32 },
+    NotEnoughCpu { required: f32, available: f32 },
+    // The hold would be too small for the cargo already in it
+    CargoWontFit { required: u32, capacity: u32 },
+}
+
+impl fmt::Display for FitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FitError::UnknownHull(id) => write!(f, "unknown hull \"{}\"", id),
+            FitError::UnknownModule(id) => write!(f, "unknown module \"{}\"", id),
+            FitError::NoSuchSlot(slot) => write!(f, "hull has no slot {}", slot),
+            FitError::NoFreeSlot(kind) => write!(f, "hull has no free {:?} slot", kind),
+            FitError::WrongSlotKind { expected, found } => {
+                write!(f, "{:?} module doesn't fit a {:?} slot", found, expected)
+            }
+            FitError::NotEnoughPower {
+                required,
+                available,
+            } => write!(
+                f,
+                "loadout needs {} power but only {} is generated",
+                required, available
+            ),
+            FitError::NotEnoughCpu {
+                required,
+                available,
+            } => write!(
+                f,
+                "loadout needs {} cpu but the hull only has {}",
+                required, available
+            ),
+            FitError::CargoWontFit { required, capacity } => write!(
+                f,
+                "hold would only take {} units but {} are loaded",
+                capacity, required
+            ),
+        }
+    }
+}
+
+// Everything about how a ship performs, derived from its hull and fitted modules
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ShipStats {
+    pub mass: f32,
+    pub thrust: f32,
+    // How fast the ship can rotate, in radians per second
+    pub turn_rate: f32,
+    pub power_output: f32,
+    pub power_draw: f32,
+    pub cpu_used: f32,
+    pub cpu_limit: f32,
+    pub shield_capacity: f32,
+    pub shield_recharge: f32,
+    pub hull_strength: f32,
+    pub cargo_capacity: u32,
+    pub capacitor: f32,
+    pub fuel_capacity: f32,
+    pub heat_capacity: f32,
+    pub heat_dissipation: f32,
+    pub fuel_use: f32,
+    pub engine_heat: f32,
+    // Best mining tool fitted, zero if there isn't one
+    pub mining_tier: u32,
+    pub mining_range: f32,
+    // Fastest weapon fitted, zero if there isn't one
+    pub projectile_speed: f32,
+}
+
+impl ShipStats {
+    fn compute(hull: &HullDef, modules: &[Option<ModuleDef>]) -> Self {
+        let mut stats = Self {
+            mass: hull.mass,
+            cpu_limit: hull.cpu,
+            cargo_capacity: hull.cargo,
+            fuel_capacity: hull.fuel_capacity,
+            hull_strength: hull.structure,
+            heat_capacity: hull.heat_capacity,
+            heat_dissipation: hull.heat_dissipation,
+            ..Default::default()
+        };
+        let mut torque = 0.0;
+        for module in modules.iter().flatten() {
+            stats.mass += module.mass;
+            stats.thrust += module.thrust;
+            stats.power_output += module.power_output;
+            stats.power_draw += module.power_draw;
+            stats.cpu_used += module.cpu;
+            stats.shield_capacity += module.shield_capacity;
+            stats.shield_recharge += module.shield_recharge;
+            stats.cargo_capacity += module.cargo;
+            stats.capacitor += module.capacitor;
+            stats.fuel_capacity += module.fuel_capacity;
+            stats.heat_dissipation += module.heat_dissipation;
+            stats.fuel_use += module.fuel_use;
+            stats.engine_heat += module.engine_heat;
+            stats.mining_tier = stats.mining_tier.max(module.mining_tier);
+            stats.mining_range = stats.mining_range.max(module.mining_range);
+            stats.projectile_speed = stats.projectile_speed.max(module.projectile_speed);
+            torque += module.turn_torque;
+        }
+        stats.turn_rate = torque / stats.mass;
+        stats
+    }
+
+    // Acceleration at full thrust
+    pub fn acceleration(&self) -> f32 {
+        self.thrust / self.mass
+    }
+
+    fn validate(&self) -> Result<(), FitError> {
+        if self.power_draw > self.power_output {
+            return Err(FitError::NotEnoughPower {
+                required: self.power_draw,
+                available: self.power_output,
+            });
+        }
+        if self.cpu_used > self.cpu_limit {
+            return Err(FitError::NotEnoughCpu {
+                required: self.cpu_used,
+                available: self.cpu_limit,
+            });
+        }
+        Ok(())
+    }
+}
+
+// A hull with a set of modules fitted into its slots
+#[derive(Clone)]
+pub struct Ship {
+    hull: HullDef,
+    modules: Vec<Option<ModuleDef>>,
+    stats: ShipStats,
+}
+
+impl Ship {
+    // Build a ship of the given hull with its default modules fitted
+    pub fn new(catalog: &ShipCatalog, hull_id: &str) -> Result<Self, FitError> {
+        let hull = catalog
+            .hull(hull_id)
+            .ok_or_else(|| FitError::UnknownHull(hull_id.to_owned()))?
+            .clone();
+        let mut modules = vec![None; hull.slots.len()];
+        for id in &hull.default_modules {
+            let module = catalog
+                .module(id)
+                .ok_or_else(|| FitError::UnknownModule(id.clone()))?;
+            let slot = hull
+                .slots
+                .iter()
+                .zip(&modules)
+                .position(|(&kind, fitted)| kind == module.slot && fitted.is_none())
+                .ok_or(FitError::NoFreeSlot(module.slot))?;
+            modules[slot] = Some(module.clone());
+        }
+
+        let stats = ShipStats::compute(&hull, &modules);
+        stats.validate()?;
+        Ok(Self {
+            hull,
+            modules,
+            stats,
+        })
+    }
+
+    // Rebuild a ship from a hull id and the module id fitted in each slot, e.g. from a save
+    pub fn with_loadout(
+        catalog: &ShipCatalog,
+        hull_id: &str,
+        loadout: &[Option<String>],
+    ) -> Result<Self, FitError> {
+        let mut ship = Self::new(catalog, hull_id)?;
+        ship.modules = vec![None; ship.hull.slots.len()];
+        for (slot, id) in loadout.iter().enumerate() {
+            if let Some(id) = id {
+                let module = catalog
+                    .module(id)
+                    .ok_or_else(|| FitError::UnknownModule(id.clone()))?;
+                let &expected = ship
+                    .hull
+                    .slots
+                    .get(slot)
+                    .ok_or(FitError::NoSuchSlot(slot))?;
+                if module.slot != expected {
+                    return Err(FitError::WrongSlotKind {
+                        expected,
+                        found: module.slot,
+                    });
+                }
+                ship.modules[slot] = Some(module.clone());
+            }
+        }
+
+        ship.stats = ShipStats::compute(&ship.hull, &ship.modules);
+        ship.stats.validate()?;
+        Ok(ship)
+    }
+
+    // Id of the module fitted in each slot
+    pub fn loadout(&self) -> Vec<Option<String>> {
+        self.modules
+            .iter()
+            .map(|module| module.as_ref().map(|m| m.id.clone()))
+            .collect()
+    }
+
+    pub fn hull(&self) -> &HullDef {
+        &self.hull
+    }
+
+    pub fn stats(&self) -> &ShipStats {
+        &self.stats
+    }
+
+    pub fn modules(&self) -> &[Option<ModuleDef>] {
+        &self.modules
+    }
+
+    // Fit a module into a slot, replacing whatever was there
+    // The loadout is only changed if the result would still be valid
+    pub fn fit(
+        &mut self,
+        catalog: &ShipCatalog,
+        slot: usize,
+        module_id: &str,
+    ) -> Result<(), FitError> {
+        let module = catalog
+            .module(module_id)
+            .ok_or_else(|| FitError::UnknownModule(module_id.to_owned()))?;
+        let &expected = self
+            .hull
+            .slots
+            .get(slot)
+            .ok_or(FitError::NoSuchSlot(slot))?;
+        if module.slot != expected {
+            return Err(FitError::WrongSlotKind {
+                expected,
+                found: module.slot,
+            });
+        }
+
+        self.try_loadout(slot, Some(module.clone()))
+    }
+
+    // Empty a slot, returning what was in it
+    // This can fail too, pulling a reactor might leave the rest of the loadout without power
+    pub fn unfit(&mut self, slot: usize) -> Result<Option<ModuleDef>, FitError> {
+        let previous = self
+            .modules
+            .get(slot)
+            .ok_or(FitError::NoSuchSlot(slot))?
+            .clone();
+        self.try_loadout(slot, None)?;
+        Ok(previous)
+    }
+
+    fn try_loadout(&mut self, slot: usize, module: Option<ModuleDef>) -> Result<(), FitError> {
+        let mut modules = self.modules.clone();
+        modules[slot] = module;
+        let stats = ShipStats::compute(&self.hull, &modules);
+        stats.validate()?;
+
+        self.modules = modules;
+        self.stats = stats;
+        Ok(())
+    }
+}
@@ -0,0 +1,934 @@
+use crate::{
+    game::{Game, Input},
+    gfx::{GameRenderer, Scene},
+    menus::StationWindow,
+    save,
+    settings::{
+        self, monitor_name, Action, Choice, Options, Settings, SettingsError, WindowMode,
+        MSAA_SAMPLES,
+    },
+    ui::{Rect, Ui},
+};
+use log::{error, info};
+use std::{cmp::Reverse, io, path::Path};
+use winit::{
+    event::{MouseButton, VirtualKeyCode},
+    window::Window,
+};
+
+const MENU_WIDTH: f32 = 260.0;
+const SETTINGS_WIDTH: f32 = 380.0;
+const CONTROLS_WIDTH: f32 = 660.0;
+const TITLE: &str = "Space Vagrant";
+// Offered in the settings menu, along with whatever the settings file has if it's something else
+const FRAME_LIMITS: [Option<u32>; 6] = [None, Some(30), Some(60), Some(120), Some(144), Some(240)];
+const RESOLUTIONS: [(u32, u32); 6] = [
+    (1280, 720),
+    (1366, 768),
+    (1600, 900),
+    (1920, 1080),
+    (2560, 1440),
+    (3840, 2160),
+];
+
+// Everything a state can touch while it handles input or updates
+pub struct StateContext<'a> {
+    pub game: &'a mut Game,
+    pub renderer: &'a mut GameRenderer,
+    pub ui: &'a mut Ui,
+    pub screen_size: na::Vector2<f32>,
+    // Mouse position in pixels from the top left of the window
+    pub cursor: na::Vector2<f32>,
+    pub settings: &'a mut Settings,
+    // Where the settings menu saves to
+    pub settings_path: &'a Path,
+    pub window: &'a Window,
+}
+
+impl StateContext<'_> {
+    // Put the window settings into effect, including rebuilding the swapchain to fit
+    pub fn apply_window_settings(&mut self) {
+        self.settings.window.apply(self.window);
+        let size = self.window.inner_size();
+        self.renderer
+            .resize(na::Vector2::new(size.width, size.height));
+    }
+
+    // Swap in the settings file after it's been edited outside the game, with the command line still on top
+    // Anything that's only read at startup (gpu, backend, anti-aliasing, log filter) still needs a restart
+    pub fn reload_settings(&mut self, options: &Options) -> Result<(), SettingsError> {
+        let mut settings = settings::read(self.settings_path)?;
+        options.apply(&mut settings);
+        let window_changed = settings.window != self.settings.window;
+        *self.settings = settings;
+        self.ui.set_scale(self.settings.ui_scale);
+        self.renderer.apply_settings(&self.settings.graphics);
+        // Saving from the settings menu changes the file too, and putting the same window settings back in can make it flicker
+        if window_changed {
+            self.apply_window_settings();
+        }
+        Ok(())
+    }
+
+    // Only the window mode goes into the file, so nothing else given on the command line gets saved along with it
+    pub fn save_window_mode(&self) {
+        let mut settings = match settings::read(self.settings_path) {
+            Ok(settings) => settings,
+            Err(SettingsError::Io(e)) if e.kind() == io::ErrorKind::NotFound => Settings::default(),
+            Err(e) => {
+                error!("Failed to save window mode: {}", e);
+                return;
+            }
+        };
+        settings.window.mode = self.settings.window.mode;
+        settings.window.fullscreen_mode = self.settings.window.fullscreen_mode;
+        match settings::write(self.settings_path, &settings) {
+            Ok(()) => info!("Window mode saved"),
+            Err(e) => error!("Failed to save window mode: {}", e),
+        }
+    }
+
+    pub fn save_settings(&self) {
+        match settings::write(self.settings_path, self.settings) {
+            Ok(()) => info!("Settings saved"),
+            Err(e) => error!("Failed to save settings: {}", e),
+        }
+    }
+}
+
+// Input that got past the ui, handed to the state on top of the stack
+#[derive(Clone, Copy, Debug)]
+pub enum StateInput {
+    Key(VirtualKeyCode, bool),
+    MouseButton(MouseButton, bool),
+    // Delta is how far the mouse moved since the last event, in pixels
+    // Where it is now is kept in the context's cursor
+    CursorMoved { delta: na::Vector2<f32> },
+    // Lines scrolled, positive is away from the player
+    Scroll(f32),
+}
+
+// What to do with the stack after a state has handled something
+pub enum Transition {
+    None,
+    Push(Box<dyn State>),
+    Pop,
+    // Swap the top state for another one
+    Replace(Box<dyn State>),
+    // Throw away the whole stack and start again from one state
+    Reset(Box<dyn State>),
+    Quit,
+}
+
+// One screen of the game, e.g. the main menu or flying around
+// Only the state on top of the stack gets input and updates, but every state gets to say how the frame is rendered
+pub trait State {
+    fn input(&mut self, _ctx: &mut StateContext, _input: StateInput) -> Transition {
+        Transition::None
+    }
+
+    // Called once a frame, this is where the game is updated (if it should be) and the state's ui is built
+    fn update(&mut self, ctx: &mut StateContext, delta: f32) -> Transition;
+
+    // Set up what the renderer draws this frame, called for every state from the bottom of the stack up
+    fn render(&self, _renderer: &mut GameRenderer) {}
+
+    // Another state was pushed on top of this one, so it won't be getting input for a while
+    fn covered(&mut self, _ctx: &mut StateContext) {}
+}
+
+pub struct StateMachine {
+    stack: Vec<Box<dyn State>>,
+}
+
+impl StateMachine {
+    pub fn new(initial: Box<dyn State>) -> Self {
+        Self {
+            stack: vec![initial],
+        }
+    }
+
+    // Once the last state has gone the game should close
+    pub fn is_empty(&self) -> bool {
+        self.stack.is_empty()
+    }
+
+    pub fn input(&mut self, ctx: &mut StateContext, input: StateInput) {
+        if let Some(state) = self.stack.last_mut() {
+            let transition = state.input(ctx, input);
+            self.apply(ctx, transition);
+        }
+    }
+
+    pub fn update(&mut self, ctx: &mut StateContext, delta: f32) {
+        if let Some(state) = self.stack.last_mut() {
+            let transition = state.update(ctx, delta);
+            self.apply(ctx, transition);
+        }
+    }
+
+    pub fn render(&self, renderer: &mut GameRenderer) {
+        renderer.scene = Scene::System;
+        renderer.show_hud = false;
+        for state in &self.stack {
+            state.render(renderer);
+        }
+    }
+
+    fn apply(&mut self, ctx: &mut StateContext, transition: Transition) {
+        match transition {
+            Transition::None => (),
+            Transition::Push(state) => {
+                if let Some(top) = self.stack.last_mut() {
+                    top.covered(ctx);
+                }
+                self.stack.push(state);
+            }
+            Transition::Pop => {
+                self.stack.pop();
+            }
+            Transition::Replace(state) => {
+                self.stack.pop();
+                self.stack.push(state);
+            }
+            Transition::Reset(state) => {
+                self.stack.clear();
+                self.stack.push(state);
+            }
+            Transition::Quit => self.stack.clear(),
+        }
+    }
+}
+
+// A column of buttons in the middle of the screen, sized to fit a number of rows
+fn menu_rect(screen_size: na::Vector2<f32>, rows: usize) -> Rect {
+    centred_rect(screen_size, MENU_WIDTH, rows)
+}
+
+fn centred_rect(screen_size: na::Vector2<f32>, width: f32, rows: usize) -> Rect {
+    let height = 40.0 + rows as f32 * 32.0;
+    Rect::new(
+        (screen_size.x - width) / 2.0,
+        (screen_size.y - height) / 2.0,
+        width,
+        height,
+    )
+}
+
+// Restore the quicksave, going back to flying if it worked
+fn load_game(game: &mut Game, message: &mut Option<String>) -> Transition {
+    match game.load(&save::quicksave_path()) {
+        Ok(()) => {
+            info!("Game loaded");
+            Transition::Reset(Box::new(Playing))
+        }
+        Err(e) => {
+            error!("Failed to load game: {}", e);
+            *message = Some(format!("Failed to load: {}", e));
+            Transition::None
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct MainMenu {
+    message: Option<String>,
+}
+
+impl State for MainMenu {
+    fn update(&mut self, ctx: &mut StateContext, _delta: f32) -> Transition {
+        let StateContext { game, ui, .. } = ctx;
+        let mut transition = Transition::None;
+        let message = &mut self.message;
+        ui.panel(TITLE, menu_rect(ui.screen_size(), 5), |ui| {
+            if ui.button("New Game") {
+                **game = Game::new();
+                transition = Transition::Reset(Box::new(Playing));
+            }
+            if ui.button("Load Game") {
+                transition = load_game(game, message);
+            }
+            if ui.button("Settings") {
+                transition = Transition::Push(Box::new(SettingsMenu::default()));
+            }
+            if ui.button("Quit") {
+                transition = Transition::Quit;
+            }
+            if let Some(message) = message {
+                ui.label(message);
+            }
+        });
+        transition
+    }
+}
+
+// Flying around a system
+pub struct Playing;
+
+impl State for Playing {
+    fn input(&mut self, ctx: &mut StateContext, input: StateInput) -> Transition {
+        flight_input(ctx, input)
+    }
+
+    fn update(&mut self, ctx: &mut StateContext, delta: f32) -> Transition {
+        ctx.game.update(delta);
+        if ctx.game.player_destroyed() {
+            return Transition::Reset(Box::new(GameOver::default()));
+        }
+        // Swapped rather than pushed, so the controls carry on working through the change
+        if ctx.game.docked_station().is_some() {
+            return Transition::Replace(Box::new(Docked::default()));
+        }
+        Transition::None
+    }
+
+    fn render(&self, renderer: &mut GameRenderer) {
+        renderer.show_hud = true;
+    }
+
+    fn covered(&mut self, ctx: &mut StateContext) {
+        // Anything held down would stay held while another state has the keyboard
+        ctx.game.input = Input::default();
+    }
+}
+
+// Flying, but close enough to a station to trade with it
+// Leaving docking range goes back to just flying
+#[derive(Default)]
+pub struct Docked {
+    station: StationWindow,
+}
+
+impl State for Docked {
+    fn input(&mut self, ctx: &mut StateContext, input: StateInput) -> Transition {
+        flight_input(ctx, input)
+    }
+
+    fn update(&mut self, ctx: &mut StateContext, delta: f32) -> Transition {
+        ctx.game.update(delta);
+        if ctx.game.player_destroyed() {
+            return Transition::Reset(Box::new(GameOver::default()));
+        }
+        if ctx.game.docked_station().is_none() {
+            return Transition::Replace(Box::new(Playing));
+        }
+        let screen_size = ctx.ui.screen_size();
+        self.station.show(ctx.ui, ctx.game, screen_size);
+        Transition::None
+    }
+
+    fn render(&self, renderer: &mut GameRenderer) {
+        renderer.show_hud = true;
+    }
+
+    fn covered(&mut self, ctx: &mut StateContext) {
+        ctx.game.input = Input::default();
+    }
+}
+
+// Controls for whenever the player is at the helm
+fn flight_input(ctx: &mut StateContext, input: StateInput) -> Transition {
+    let game = &mut *ctx.game;
+    match input {
+        // Escape isn't bindable, so there's always a way to the menus
+        StateInput::Key(VirtualKeyCode::Escape, true) => {
+            return Transition::Push(Box::new(Paused::default()))
+        }
+        StateInput::Key(keycode, pressed) => {
+            // TODO: scancode instead of virtual keycode
+            let action = match ctx.settings.keybinds.action(keycode) {
+                Some(action) => action,
+                None => return Transition::None,
+            };
+            match action {
+                Action::TurnLeft => game.input.move_l = pressed,
+                Action::TurnRight => game.input.move_r = pressed,
+                Action::Reverse => game.input.move_b = pressed,
+                Action::Forward => game.input.move_f = pressed,
+                Action::StrafeLeft => game.input.strafe_l = pressed,
+                Action::StrafeRight => game.input.strafe_r = pressed,
+                Action::Afterburner => game.input.afterburner = pressed,
+                Action::FlightAssist => game.input.flight_assist = pressed,
+                Action::KillRotation => game.input.kill_rotation = pressed,
+                Action::KillVelocity => game.input.kill_velocity = pressed,
+                Action::Mine => game.input.mine = pressed,
+                Action::Fire => game.input.fire = pressed,
+                Action::CycleTarget if pressed => game.cycle_target(),
+                // Maps and radar
+                Action::GalaxyMap if pressed => {
+                    return Transition::Push(Box::new(Map::new(Scene::GalaxyMap)))
+                }
+                Action::SystemMap if pressed => {
+                    let close_key = ctx.settings.keybinds.key(Action::SystemMap);
+                    ctx.renderer
+                        .system_map
+                        .open(game.player().position, close_key);
+                    return Transition::Push(Box::new(Map::new(Scene::SystemMap)));
+                }
+                Action::RadarMode if pressed => ctx.renderer.radar.toggle_mode(),
+                Action::RadarZoomIn if pressed => ctx.renderer.radar.zoom_in(),
+                Action::RadarZoomOut if pressed => ctx.renderer.radar.zoom_out(),
+                // Quicksave and quickload
+                Action::Quicksave if pressed => match game.save(&save::quicksave_path()) {
+                    Ok(()) => info!("Game saved"),
+                    Err(e) => error!("Failed to save game: {}", e),
+                },
+                Action::Quickload if pressed => return load_game(game, &mut None),
+                _ => route_input(game, action, pressed),
+            }
+        }
+        // Clicks on the ui don't reach the game, anywhere else they fire the mining laser
+        StateInput::MouseButton(MouseButton::Left, pressed)
+            if !pressed || !ctx.ui.wants_mouse() =>
+        {
+            game.input.mine = pressed
+        }
+        StateInput::Scroll(lines) if !ctx.ui.wants_mouse() => {
+            if lines > 0.0 {
+                ctx.renderer.radar.zoom_in();
+            } else if lines < 0.0 {
+                ctx.renderer.radar.zoom_out();
+            }
+        }
+        _ => (),
+    }
+    Transition::None
+}
+
+// Autopilot controls, which work from the maps as well as the helm
+fn route_input(game: &mut Game, action: Action, pressed: bool) {
+    match action {
+        Action::PlotRoute if pressed => {
+            // Cycle the autopilot through every other system
+            let count = game.galaxy.systems.len();
+            let mut destination = game.route().last().copied().unwrap_or(game.system());
+            destination = (destination + 1) % count;
+            if destination == game.system() {
+                destination = (destination + 1) % count;
+            }
+            match game.plot_route(destination) {
+                Ok(()) => info!(
+                    "Route plotted to {}, {} jumps",
+                    game.galaxy.system(destination).name,
+                    game.route().len()
+                ),
+                Err(e) => error!("Failed to plot route: {}", e),
+            }
+        }
+        Action::Jump if pressed => match game.engage_jump() {
+            Ok(()) => info!("Jump drive charging"),
+            Err(e) => error!("Can't jump: {}", e),
+        },
+        Action::CancelJump if pressed => game.cancel_jump(),
+        _ => (),
+    }
+}
+
+// The galaxy or system map, the game carries on underneath
+pub struct Map {
+    scene: Scene,
+    // The system map is dragged around with the right mouse button
+    panning: bool,
+}
+
+impl Map {
+    pub fn new(scene: Scene) -> Self {
+        Self {
+            scene,
+            panning: false,
+        }
+    }
+}
+
+impl State for Map {
+    fn input(&mut self, ctx: &mut StateContext, input: StateInput) -> Transition {
+        let system_map = self.scene == Scene::SystemMap;
+        match input {
+            StateInput::Key(VirtualKeyCode::Escape, true) => return Transition::Pop,
+            StateInput::Key(keycode, pressed) => match ctx.settings.keybinds.action(keycode) {
+                Some(Action::GalaxyMap) if pressed && system_map => {
+                    return Transition::Replace(Box::new(Map::new(Scene::GalaxyMap)))
+                }
+                Some(Action::SystemMap) if pressed && !system_map => {
+                    let close_key = ctx.settings.keybinds.key(Action::SystemMap);
+                    ctx.renderer
+                        .system_map
+                        .open(ctx.game.player().position, close_key);
+                    return Transition::Replace(Box::new(Map::new(Scene::SystemMap)));
+                }
+                Some(Action::GalaxyMap) | Some(Action::SystemMap) if pressed => {
+                    return Transition::Pop
+                }
+                Some(action) => route_input(ctx.game, action, pressed),
+                None => (),
+            },
+            StateInput::MouseButton(MouseButton::Right, pressed) => {
+                self.panning = pressed && system_map
+            }
+            StateInput::CursorMoved { delta } if self.panning => ctx.renderer.system_map.pan(delta),
+            StateInput::Scroll(lines) if system_map => {
+                ctx.renderer
+                    .system_map
+                    .zoom(lines, ctx.cursor, ctx.screen_size)
+            }
+            _ => (),
+        }
+        Transition::None
+    }
+
+    fn update(&mut self, ctx: &mut StateContext, delta: f32) -> Transition {
+        ctx.game.update(delta);
+        if ctx.game.player_destroyed() {
+            return Transition::Reset(Box::new(GameOver::default()));
+        }
+        Transition::None
+    }
+
+    fn render(&self, renderer: &mut GameRenderer) {
+        renderer.scene = self.scene;
+    }
+}
+
+// The game stops while the pause menu is open
+#[derive(Default)]
+pub struct Paused {
+    message: Option<String>,
+}
+
+impl State for Paused {
+    fn input(&mut self, _ctx: &mut StateContext, input: StateInput) -> Transition {
+        match input {
+            StateInput::Key(VirtualKeyCode::Escape, true) => Transition::Pop,
+            _ => Transition::None,
+        }
+    }
+
+    fn update(&mut self, ctx: &mut StateContext, _delta: f32) -> Transition {
+        let StateContext { game, ui, .. } = ctx;
+        let mut transition = Transition::None;
+        let message = &mut self.message;
+        ui.panel("Paused", menu_rect(ui.screen_size(), 7), |ui| {
+            if ui.button("Resume") {
+                transition = Transition::Pop;
+            }
+            if ui.button("Save Game") {
+                *message = Some(match game.save(&save::quicksave_path()) {
+                    Ok(()) => "Game saved".to_owned(),
+                    Err(e) => format!("Failed to save: {}", e),
+                });
+            }
+            if ui.button("Load Game") {
+                transition = load_game(game, message);
+            }
+            if ui.button("Settings") {
+                transition = Transition::Push(Box::new(SettingsMenu::default()));
+            }
+            if ui.button("Main Menu") {
+                transition = Transition::Reset(Box::new(MainMenu::default()));
+            }
+            if ui.button("Quit") {
+                transition = Transition::Quit;
+            }
+            if let Some(message) = message {
+                ui.label(message);
+            }
+        });
+        transition
+    }
+}
+
+// The player's ship was destroyed, everything stops until they load or start again
+#[derive(Default)]
+pub struct GameOver {
+    message: Option<String>,
+}
+
+impl State for GameOver {
+    fn update(&mut self, ctx: &mut StateContext, delta: f32) -> Transition {
+        // The wreck stays put, but the explosion plays out
+        ctx.game.particles.update(delta);
+
+        let StateContext { game, ui, .. } = ctx;
+        let mut transition = Transition::None;
+        let message = &mut self.message;
+        ui.panel("Ship Destroyed", menu_rect(ui.screen_size(), 4), |ui| {
+            if ui.button("Load Game") {
+                transition = load_game(game, message);
+            }
+            if ui.button("Main Menu") {
+                transition = Transition::Reset(Box::new(MainMenu::default()));
+            }
+            if ui.button("Quit") {
+                transition = Transition::Quit;
+            }
+            if let Some(message) = message {
+                ui.label(message);
+            }
+        });
+        transition
+    }
+}
+
+// Changes take effect straight away where they can, and are written to the settings file when the menu closes
+#[derive(Default)]
+pub struct SettingsMenu {
+    message: Option<String>,
+}
+
+impl SettingsMenu {
+    fn close(ctx: &mut StateContext) -> Transition {
+        ctx.save_settings();
+        Transition::Pop
+    }
+}
+
+impl State for SettingsMenu {
+    fn input(&mut self, ctx: &mut StateContext, input: StateInput) -> Transition {
+        match input {
+            StateInput::Key(VirtualKeyCode::Escape, true) => SettingsMenu::close(ctx),
+            _ => Transition::None,
+        }
+    }
+
+    fn update(&mut self, ctx: &mut StateContext, _delta: f32) -> Transition {
+        let mut transition = Transition::None;
+        let mut window_changed = false;
+        let mut graphics_changed = false;
+        let settings = &mut *ctx.settings;
+        let message = &mut self.message;
+        let mut ui_scale = settings.ui_scale * 100.0;
+        let window = ctx.window;
+        let monitor = settings.window.monitor(window);
+        // Exclusive fullscreen has a refresh rate to pick as well
+        let exclusive = settings.window.mode == WindowMode::Fullscreen;
+
+        let screen_size = ctx.ui.screen_size();
+        ctx.ui.panel(
+            "Settings",
+            centred_rect(screen_size, SETTINGS_WIDTH, if exclusive { 19 } else { 18 }),
+            |ui| {
+                let display = &mut settings.window;
+                window_changed |= choose(ui, "Display", &mut display.mode);
+
+                let monitors = window
+                    .available_monitors()
+                    .map(|monitor| monitor_name(&monitor))
+                    .collect::<Vec<_>>();
+                let current = monitor.as_ref().map(monitor_name);
+                let mut selected = monitors
+                    .iter()
+                    .position(|name| Some(name) == current.as_ref())
+                    .unwrap_or(0);
+                if choose_name(ui, "Monitor", &monitors, &mut selected) {
+                    display.monitor = Some(monitors[selected].clone());
+                    window_changed = true;
+                }
+
+                // Exclusive fullscreen can only use what the monitor supports, biggest first
+                let mut resolutions = match &monitor {
+                    Some(monitor) if exclusive => {
+                        let mut sizes = monitor
+                            .video_modes()
+                            .map(|mode| (mode.size().width, mode.size().height))
+                            .collect::<Vec<_>>();
+                        sizes.sort_by_key(|&(width, height)| Reverse(width * height));
+                        sizes.dedup();
+                        sizes
+                    }
+                    _ => RESOLUTIONS.to_vec(),
+                };
+                // Show what's actually in use, which in exclusive fullscreen is the closest video mode the monitor has
+                let video_mode = monitor
+                    .as_ref()
+                    .filter(|_| exclusive)
+                    .and_then(|monitor| display.video_mode(monitor));
+                let size = video_mode
+                    .as_ref()
+                    .map_or((display.width, display.height), |mode| {
+                        (mode.size().width, mode.size().height)
+                    });
+                if !resolutions.contains(&size) {
+                    resolutions.insert(0, size);
+                }
+                let names = resolutions
+                    .iter()
+                    .map(|(width, height)| format!("{}x{}", width, height))
+                    .collect::<Vec<_>>();
+                let mut selected = resolutions
+                    .iter()
+                    .position(|&resolution| resolution == size)
+                    .unwrap_or(0);
+                if choose_name(ui, "Resolution", &names, &mut selected) {
+                    let (width, height) = resolutions[selected];
+                    display.width = width;
+                    display.height = height;
+                    window_changed = true;
+                }
+
+                if let (Some(monitor), Some(video_mode)) = (&monitor, &video_mode) {
+                    let mut rates = monitor
+                        .video_modes()
+                        .filter(|mode| mode.size() == video_mode.size())
+                        .map(|mode| mode.refresh_rate())
+                        .collect::<Vec<_>>();
+                    rates.sort_by_key(|&rate| Reverse(rate));
+                    rates.dedup();
+                    let names = rates
+                        .iter()
+                        .map(|rate| format!("{} Hz", rate))
+                        .collect::<Vec<_>>();
+                    let mut selected = rates
+                        .iter()
+                        .position(|&rate| rate == video_mode.refresh_rate())
+                        .unwrap_or(0);
+                    if choose_name(ui, "Refresh Rate", &names, &mut selected) {
+                        display.refresh_rate = Some(rates[selected]);
+                        window_changed = true;
+                    }
+                }
+
+                let graphics = &mut settings.graphics;
+                graphics_changed |= choose(ui, "Vsync", &mut graphics.vsync);
+
+                // The limit only matters to the event loop, which reads it every frame
+                let mut limits = FRAME_LIMITS.to_vec();
+                if !limits.contains(&graphics.frame_limit) {
+                    limits.insert(1, graphics.frame_limit);
+                }
+                let names = limits
+                    .iter()
+                    .map(|limit| match limit {
+                        Some(limit) => format!("{} FPS", limit),
+                        None => "Off".to_owned(),
+                    })
+                    .collect::<Vec<_>>();
+                let mut selected = limits
+                    .iter()
+                    .position(|&limit| limit == graphics.frame_limit)
+                    .unwrap_or(0);
+                if choose_name(ui, "Frame Limit", &names, &mut selected) {
+                    graphics.frame_limit = limits[selected];
+                }
+                let mut throttle = if graphics.throttle_in_background {
+                    0
+                } else {
+                    1
+                };
+                if ui.choice("In Background", &["Throttle", "Full Speed"], &mut throttle) {
+                    graphics.throttle_in_background = throttle == 0;
+                }
+                ui.slider("UI Scale %", &mut ui_scale, 50.0, 200.0);
+
+                let audio = &mut settings.audio;
+                for (label, volume) in [
+                    ("Master Volume", &mut audio.master),
+                    ("Music Volume", &mut audio.music),
+                    ("Effects Volume", &mut audio.effects),
+                ]
+                .iter_mut()
+                {
+                    let mut percent = **volume * 100.0;
+                    if ui.slider(label, &mut percent, 0.0, 100.0) {
+                        **volume = percent / 100.0;
+                    }
+                }
+
+                choose(ui, "GPU", &mut graphics.adapter);
+                choose(ui, "Backend", &mut graphics.backend);
+                let mut samples = MSAA_SAMPLES
+                    .iter()
+                    .position(|&samples| samples == graphics.msaa_samples)
+                    .unwrap_or(0);
+                if ui.choice("Anti-aliasing", &["Off", "2x", "4x", "8x"], &mut samples) {
+                    graphics.msaa_samples = MSAA_SAMPLES[samples];
+                }
+                ui.label("GPU, backend and anti-aliasing apply after a restart");
+
+                ui.row(|ui| {
+                    if ui.button("Controls") {
+                        transition = Transition::Push(Box::new(ControlsMenu::default()));
+                    }
+                    if ui.button("Effects") {
+                        transition = Transition::Push(Box::new(EffectsMenu));
+                    }
+                });
+                ui.row(|ui| {
+                    if ui.button("Defaults") {
+                        // Keys are left alone, they have their own reset in the controls menu
+                        let keybinds = settings.keybinds.clone();
+                        *settings = Settings {
+                            keybinds,
+                            log_filter: settings.log_filter.clone(),
+                            ..Settings::default()
+                        };
+                        ui_scale = settings.ui_scale * 100.0;
+                        window_changed = true;
+                        graphics_changed = true;
+                        *message = Some("Settings reset".to_owned());
+                    }
+                    if ui.button("Back") {
+                        transition = Transition::Pop;
+                    }
+                });
+                if let Some(message) = message {
+                    ui.label(message);
+                }
+            },
+        );
+
+        // Dragging the slider lands anywhere, so it's kept to steps of 5%
+        ctx.settings.ui_scale = (ui_scale / 5.0).round() * 5.0 / 100.0;
+        ctx.ui.set_scale(ctx.settings.ui_scale);
+        if graphics_changed {
+            ctx.renderer.apply_settings(&ctx.settings.graphics);
+        }
+        if window_changed {
+            ctx.apply_window_settings();
+        }
+        match transition {
+            Transition::Pop => SettingsMenu::close(ctx),
+            transition => transition,
+        }
+    }
+}
+
+// Post-processing switches, which have a menu of their own so the settings still fit on small screens
+// They're saved along with everything else when the settings menu closes
+pub struct EffectsMenu;
+
+impl State for EffectsMenu {
+    fn input(&mut self, _ctx: &mut StateContext, input: StateInput) -> Transition {
+        match input {
+            StateInput::Key(VirtualKeyCode::Escape, true) => Transition::Pop,
+            _ => Transition::None,
+        }
+    }
+
+    fn update(&mut self, ctx: &mut StateContext, _delta: f32) -> Transition {
+        let mut transition = Transition::None;
+        let mut changed = false;
+        let graphics = &mut ctx.settings.graphics;
+
+        let screen_size = ctx.ui.screen_size();
+        ctx.ui.panel(
+            "Effects",
+            centred_rect(screen_size, SETTINGS_WIDTH, 5),
+            |ui| {
+                for (label, enabled) in [
+                    ("Bloom", &mut graphics.bloom),
+                    ("Tone Mapping", &mut graphics.tonemapping),
+                    ("Vignette", &mut graphics.vignette),
+                    ("Aberration", &mut graphics.chromatic_aberration),
+                ]
+                .iter_mut()
+                {
+                    changed |= toggle(ui, label, enabled);
+                }
+                if ui.button("Back") {
+                    transition = Transition::Pop;
+                }
+            },
+        );
+
+        if changed {
+            ctx.renderer.apply_settings(&ctx.settings.graphics);
+        }
+        transition
+    }
+}
+
+fn toggle(ui: &mut Ui, label: &str, enabled: &mut bool) -> bool {
+    let mut selected = if *enabled { 0 } else { 1 };
+    let changed = ui.choice(label, &["On", "Off"], &mut selected);
+    *enabled = selected == 0;
+    changed
+}
+
+fn choose_name(ui: &mut Ui, label: &str, names: &[String], selected: &mut usize) -> bool {
+    let names = names.iter().map(String::as_str).collect::<Vec<_>>();
+    ui.choice(label, &names, selected)
+}
+
+// Pick from one of the settings enums
+fn choose<T: Choice>(ui: &mut Ui, label: &str, value: &mut T) -> bool {
+    let names = T::ALL
+        .iter()
+        .map(|choice| choice.name())
+        .collect::<Vec<_>>();
+    let mut selected = T::ALL
+        .iter()
+        .position(|choice| choice == value)
+        .unwrap_or(0);
+    let changed = ui.choice(label, &names, &mut selected);
+    *value = T::ALL[selected];
+    changed
+}
+
+// Click an action, then press the key to bind to it
+// Arrow keys and enter move around the ui, so they can't be bound
+#[derive(Default)]
+pub struct ControlsMenu {
+    rebinding: Option<Action>,
+}
+
+impl State for ControlsMenu {
+    fn input(&mut self, ctx: &mut StateContext, input: StateInput) -> Transition {
+        match (input, self.rebinding) {
+            (StateInput::Key(VirtualKeyCode::Escape, true), Some(_)) => self.rebinding = None,
+            (StateInput::Key(VirtualKeyCode::Escape, true), None) => return Transition::Pop,
+            (StateInput::Key(keycode, true), Some(action)) => {
+                ctx.settings.keybinds.bind(action, keycode);
+                self.rebinding = None;
+            }
+            _ => (),
+        }
+        Transition::None
+    }
+
+    fn update(&mut self, ctx: &mut StateContext, _delta: f32) -> Transition {
+        let mut transition = Transition::None;
+        let keybinds = &mut ctx.settings.keybinds;
+        let rebinding = &mut self.rebinding;
+        // Two columns of actions
+        let rows = Action::ALL.chunks(2).len();
+
+        let screen_size = ctx.ui.screen_size();
+        ctx.ui.panel(
+            "Controls",
+            centred_rect(screen_size, CONTROLS_WIDTH, rows + 2),
+            |ui| {
+                for pair in Action::ALL.chunks(2) {
+                    ui.row(|ui| {
+                        for &action in pair {
+                            let key = if *rebinding == Some(action) {
+                                "...".to_owned()
+                            } else {
+                                format!("{:?}", keybinds.key(action))
+                            };
+                            // The font is monospaced, so padding lines the columns up
+                            if ui.button(&format!("{:<16}{:>10}", action.name(), key)) {
+                                *rebinding = match *rebinding {
+                                    Some(current) if current == action => None,
+                                    _ => Some(action),
+                                };
+                            }
+                        }
+                    });
+                }
+                ui.row(|ui| {
+                    if ui.button("Defaults") {
+                        *keybinds = Default::default();
+                        *rebinding = None;
+                    }
+                    if ui.button("Back") {
+                        transition = Transition::Pop;
+                    }
+                });
+                if let Some(action) = rebinding {
+                    ui.label(&format!("Press a key for {}", action.name()));
+                }
+            },
+        );
+        transition
+    }
+}
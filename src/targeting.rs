@@ -0,0 +1,63 @@
+use crate::economy::StationId;
+
+// Something in the system the player can lock on to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Target {
+    Station(StationId),
+    // Index into the system's asteroids
+    Asteroid(usize),
+    // Id of one of the outlaws in the system
+    Outlaw(u32),
+}
+
+// A snapshot of the current target for the hud
+pub struct TargetInfo {
+    pub name: String,
+    pub position: na::Vector2<f32>,
+    pub velocity: na::Vector2<f32>,
+    // Roughly how big it is, for sizing the reticle
+    pub radius: f32,
+}
+
+// Where to aim so a shot fired now meets the target, assuming neither of them changes course
+// Shots inherit the velocity of the ship that fired them, so only the relative motion matters
+// None if the shot can never catch the target
+pub fn lead_position(
+    shooter_position: na::Vector2<f32>,
+    shooter_velocity: na::Vector2<f32>,
+    target_position: na::Vector2<f32>,
+    target_velocity: na::Vector2<f32>,
+    projectile_speed: f32,
+) -> Option<na::Vector2<f32>> {
+    if projectile_speed <= 0.0 {
+        return None;
+    }
+    let offset = target_position - shooter_position;
+    let velocity = target_velocity - shooter_velocity;
+
+    // Solve |offset + velocity * t| = projectile_speed * t for the earliest time in the future
+    let a = velocity.norm_squared() - projectile_speed * projectile_speed;
+    let b = 2.0 * offset.dot(&velocity);
+    let c = offset.norm_squared();
+    let time = if a.abs() < f32::EPSILON {
+        // The target moves as fast as the shot, so it can only be caught head on
+        if b >= 0.0 {
+            return None;
+        }
+        -c / b
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        let (t1, t2) = ((-b - root) / (2.0 * a), (-b + root) / (2.0 * a));
+        match (t1 >= 0.0, t2 >= 0.0) {
+            (true, true) => t1.min(t2),
+            (true, false) => t1,
+            (false, true) => t2,
+            (false, false) => return None,
+        }
+    };
+    Some(target_position + velocity * time)
+}
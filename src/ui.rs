@@ -0,0 +1,682 @@
+use crate::gpu::font::{Align, Font, TextStyle};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+// Everything is measured in pixels from the top left corner of the window, with y pointing down
+const TEXT_SIZE: f32 = 18.0;
+const ROW_HEIGHT: f32 = 26.0;
+const SPACING: f32 = 6.0;
+const PADDING: f32 = 10.0;
+const TITLE_HEIGHT: f32 = 30.0;
+const SLIDER_WIDTH: f32 = 160.0;
+const TEXT_FIELD_WIDTH: f32 = 160.0;
+const CHOICE_WIDTH: f32 = 160.0;
+const FOCUS_OUTLINE: f32 = 2.0;
+
+const PANEL_COLOR: [f32; 4] = [0.05, 0.07, 0.1, 0.85];
+const TITLE_COLOR: [f32; 4] = [0.1, 0.2, 0.3, 0.95];
+const WIDGET_COLOR: [f32; 4] = [0.15, 0.2, 0.28, 1.0];
+const HOVER_COLOR: [f32; 4] = [0.22, 0.3, 0.42, 1.0];
+const ACTIVE_COLOR: [f32; 4] = [0.3, 0.45, 0.6, 1.0];
+const SELECTED_COLOR: [f32; 4] = [0.2, 0.5, 0.7, 1.0];
+const FOCUS_COLOR: [f32; 4] = [1.0, 0.8, 0.3, 1.0];
+const FILL_COLOR: [f32; 4] = [0.3, 0.6, 0.85, 1.0];
+const TEXT_COLOR: [f32; 4] = [0.9, 0.93, 1.0, 1.0];
+
+pub type WidgetId = u64;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    // Top left corner
+    pub min: na::Vector2<f32>,
+    pub size: na::Vector2<f32>,
+}
+
+impl Rect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            min: na::Vector2::new(x, y),
+            size: na::Vector2::new(width, height),
+        }
+    }
+
+    pub fn max(&self) -> na::Vector2<f32> {
+        self.min + self.size
+    }
+
+    pub fn centre(&self) -> na::Vector2<f32> {
+        self.min + self.size / 2.0
+    }
+
+    pub fn contains(&self, point: na::Vector2<f32>) -> bool {
+        let max = self.max();
+        point.x >= self.min.x && point.y >= self.min.y && point.x < max.x && point.y < max.y
+    }
+
+    pub fn shrink(&self, amount: f32) -> Self {
+        Self {
+            min: self.min.add_scalar(amount),
+            size: self.size.map(|size| (size - amount * 2.0).max(0.0)),
+        }
+    }
+}
+
+// What the renderer has to draw, in the order it has to draw it
+pub enum DrawCommand {
+    Rect {
+        rect: Rect,
+        color: [f32; 4],
+    },
+    // Position is the top left of the first line
+    Text {
+        text: String,
+        position: na::Vector2<f32>,
+        style: TextStyle,
+    },
+    Line {
+        start: na::Vector2<f32>,
+        end: na::Vector2<f32>,
+        width: f32,
+        color: [f32; 4],
+    },
+}
+
+impl DrawCommand {
+    // Grow everything out from the top left corner
+    fn scale(&mut self, factor: f32) {
+        match self {
+            DrawCommand::Rect { rect, .. } => {
+                rect.min *= factor;
+                rect.size *= factor;
+            }
+            DrawCommand::Text {
+                position, style, ..
+            } => {
+                *position *= factor;
+                style.size *= factor;
+                style.wrap_width = style.wrap_width.map(|width| width * factor);
+            }
+            DrawCommand::Line {
+                start, end, width, ..
+            } => {
+                *start *= factor;
+                *end *= factor;
+                *width *= factor;
+            }
+        }
+    }
+}
+
+// Directions and actions that move keyboard or gamepad focus between widgets
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Nav {
+    Up,
+    Down,
+    Left,
+    Right,
+    Activate,
+    Back,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Direction {
+    Vertical,
+    Horizontal,
+}
+
+// Space being filled with widgets, one after another
+struct Layout {
+    rect: Rect,
+    cursor: na::Vector2<f32>,
+    direction: Direction,
+    id: WidgetId,
+}
+
+impl Layout {
+    fn new(rect: Rect, direction: Direction, id: WidgetId) -> Self {
+        Self {
+            rect,
+            cursor: rect.min,
+            direction,
+            id,
+        }
+    }
+
+    // Take the space for the next widget
+    // Widgets in a column fill its width, widgets in a row are as wide as they ask to be
+    fn allocate(&mut self, width: f32, height: f32) -> Rect {
+        match self.direction {
+            Direction::Vertical => {
+                let rect = Rect::new(self.cursor.x, self.cursor.y, self.rect.size.x, height);
+                self.cursor.y += height + SPACING;
+                rect
+            }
+            Direction::Horizontal => {
+                let rect = Rect::new(self.cursor.x, self.cursor.y, width, self.rect.size.y);
+                self.cursor.x += width + SPACING;
+                rect
+            }
+        }
+    }
+}
+
+// Input collected from the window between frames
+#[derive(Default)]
+struct Input {
+    mouse: Option<na::Vector2<f32>>,
+    mouse_down: bool,
+    mouse_pressed: bool,
+    mouse_released: bool,
+    characters: Vec<char>,
+    nav: Vec<Nav>,
+}
+
+// Immediate mode ui, widgets are declared again every frame and respond to input as they are
+// Only the widget ids that need to persist between frames (hovered, held, focused) are stored
+pub struct Ui {
+    font: Arc<Font>,
+    input: Input,
+    commands: Vec<DrawCommand>,
+    layouts: Vec<Layout>,
+    // Widget the mouse button went down on, it stays active until the button is released
+    active: Option<WidgetId>,
+    // Widget that keyboard and gamepad input goes to
+    focus: Option<WidgetId>,
+    // Widget to activate this frame because of a nav input
+    activate: Option<WidgetId>,
+    // Every focusable widget in the order they were declared, this frame and last frame
+    focusable: Vec<WidgetId>,
+    last_focusable: Vec<WidgetId>,
+    // Whether the mouse is over any panel
+    hovered: bool,
+    // Whether a text field has focus
+    typing: bool,
+    // Widgets are laid out at normal size in a smaller (or bigger) screen, then everything's scaled up to fill the window
+    scale: f32,
+    screen_size: na::Vector2<f32>,
+}
+
+impl Ui {
+    pub fn new(font: Arc<Font>) -> Self {
+        Self {
+            font,
+            input: Input::default(),
+            commands: Vec::new(),
+            layouts: Vec::new(),
+            active: None,
+            focus: None,
+            activate: None,
+            focusable: Vec::new(),
+            last_focusable: Vec::new(),
+            hovered: false,
+            typing: false,
+            scale: 1.0,
+            screen_size: na::Vector2::zeros(),
+        }
+    }
+
+    pub fn set_scale(&mut self, scale: f32) {
+        self.input.mouse = self.input.mouse.map(|mouse| mouse * self.scale / scale);
+        self.scale = scale;
+    }
+
+    // Size of the screen in ui units, which is what panels should be placed in
+    pub fn screen_size(&self) -> na::Vector2<f32> {
+        self.screen_size
+    }
+
+    // Input, routed here from the window's event loop
+
+    // Input positions are in pixels, like the window
+    pub fn cursor_moved(&mut self, position: na::Vector2<f32>) {
+        self.input.mouse = Some(position / self.scale);
+    }
+
+    pub fn cursor_left(&mut self) {
+        self.input.mouse = None;
+    }
+
+    pub fn mouse_button(&mut self, pressed: bool) {
+        if pressed && !self.input.mouse_down {
+            self.input.mouse_pressed = true;
+        } else if !pressed && self.input.mouse_down {
+            self.input.mouse_released = true;
+        }
+        self.input.mouse_down = pressed;
+    }
+
+    pub fn character(&mut self, c: char) {
+        self.input.characters.push(c);
+    }
+
+    pub fn navigate(&mut self, nav: Nav) {
+        self.input.nav.push(nav);
+    }
+
+    // Whether the mouse is over the ui, clicks there shouldn't also go to the game
+    pub fn wants_mouse(&self) -> bool {
+        self.hovered || self.active.is_some()
+    }
+
+    // Whether keys should be going to the ui rather than the game, i.e. while typing
+    pub fn wants_keyboard(&self) -> bool {
+        self.typing
+    }
+
+    pub fn has_focus(&self) -> bool {
+        self.focus.is_some()
+    }
+
+    pub fn draw_commands(&self) -> &[DrawCommand] {
+        &self.commands
+    }
+
+    // Start declaring this frame's widgets
+    pub fn begin_frame(&mut self, screen_size: na::Vector2<f32>) {
+        self.screen_size = screen_size / self.scale;
+        self.commands.clear();
+        self.layouts.clear();
+        self.layouts.push(Layout::new(
+            Rect::new(0.0, 0.0, self.screen_size.x, self.screen_size.y),
+            Direction::Vertical,
+            0,
+        ));
+        self.last_focusable = std::mem::take(&mut self.focusable);
+        self.hovered = false;
+        self.typing = false;
+
+        // Move focus using the order widgets were declared in last frame
+        self.activate = None;
+        for nav in std::mem::take(&mut self.input.nav) {
+            let count = self.last_focusable.len();
+            let current = self
+                .focus
+                .and_then(|focus| self.last_focusable.iter().position(|&id| id == focus));
+            match nav {
+                Nav::Up | Nav::Down if count > 0 => {
+                    let next = match (current, nav) {
+                        (None, Nav::Up) => count - 1,
+                        (None, _) => 0,
+                        (Some(i), Nav::Up) => (i + count - 1) % count,
+                        (Some(i), _) => (i + 1) % count,
+                    };
+                    self.focus = Some(self.last_focusable[next]);
+                }
+                Nav::Activate => self.activate = self.focus,
+                Nav::Back => self.focus = None,
+                // Left and right are handled by whichever widget has focus
+                _ => self.input.nav.push(nav),
+            }
+        }
+    }
+
+    // Finish the frame, the draw commands are ready after this
+    pub fn end_frame(&mut self) {
+        if self.scale != 1.0 {
+            for command in &mut self.commands {
+                command.scale(self.scale);
+            }
+        }
+        // Clicking on nothing drops focus
+        if self.input.mouse_pressed && self.active.is_none() {
+            self.focus = None;
+        }
+        if self.input.mouse_released {
+            self.active = None;
+        }
+        // Focused widget might not exist any more
+        if let Some(focus) = self.focus {
+            if !self.focusable.contains(&focus) {
+                self.focus = None;
+            }
+        }
+        let mouse = self.input.mouse;
+        let mouse_down = self.input.mouse_down;
+        self.input = Input {
+            mouse,
+            mouse_down,
+            ..Input::default()
+        };
+    }
+
+    // A window with a title bar, widgets declared in the closure are stacked inside it
+    pub fn panel(&mut self, title: &str, rect: Rect, contents: impl FnOnce(&mut Self)) {
+        let id = self.id(title);
+        if self.mouse_over(rect) {
+            self.hovered = true;
+        }
+        self.rect(rect, PANEL_COLOR);
+        let title_rect = Rect::new(rect.min.x, rect.min.y, rect.size.x, TITLE_HEIGHT);
+        self.rect(title_rect, TITLE_COLOR);
+        self.text(
+            title,
+            title_rect.shrink(PADDING / 2.0),
+            Align::Left,
+            TEXT_COLOR,
+        );
+
+        let inner = Rect::new(
+            rect.min.x,
+            rect.min.y + TITLE_HEIGHT,
+            rect.size.x,
+            rect.size.y - TITLE_HEIGHT,
+        )
+        .shrink(PADDING);
+        self.layouts
+            .push(Layout::new(inner, Direction::Vertical, id));
+        contents(self);
+        self.layouts.pop();
+    }
+
+    // Lay out the widgets declared in the closure side by side
+    pub fn row(&mut self, contents: impl FnOnce(&mut Self)) {
+        let rect = self.allocate(0.0, ROW_HEIGHT);
+        let id = self.layout().id;
+        self.layouts
+            .push(Layout::new(rect, Direction::Horizontal, id));
+        contents(self);
+        self.layouts.pop();
+    }
+
+    pub fn label(&mut self, text: &str) {
+        let width = self.text_width(text);
+        let rect = self.allocate(width, ROW_HEIGHT);
+        self.text(text, rect, Align::Left, TEXT_COLOR);
+    }
+
+    // Text that wraps onto as many lines as it needs to fit the width of the layout
+    pub fn paragraph(&mut self, text: &str) {
+        let margin = (ROW_HEIGHT - TEXT_SIZE) / 2.0;
+        let style = TextStyle::new(TEXT_SIZE, TEXT_COLOR).wrap(self.layout().rect.size.x);
+        let height = self.font.measure(text, &style).y + margin * 2.0;
+        let rect = self.allocate(0.0, height.max(ROW_HEIGHT));
+        self.commands.push(DrawCommand::Text {
+            text: text.to_owned(),
+            position: rect.min + na::Vector2::new(0.0, margin),
+            style,
+        });
+    }
+
+    // Returns true the frame it's clicked (or activated with the keyboard or gamepad)
+    pub fn button(&mut self, label: &str) -> bool {
+        let id = self.id(label);
+        let width = self.text_width(label) + PADDING * 2.0;
+        let rect = self.allocate(width, ROW_HEIGHT);
+        let (hovered, clicked) = self.interact(id, rect);
+
+        let color = match (self.active == Some(id), hovered) {
+            (true, _) => ACTIVE_COLOR,
+            (false, true) => HOVER_COLOR,
+            (false, false) => WIDGET_COLOR,
+        };
+        self.rect(rect, color);
+        self.focus_outline(id, rect);
+        self.text(label, rect, Align::Center, TEXT_COLOR);
+        clicked
+    }
+
+    // Drag, or use left and right while focused, to pick a value in a range
+    // Returns true when the value changes
+    pub fn slider(&mut self, label: &str, value: &mut f32, min: f32, max: f32) -> bool {
+        let id = self.id(label);
+        let label_width = self.text_width(label);
+        let rect = self.allocate(label_width + SPACING + SLIDER_WIDTH, ROW_HEIGHT);
+        self.interact(id, rect);
+        let track = Rect::new(
+            rect.max().x - SLIDER_WIDTH,
+            rect.min.y,
+            SLIDER_WIDTH,
+            rect.size.y,
+        );
+
+        let old = *value;
+        if self.active == Some(id) {
+            if let Some(mouse) = self.input.mouse {
+                let t = ((mouse.x - track.min.x) / track.size.x).clamp(0.0, 1.0);
+                *value = min + (max - min) * t;
+            }
+        }
+        if self.focus == Some(id) {
+            // Keyboard and gamepad step through the range in tenths
+            let step = (max - min) / 10.0;
+            for nav in &self.input.nav {
+                match nav {
+                    Nav::Left => *value -= step,
+                    Nav::Right => *value += step,
+                    _ => (),
+                }
+            }
+            *value = value.clamp(min, max);
+        }
+
+        self.text(label, rect, Align::Left, TEXT_COLOR);
+        self.rect(track, WIDGET_COLOR);
+        let t = if max > min {
+            (*value - min) / (max - min)
+        } else {
+            0.0
+        };
+        self.rect(
+            Rect::new(track.min.x, track.min.y, track.size.x * t, track.size.y),
+            FILL_COLOR,
+        );
+        self.focus_outline(id, track);
+        self.text(&format!("{:.0}", value), track, Align::Center, TEXT_COLOR);
+        *value != old
+    }
+
+    // One of a few options, clicking or pressing right moves on to the next one and left goes back
+    // Returns true when the choice changes
+    pub fn choice(&mut self, label: &str, options: &[&str], selected: &mut usize) -> bool {
+        let id = self.id(label);
+        let label_width = self.text_width(label);
+        let rect = self.allocate(label_width + SPACING + CHOICE_WIDTH, ROW_HEIGHT);
+        let field = Rect::new(
+            rect.max().x - CHOICE_WIDTH,
+            rect.min.y,
+            CHOICE_WIDTH,
+            rect.size.y,
+        );
+        let (hovered, clicked) = self.interact(id, rect);
+
+        let old = *selected;
+        let count = options.len();
+        if count > 0 {
+            if clicked {
+                *selected = (*selected + 1) % count;
+            }
+            if self.focus == Some(id) {
+                for nav in &self.input.nav {
+                    match nav {
+                        Nav::Left => *selected = (*selected + count - 1) % count,
+                        Nav::Right => *selected = (*selected + 1) % count,
+                        _ => (),
+                    }
+                }
+            }
+        }
+
+        let color = match (self.active == Some(id), hovered) {
+            (true, _) => ACTIVE_COLOR,
+            (false, true) => HOVER_COLOR,
+            (false, false) => WIDGET_COLOR,
+        };
+        self.text(label, rect, Align::Left, TEXT_COLOR);
+        self.rect(field, color);
+        self.focus_outline(id, field);
+        if let Some(option) = options.get(*selected) {
+            self.text(option, field, Align::Center, TEXT_COLOR);
+        }
+        *selected != old
+    }
+
+    // Rows of selectable items, returns true when the selection changes
+    pub fn list(&mut self, label: &str, items: &[String], selected: &mut Option<usize>) -> bool {
+        let old = *selected;
+        for (i, item) in items.iter().enumerate() {
+            let id = self.id(&format!("{}/{}", label, i));
+            let width = self.text_width(item) + PADDING * 2.0;
+            let rect = self.allocate(width, ROW_HEIGHT);
+            let (hovered, clicked) = self.interact(id, rect);
+            if clicked {
+                *selected = Some(i);
+            }
+
+            let color = if *selected == Some(i) {
+                SELECTED_COLOR
+            } else if hovered {
+                HOVER_COLOR
+            } else {
+                WIDGET_COLOR
+            };
+            self.rect(rect, color);
+            self.focus_outline(id, rect);
+            self.text(
+                item,
+                Rect::new(
+                    rect.min.x + PADDING,
+                    rect.min.y,
+                    rect.size.x - PADDING,
+                    rect.size.y,
+                ),
+                Align::Left,
+                TEXT_COLOR,
+            );
+        }
+        *selected != old
+    }
+
+    // Single line of editable text, typed into while it has focus
+    // Returns true when the text changes
+    pub fn text_field(&mut self, label: &str, text: &mut String) -> bool {
+        let id = self.id(label);
+        let label_width = self.text_width(label);
+        let rect = self.allocate(label_width + SPACING + TEXT_FIELD_WIDTH, ROW_HEIGHT);
+        let field = Rect::new(
+            rect.max().x - TEXT_FIELD_WIDTH,
+            rect.min.y,
+            TEXT_FIELD_WIDTH,
+            rect.size.y,
+        );
+        let (hovered, _) = self.interact(id, rect);
+
+        let mut changed = false;
+        let focused = self.focus == Some(id);
+        if focused {
+            for &c in &self.input.characters {
+                match c {
+                    // Backspace
+                    '\u{8}' => changed |= text.pop().is_some(),
+                    // Enter finishes editing
+                    '\r' | '\n' => self.focus = None,
+                    c if !c.is_control() => {
+                        text.push(c);
+                        changed = true;
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        self.typing |= self.focus == Some(id);
+        self.text(label, rect, Align::Left, TEXT_COLOR);
+        self.rect(field, if hovered { HOVER_COLOR } else { WIDGET_COLOR });
+        self.focus_outline(id, field);
+        let shown = if focused {
+            format!("{}_", text)
+        } else {
+            text.clone()
+        };
+        self.text(
+            &shown,
+            Rect::new(
+                field.min.x + PADDING / 2.0,
+                field.min.y,
+                field.size.x - PADDING,
+                field.size.y,
+            ),
+            Align::Left,
+            TEXT_COLOR,
+        );
+        changed
+    }
+
+    fn layout(&mut self) -> &mut Layout {
+        self.layouts.last_mut().unwrap()
+    }
+
+    fn allocate(&mut self, width: f32, height: f32) -> Rect {
+        self.layout().allocate(width, height)
+    }
+
+    // Ids come from the label and whatever panel the widget is in, so labels only need to be unique within a panel
+    fn id(&mut self, label: &str) -> WidgetId {
+        let mut hasher = DefaultHasher::new();
+        self.layout().id.hash(&mut hasher);
+        label.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn mouse_over(&self, rect: Rect) -> bool {
+        matches!(self.input.mouse, Some(mouse) if rect.contains(mouse))
+    }
+
+    // Shared mouse and focus handling for anything that can be clicked
+    // Returns whether the widget is hovered and whether it was clicked this frame
+    fn interact(&mut self, id: WidgetId, rect: Rect) -> (bool, bool) {
+        self.focusable.push(id);
+        let hovered = self.mouse_over(rect);
+        if hovered && self.input.mouse_pressed {
+            self.active = Some(id);
+            self.focus = Some(id);
+        }
+        let clicked = (hovered && self.input.mouse_released && self.active == Some(id))
+            || self.activate == Some(id);
+        (hovered, clicked)
+    }
+
+    fn rect(&mut self, rect: Rect, color: [f32; 4]) {
+        self.commands.push(DrawCommand::Rect { rect, color });
+    }
+
+    fn focus_outline(&mut self, id: WidgetId, rect: Rect) {
+        if self.focus != Some(id) {
+            return;
+        }
+        let Rect { min, size } = rect;
+        let width = FOCUS_OUTLINE;
+        self.rect(Rect::new(min.x, min.y, size.x, width), FOCUS_COLOR);
+        self.rect(
+            Rect::new(min.x, min.y + size.y - width, size.x, width),
+            FOCUS_COLOR,
+        );
+        self.rect(Rect::new(min.x, min.y, width, size.y), FOCUS_COLOR);
+        self.rect(
+            Rect::new(min.x + size.x - width, min.y, width, size.y),
+            FOCUS_COLOR,
+        );
+    }
+
+    fn text_width(&self, text: &str) -> f32 {
+        self.font
+            .measure(text, &TextStyle::new(TEXT_SIZE, TEXT_COLOR))
+            .x
+    }
+
+    // Text centred vertically in a rect, and aligned horizontally within it
+    fn text(&mut self, text: &str, rect: Rect, align: Align, color: [f32; 4]) {
+        let x = match align {
+            Align::Left => rect.min.x,
+            Align::Center => rect.centre().x,
+            Align::Right => rect.max().x,
+        };
+        self.commands.push(DrawCommand::Text {
+            text: text.to_owned(),
+            position: na::Vector2::new(x, rect.centre().y - TEXT_SIZE / 2.0),
+            style: TextStyle::new(TEXT_SIZE, color).align(align),
+        });
+    }
+}
@@ -0,0 +1,48 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+// The game's own directory inside each of the per-user ones
+const APP_DIR: &str = "spacevagrant";
+
+// Where saves go, in the usual place for the platform
+// Falls back to next to the executable when there's no home directory to put it in
+pub fn data_dir() -> PathBuf {
+    app_dir("XDG_DATA_HOME", ".local/share")
+}
+
+// Where the settings file goes, which is the same as the data directory everywhere but Linux and the like
+pub fn config_dir() -> PathBuf {
+    app_dir("XDG_CONFIG_HOME", ".config")
+}
+
+// Windows and macOS have one place for both, everything else follows the XDG base directory spec
+fn app_dir(xdg_var: &str, xdg_default: &str) -> PathBuf {
+    let base = if cfg!(windows) {
+        env_dir("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        home_dir().map(|home| home.join("Library/Application Support"))
+    } else {
+        env_dir(xdg_var).or_else(|| home_dir().map(|home| home.join(xdg_default)))
+    };
+    base.map(|base| base.join(APP_DIR)).unwrap_or_else(exe_dir)
+}
+
+// Relative paths in these variables are meant to be ignored
+fn env_dir(var: &str) -> Option<PathBuf> {
+    env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+}
+
+fn home_dir() -> Option<PathBuf> {
+    env_dir("HOME")
+}
+
+fn exe_dir() -> PathBuf {
+    env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+        .unwrap_or_default()
+}
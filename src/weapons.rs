@@ -0,0 +1,139 @@
+use crate::{asteroid::Asteroid, event::GameEvent, outlaw::Outlaw, player::Player, ship::SlotKind};
+
+// Seconds between volleys while the trigger is held
+const FIRE_INTERVAL: f32 = 0.25;
+// Seconds a shot flies for before it fizzles out
+const SHOT_LIFETIME: f32 = 1.5;
+// Side by side guns are spread out across the front of the ship this far apart
+const GUN_SPACING: f32 = 0.4;
+
+// A shot in flight, in world space
+#[derive(Clone, Copy, Debug)]
+pub struct Projectile {
+    pub position: na::Vector2<f32>,
+    pub velocity: na::Vector2<f32>,
+    damage: f32,
+    // Time left before it fizzles out
+    life: f32,
+}
+
+// What a shot ran into
+enum Hit {
+    Asteroid,
+    // Index into the outlaws
+    Outlaw(usize),
+}
+
+// The player's fitted weapons, all fired together while the trigger is held
+// Each gun pays for its own shot, so a ship low on energy or overheating fires fewer of them or none at all
+#[derive(Default)]
+pub struct Weapons {
+    // Time left before the next volley can go
+    cooldown: f32,
+    projectiles: Vec<Projectile>,
+}
+
+impl Weapons {
+    pub fn projectiles(&self) -> &[Projectile] {
+        &self.projectiles
+    }
+
+    pub fn update(
+        &mut self,
+        delta: f32,
+        firing: bool,
+        player: &mut Player,
+        asteroids: &[Asteroid],
+        outlaws: &mut Vec<Outlaw>,
+        events: &mut Vec<GameEvent>,
+    ) {
+        self.cooldown = (self.cooldown - delta).max(0.0);
+        if firing && self.cooldown <= 0.0 {
+            self.fire(player);
+        }
+
+        // Shots are moved along a frame at a time, and stop at the first thing in the way
+        // Rocks just soak them up, ships take damage
+        self.projectiles.retain(|projectile| {
+            let speed = projectile.velocity.norm();
+            if speed <= 0.0 {
+                return true;
+            }
+            let step = speed * delta;
+            let direction = projectile.velocity / speed;
+            let rocks = asteroids.iter().filter_map(|asteroid| {
+                asteroid
+                    .ray_hit(projectile.position, direction)
+                    .map(|distance| (Hit::Asteroid, distance))
+            });
+            let ships = outlaws.iter().enumerate().filter_map(|(i, outlaw)| {
+                outlaw
+                    .ray_hit(projectile.position, direction)
+                    .map(|distance| (Hit::Outlaw(i), distance))
+            });
+            let (hit, distance) = match rocks
+                .chain(ships)
+                .filter(|&(_, distance)| distance <= step)
+                .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+            {
+                Some(hit) => hit,
+                None => return true,
+            };
+
+            events.push(GameEvent::ShotHit(
+                projectile.position + direction * distance,
+            ));
+            if let Hit::Outlaw(i) = hit {
+                if outlaws[i].damage(projectile.damage) {
+                    let outlaw = outlaws.swap_remove(i);
+                    events.push(GameEvent::ShipDestroyed {
+                        name: outlaw.name,
+                        position: outlaw.position,
+                    });
+                }
+            }
+            false
+        });
+        for projectile in &mut self.projectiles {
+            projectile.position += projectile.velocity * delta;
+            projectile.life -= delta;
+        }
+        self.projectiles.retain(|projectile| projectile.life > 0.0);
+    }
+
+    fn fire(&mut self, player: &mut Player) {
+        let guns = player
+            .ship()
+            .modules()
+            .iter()
+            .flatten()
+            .filter(|module| module.slot == SlotKind::Weapon && module.projectile_speed > 0.0)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let heading = player.heading();
+        let right = na::Vector2::new(heading.y, -heading.x);
+        let mut fired = false;
+        for (i, gun) in guns.iter().enumerate() {
+            if !player.try_spend_energy(gun.shot_energy, gun.shot_heat) {
+                continue;
+            }
+            let offset = (i as f32 - (guns.len() - 1) as f32 / 2.0) * GUN_SPACING;
+            self.projectiles.push(Projectile {
+                position: player.position + right * offset,
+                velocity: player.velocity + heading * gun.projectile_speed,
+                damage: gun.shot_damage,
+                life: SHOT_LIFETIME,
+            });
+            fired = true;
+        }
+        if fired {
+            self.cooldown = FIRE_INTERVAL;
+        }
+    }
+
+    // Shots belong to the system they were fired in
+    pub fn clear(&mut self) {
+        self.projectiles.clear();
+    }
+}